@@ -1,416 +1,10424 @@
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers},
-    execute,
+    cursor::{RestorePosition, SavePosition},
+    event::{
+        self, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture,
+        Event, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+    },
+    execute, queue,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
+use regex::Regex;
 use std::{
+    cmp::Ordering,
+    collections::{BTreeSet, HashMap, HashSet, VecDeque},
     env,
     error::Error,
+    ffi::{OsStr, OsString},
     io,
-    path::{Path, PathBuf},
+    ops::Range,
+    path::{Component, Path, PathBuf},
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+    time::{Duration, Instant, SystemTime},
 };
 use tui::{
     backend::{Backend, CrosstermBackend},
-    layout::{Constraint, Direction, Layout},
+    buffer::{Buffer, Cell},
+    layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Span, Spans},
-    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Tabs, Widget, Wrap},
     Frame, Terminal,
 };
 
 fn main() -> Result<(), Box<dyn Error>> {
+    let options = match parse_args(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            std::process::exit(2);
+        }
+    };
+
+    let mut paths = match load_entries(&options) {
+        Ok(paths) => paths,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            std::process::exit(1);
+        }
+    };
+
+    let report = clean_pipeline(&mut paths, &options);
+
+    if let Some(file) = &options.expect {
+        let expected = match std::fs::read_to_string(file) {
+            Ok(contents) => parse_path_file(&contents),
+            Err(err) => {
+                eprintln!("Error: cannot read {}: {}", file.display(), err);
+                std::process::exit(1);
+            }
+        };
+        if let Some(drift) = drift_report(
+            &expected,
+            &paths,
+            options.ignore_order,
+            &options.dedupe_opts,
+        ) {
+            eprintln!(
+                "{} differs from {}:\n{}",
+                options.var_name(),
+                file.display(),
+                drift
+            );
+            std::process::exit(3);
+        }
+        return Ok(());
+    }
+
+    if let Some((dir, position)) = &options.move_entry {
+        if let Err(err) = move_entry(&mut paths, dir, *position, &options.dedupe_opts) {
+            eprintln!("Error: {}", err);
+            std::process::exit(1);
+        }
+        emit(&render_batch(paths, &options), &options);
+        return Ok(());
+    }
+
+    if let Some(dirs) = &options.add {
+        add_entries(&mut paths, dirs, &options.dedupe_opts);
+        emit(&render_batch(paths, &options), &options);
+        return Ok(());
+    }
+
+    if options.audit {
+        println!("{}", audit_report(&paths));
+        return Ok(());
+    }
+
+    // Non-interactive modes print the command without starting the TUI
+    if options.clean || options.dedupe {
+        if options.verbose {
+            eprintln!("{}", report);
+        }
+        emit(&render_batch(paths, &options), &options);
+        return Ok(());
+    }
+
+    let mut app = App::new(paths);
+    if let Some(file) = &options.base {
+        match std::fs::read_to_string(file) {
+            Ok(contents) => app.base = Some(parse_path_file(&contents)),
+            Err(err) => {
+                eprintln!("Error: cannot read {}: {}", file.display(), err);
+                std::process::exit(1);
+            }
+        }
+    }
+    app.output = options.clone();
+    app.keep_policy = options.dedupe_keep;
+    app.dedupe_opts = options.dedupe_opts;
+    app.var = options.var_name().to_string();
+    app.single = options.single;
+    app.hyperlinks = options.hyperlinks;
+    app.zebra = options.zebra;
+    app.literal_input = options.literal;
+    app.mounts = load_mounts();
+    app.length_limit = options.length_limit.unwrap_or(DEFAULT_LENGTH_LIMIT);
+    let config = match load_config() {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            std::process::exit(1);
+        }
+    };
+    let theme = options.theme.as_deref().or(config.theme.as_deref());
+    app.theme = match theme.and_then(Theme::named) {
+        _ if !colors_enabled() => Theme::MONOCHROME,
+        Some(theme) => theme,
+        None => Theme::default(),
+    };
+    app.shell = match &options.format {
+        OutputFormat::Shell(shell) => Some(shell.clone()),
+        OutputFormat::Auto | OutputFormat::Universal | OutputFormat::Raw => detect_shell(),
+    };
+    if options.combined {
+        match load_combined() {
+            Ok((mut system, mut user)) => {
+                clean_entries(&mut system, options.keep_empty);
+                clean_entries(&mut user, options.keep_empty);
+                app.system_len = Some(system.len());
+                app.paths = [system, user].concat();
+                app.original = app.paths.clone();
+                app.clamp_selection();
+            }
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(script) = &options.script {
+        match run_script(&mut app, script) {
+            Ok(()) => emit(&render_batch(app.enabled(&app.paths), &options), &options),
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+    app.confirm_delete = !options.no_confirm;
+    // Scripts are written against the default keys
+    app.keys = match Keys::new(&config.keys) {
+        Ok(keys) => keys,
+        Err(err) => {
+            eprintln!("Error: [keys] in the config file: {}", err);
+            std::process::exit(1);
+        }
+    };
+    app.open_tabs(&options.tabs, options.keep_empty);
+    if let Some(disabled) = config.disabled.get(&app.var) {
+        app.disabled = disabled.iter().cloned().collect();
+    }
+    if let Some(pinned) = config.pinned.get(&app.var) {
+        app.pinned = pinned.iter().cloned().collect();
+    }
+    for tab in &mut app.tabs {
+        if let Some(disabled) = config.disabled.get(&tab.var) {
+            tab.disabled = disabled.iter().cloned().collect();
+        }
+        if let Some(pinned) = config.pinned.get(&tab.var) {
+            tab.pinned = pinned.iter().cloned().collect();
+        }
+    }
+    app.config_file = config_path();
+    app.extra_suggestions = config.suggestions;
+
     // Configure terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    execute!(
+        stdout,
+        EnterAlternateScreen,
+        EnableMouseCapture,
+        EnableBracketedPaste
+    )?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    // Run application
-    let res = run_app(&mut terminal);
+    // Run application
+    let res = run_app(&mut terminal, app);
+
+    // Restore terminal
+    disable_raw_mode()?;
+    execute!(
+        terminal.backend_mut(),
+        LeaveAlternateScreen,
+        DisableMouseCapture,
+        DisableBracketedPaste
+    )?;
+    terminal.show_cursor()?;
+
+    match res {
+        Ok(command) => {
+            emit(&command, &options);
+        }
+        Err(err) => {
+            eprintln!("Error: {:?}", err);
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints the command, or writes it to the `--output` file, exiting on
+/// failure.
+fn emit(command: &str, options: &Options) {
+    match &options.output {
+        Some(file) => {
+            let file = expand_path(file);
+            if let Err(err) = write_output(&file, command) {
+                eprintln!("Error: cannot write {}: {}", file.display(), err);
+                std::process::exit(1);
+            }
+        }
+        None => println!("{}", command),
+    }
+}
+
+/// Writes the command to `file` with a trailing newline, exactly as it
+/// would be printed.
+fn write_output(file: &Path, command: &str) -> io::Result<()> {
+    std::fs::write(file, format!("{}\n", command))
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+enum OutputFormat {
+    /// Detect the target shell from `$SHELL`.
+    #[default]
+    Auto,
+    /// Emit the assignment for the named shell.
+    Shell(String),
+    /// Emit a `case "$SHELL"` snippet with one arm per supported shell.
+    Universal,
+    /// Emit the bare joined entries, for tools other than shells.
+    Raw,
+}
+
+#[derive(Debug, Default, Clone)]
+struct Options {
+    format: OutputFormat,
+    /// Load the entries from the `ENV PATH` instruction of a Dockerfile.
+    from_dockerfile: Option<PathBuf>,
+    /// Load the macOS GUI session's PATH from `launchctl getenv`.
+    from_launchctl: bool,
+    /// Keep empty elements, which some shells treat as the current directory.
+    keep_empty: bool,
+    /// Remove duplicate entries and print the command without the TUI.
+    dedupe: bool,
+    /// Like `dedupe`, also dropping entries whose directory does not exist.
+    clean: bool,
+    /// Report what the non-interactive cleanup changed on stderr.
+    verbose: bool,
+    /// Which occurrence of a duplicated entry survives deduplication.
+    dedupe_keep: KeepPolicy,
+    /// Feed the key names in this file to the editor instead of a terminal.
+    script: Option<PathBuf>,
+    /// Variable to edit instead of `PATH`.
+    var: Option<String>,
+    /// Treat the variable as a single directory rather than a list.
+    single: bool,
+    /// Emit a PowerShell command that persists the value for the user.
+    pwsh_persist: bool,
+    /// Render existing directories as clickable `file://` hyperlinks.
+    hyperlinks: bool,
+    /// Prefix the output with a comment saying it was generated by pathctl.
+    comment: bool,
+    /// Sort the entries into a canonical order in non-interactive modes.
+    canonical_order: bool,
+    /// File holding a captured "clean login" PATH to compare against.
+    base: Option<PathBuf>,
+    /// Emit zsh's tied array with `typeset -U` instead of an export.
+    zsh_array: bool,
+    /// With fish, emit `fish_add_path` for each entry added to the startup
+    /// PATH instead of reassigning it.
+    fish_add_path: bool,
+    /// Have `fish_add_path` append the entries rather than prepend them.
+    fish_append: bool,
+    /// Rules deciding when two entries count as duplicates.
+    dedupe_opts: DedupeOpts,
+    /// Edit the system and user PATH from the Windows registry side by side.
+    combined: bool,
+    /// Write the command to this file instead of stdout.
+    output: Option<PathBuf>,
+    /// Join the entries with this string in the raw format.
+    out_separator: Option<String>,
+    /// Print executable counts and shadowed executables instead of a command.
+    audit: bool,
+    /// `add` subcommand: put these directories first and print the command.
+    add: Option<Vec<PathBuf>>,
+    /// Move this directory to the given 1-based position and print the
+    /// command.
+    move_entry: Option<(PathBuf, usize)>,
+    /// Captured PATH the current one must match, for drift checks in CI.
+    expect: Option<PathBuf>,
+    /// With `expect`, only compare which entries are present.
+    ignore_order: bool,
+    /// Shade every other row of the list.
+    zebra: bool,
+    /// Warn when the joined value gets close to this many bytes.
+    length_limit: Option<usize>,
+    /// Delete with `dd` without asking first.
+    no_confirm: bool,
+    /// Store typed entries as written, without expanding `~` and variables.
+    literal: bool,
+    /// Color theme, overriding the one in the config file.
+    theme: Option<String>,
+    /// More variables to edit, each in a tab of its own.
+    tabs: Vec<String>,
+}
+
+impl Options {
+    fn var_name(&self) -> &str {
+        self.var.as_deref().unwrap_or("PATH")
+    }
+}
+
+fn parse_args<I: IntoIterator<Item = String>>(args: I) -> Result<Options, String> {
+    let mut options = Options::default();
+    let (mut move_dir, mut move_position) = (None, None);
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => {
+                let value = flag_value(&mut args, &arg)?;
+                options.format = match value.as_str() {
+                    "auto" => OutputFormat::Auto,
+                    "universal" => OutputFormat::Universal,
+                    "raw" => OutputFormat::Raw,
+                    "bash" | "zsh" | "sh" | "fish" | "csh" | "tcsh" | "pwsh" | "powershell" => {
+                        OutputFormat::Shell(value)
+                    }
+                    _ => return Err(format!("unknown format: {}", value)),
+                };
+            }
+            "--keep-empty" => options.keep_empty = true,
+            "--dedupe" => options.dedupe = true,
+            "--clean" => options.clean = true,
+            "-v" | "--verbose" => options.verbose = true,
+            "--dedupe-keep" => {
+                let value = flag_value(&mut args, &arg)?;
+                options.dedupe_keep = match value.as_str() {
+                    "first" => KeepPolicy::First,
+                    "last" => KeepPolicy::Last,
+                    _ => return Err(format!("unknown dedupe policy: {}", value)),
+                };
+                options.dedupe = true;
+            }
+            "--var" => options.var = Some(flag_value(&mut args, &arg)?),
+            "--single" => options.single = true,
+            "--pwsh-persist" => options.pwsh_persist = true,
+            "--hyperlinks" => options.hyperlinks = true,
+            "--comment" => options.comment = true,
+            "--canonical-order" => options.canonical_order = true,
+            "--zsh-array" => options.zsh_array = true,
+            "--fish-add-path" => options.fish_add_path = true,
+            "--prepend" => options.fish_append = false,
+            "--append" => options.fish_append = true,
+            "--ignore-case" => options.dedupe_opts.ignore_case = true,
+            "--match-case" => options.dedupe_opts.ignore_case = false,
+            "--unify-separators" => options.dedupe_opts.unify_separators = true,
+            "--combined" => options.combined = true,
+            "--audit" => options.audit = true,
+            "--output" => options.output = Some(PathBuf::from(flag_value(&mut args, &arg)?)),
+            "--base" => options.base = Some(PathBuf::from(flag_value(&mut args, &arg)?)),
+            "--script" => {
+                options.script = Some(PathBuf::from(flag_value(&mut args, &arg)?));
+            }
+            "--from-launchctl" => options.from_launchctl = true,
+            "--from-dockerfile" => {
+                options.from_dockerfile = Some(PathBuf::from(flag_value(&mut args, &arg)?));
+            }
+            "--out-separator" => options.out_separator = Some(flag_value(&mut args, &arg)?),
+            "--expect" => options.expect = Some(PathBuf::from(flag_value(&mut args, &arg)?)),
+            "--ignore-order" => options.ignore_order = true,
+            "--zebra" => options.zebra = true,
+            "--no-confirm" => options.no_confirm = true,
+            "--literal" => options.literal = true,
+            "--tab" => options.tabs.push(flag_value(&mut args, &arg)?),
+            "--theme" => {
+                let value = flag_value(&mut args, &arg)?;
+                if Theme::named(&value).is_none() {
+                    return Err(format!("unknown theme: {}", value));
+                }
+                options.theme = Some(value);
+            }
+            "--length-limit" => {
+                let value = flag_value(&mut args, &arg)?;
+                let limit = value
+                    .parse()
+                    .map_err(|_| format!("invalid length limit: {}", value))?;
+                options.length_limit = Some(limit);
+            }
+            "--move" => move_dir = Some(PathBuf::from(flag_value(&mut args, &arg)?)),
+            "--to" => {
+                let value = flag_value(&mut args, &arg)?;
+                match value.parse::<usize>() {
+                    Ok(position) if position > 0 => move_position = Some(position),
+                    _ => return Err(format!("--to expects a position from 1: {}", value)),
+                }
+            }
+            "add" if options.add.is_none() => options.add = Some(vec![]),
+            _ if !arg.starts_with('-') && options.add.is_some() => {
+                options
+                    .add
+                    .get_or_insert_with(Vec::new)
+                    .push(PathBuf::from(arg));
+            }
+            _ => return Err(format!("unknown argument: {}", arg)),
+        }
+    }
+    options.move_entry = match (move_dir, move_position) {
+        (Some(dir), Some(position)) => Some((dir, position)),
+        (None, None) => None,
+        _ => return Err("--move and --to must be given together".to_string()),
+    };
+    if options.add.as_ref().is_some_and(Vec::is_empty) {
+        return Err("add requires at least one directory".to_string());
+    }
+    if options.out_separator.is_some() && options.format != OutputFormat::Raw {
+        return Err("--out-separator requires --format raw".to_string());
+    }
+    Ok(options)
+}
+
+fn flag_value<I: Iterator<Item = String>>(args: &mut I, flag: &str) -> Result<String, String> {
+    args.next()
+        .ok_or_else(|| format!("{} requires a value", flag))
+}
+
+/// Returns the entries to start editing with, according to the options.
+fn load_entries(options: &Options) -> Result<Vec<PathBuf>, String> {
+    let paths = if let Some(file) = &options.from_dockerfile {
+        let contents = std::fs::read_to_string(file)
+            .map_err(|err| format!("cannot read {}: {}", file.display(), err))?;
+        let path_var = extract_dockerfile_path(&contents)
+            .ok_or_else(|| format!("no ENV PATH instruction in {}", file.display()))?;
+        env::split_paths(&path_var).collect()
+    } else if options.from_launchctl {
+        let output = launchctl_getenv(options.var_name())?;
+        let paths = parse_launchctl_output(&output);
+        if paths.is_empty() {
+            return Err(format!("launchctl has no {} set", options.var_name()));
+        }
+        paths
+    } else if options.var.is_some() || options.single {
+        get_var_entries(options.var_name(), options.single)
+    } else {
+        get_path_entries()
+    };
+    Ok(paths)
+}
+
+/// What each step of the cleanup removed.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct CleanReport {
+    duplicates: usize,
+    missing: usize,
+    empty: usize,
+    /// Number of entries before and after the cleanup.
+    before: usize,
+    after: usize,
+}
+
+impl std::fmt::Display for CleanReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "removed {} duplicates, dropped {} missing, trimmed {} empty; {}",
+            self.duplicates,
+            self.missing,
+            self.empty,
+            lookup_estimate(self.before, self.after)
+        )
+    }
+}
+
+/// Describes how many directories a shell may have to search to resolve a
+/// command (all of them when it is not found) before and after cleaning.
+fn lookup_estimate(before: usize, after: usize) -> String {
+    let mut estimate = format!("lookup dirs: {} → {}", before, after);
+    if after < before {
+        let saved = (before - after) * 100 / before;
+        estimate.push_str(&format!(" ({}% fewer)", saved));
+    }
+    estimate
+}
+
+/// Runs the cleanup steps requested in `options` over freshly loaded entries.
+/// Empty elements are always trimmed unless `--keep-empty` is given.
+fn clean_pipeline(paths: &mut Vec<PathBuf>, options: &Options) -> CleanReport {
+    let mut report = CleanReport {
+        before: paths.len(),
+        empty: clean_entries(paths, options.keep_empty),
+        ..CleanReport::default()
+    };
+    if options.clean {
+        report.missing = drop_missing(paths);
+    }
+    if options.clean || options.dedupe {
+        report.duplicates = dedupe_paths(paths, options.dedupe_keep, &options.dedupe_opts);
+    }
+    report.after = paths.len();
+    report
+}
+
+/// Puts `dirs` at the front of `paths` in the given order, removing any
+/// occurrence they already had further down.
+fn add_entries(paths: &mut Vec<PathBuf>, dirs: &[PathBuf], opts: &DedupeOpts) {
+    let keys: HashSet<OsString> = dirs.iter().map(|d| dedupe_key(d, opts)).collect();
+    paths.retain(|p| !keys.contains(&dedupe_key(p, opts)));
+    paths.splice(0..0, dirs.iter().cloned());
+}
+
+/// Moves `dir` to the 1-based `position`, clamped to the end of the list.
+/// Fails when `dir` is not in the list rather than silently doing nothing,
+/// so a script checking an ordering notices a typo.
+fn move_entry(
+    paths: &mut Vec<PathBuf>,
+    dir: &Path,
+    position: usize,
+    opts: &DedupeOpts,
+) -> Result<(), String> {
+    let key = dedupe_key(dir, opts);
+    let from = paths
+        .iter()
+        .position(|p| dedupe_key(p, opts) == key)
+        .ok_or_else(|| format!("{} is not in the list", dir.display()))?;
+    move_to(paths, from, position.saturating_sub(1));
+    Ok(())
+}
+
+/// The `pathctl add` invocation that puts `entry` first, quoted for a
+/// POSIX shell.
+fn add_command(entry: &Path) -> String {
+    format!("pathctl add {}", sh_quote(&entry.to_string_lossy()))
+}
+
+/// Drops empty elements, usually left behind by careless `PATH=$PATH:`
+/// concatenations. With `keep_empty` they are preserved as empty entries so
+/// that the "current directory" meaning survives the round trip. Returns the
+/// number of entries removed.
+fn clean_entries(paths: &mut Vec<PathBuf>, keep_empty: bool) -> usize {
+    let before = paths.len();
+    paths.retain(|p| keep_empty || !p.as_os_str().is_empty());
+    before - paths.len()
+}
+
+/// Drops entries whose directory does not exist. Empty elements stand for
+/// the current directory and are left alone, as are directories carrying a
+/// `.pathctl-ignore` marker, which exist by definition. Returns the number
+/// removed.
+fn drop_missing(paths: &mut Vec<PathBuf>) -> usize {
+    let before = paths.len();
+    paths.retain(|p| !is_missing_dir(p));
+    before - paths.len()
+}
+
+/// Whether `p` is an entry `--clean` drops: not empty, and not a directory.
+fn is_missing_dir(p: &Path) -> bool {
+    !p.as_os_str().is_empty() && !p.is_dir()
+}
+
+/// Name of the file marking a directory as exempt from cleanup.
+const IGNORE_MARKER: &str = ".pathctl-ignore";
+
+/// Whether `dir` holds a `.pathctl-ignore` marker, meaning cleanup must
+/// leave its entries alone even when they look redundant.
+fn is_pathctl_ignored(dir: &Path) -> bool {
+    !dir.as_os_str().is_empty() && dir.join(IGNORE_MARKER).is_file()
+}
+
+fn display_entry(path: &Path) -> String {
+    if path.as_os_str().is_empty() {
+        "(current directory)".to_string()
+    } else {
+        path.display().to_string()
+    }
+}
+
+struct App {
+    paths: Vec<PathBuf>,
+    list_state: ListState,
+    input_mode: InputMode,
+    input: String,
+    /// Show the joined PATH string instead of the list of entries.
+    raw_view: bool,
+    /// Which occurrence survives when removing duplicates with `D`.
+    keep_policy: KeepPolicy,
+    /// Rules deciding when two entries count as duplicates.
+    dedupe_opts: DedupeOpts,
+    /// Popup drawn over the list; the next key press dismisses it.
+    popup: Option<Popup>,
+    /// Entries parked aside while reorganizing. They are not part of the
+    /// emitted PATH until moved back.
+    scratch: Vec<PathBuf>,
+    /// Name of the variable being edited.
+    var: String,
+    /// Every variable being edited when there are several, in tab order.
+    /// The shown one's slot is stale: its state lives in the fields above.
+    tabs: Vec<Tab>,
+    /// Position of the shown variable in `tabs`.
+    tab: usize,
+    /// The variable holds one directory; inserting replaces it.
+    single: bool,
+    /// Feedback about the last action, cleared on the next key press.
+    status: Option<String>,
+    /// Feedback about the last few actions, shown until it expires.
+    messages: Messages,
+    /// Render existing directories as OSC 8 `file://` hyperlinks.
+    hyperlinks: bool,
+    /// Where the list last drew each entry to link, one row apiece.
+    links: Vec<(Rect, PathBuf)>,
+    /// First entry shown in the list, mirroring the widget's own scrolling.
+    list_offset: usize,
+    /// Where the rows of the list were last drawn, for pages and the mouse.
+    list_area: Rect,
+    /// The list as it was when a mouse drag started.
+    drag: Option<Vec<PathBuf>>,
+    /// Entries last cut with `dd` or yanked with `yy`, put back with `p`/`P`.
+    register: Vec<PathBuf>,
+    /// Indices of the entries marked with Space, which bulk commands act on.
+    marks: BTreeSet<usize>,
+    /// Where `V` started the range being marked, while marking one.
+    visual_anchor: Option<usize>,
+    /// First key of a two-key command such as `dd`, and when it was typed.
+    pending: Option<(char, Instant)>,
+    /// Count typed before a command, as in `5j`.
+    count: Option<usize>,
+    /// Captured login PATH used to tell inherited entries from added ones.
+    base: Option<Vec<PathBuf>>,
+    /// Highlight entries by origin when a base is available.
+    show_origins: bool,
+    /// Shell the output is meant for, which decides the save targets.
+    shell: Option<String>,
+    /// Targets offered by the save picker and the highlighted one.
+    save_targets: Vec<SaveTarget>,
+    save_choice: usize,
+    /// Save waiting for confirmation, and how it changes the managed block.
+    pending_save: Option<SaveTarget>,
+    save_diff: Vec<DiffLine>,
+    /// In the combined Windows view, how many leading entries belong to the
+    /// system PATH; the rest belong to the user's.
+    system_len: Option<usize>,
+    /// Root of the git repository the list is filtered to, if filtering.
+    repo_filter: Option<PathBuf>,
+    /// Text the list is narrowed to while searching, if any.
+    search: Option<String>,
+    /// Entry kept at index 0 whatever the edits, such as a version manager
+    /// shim directory.
+    mandatory_first: Option<PathBuf>,
+    /// Options the command printed on exit is rendered with.
+    output: Options,
+    /// Earlier states of the list, for undo.
+    history: History,
+    /// Highlighted snapshot in the history browser, newest first.
+    history_choice: usize,
+    /// Commands provided by more than one entry, as of the last scan.
+    shadowing: Vec<Shadowed>,
+    /// Highlighted command in the shadowing view.
+    shadow_choice: usize,
+    /// Directory shown in the directory browser, its subdirectories and the
+    /// highlighted one.
+    browse_dir: PathBuf,
+    browse_entries: Vec<PathBuf>,
+    browse_choice: usize,
+    /// Directories offered by `t` on top of `SUGGESTED_DIRS`, from the
+    /// config file.
+    extra_suggestions: Vec<PathBuf>,
+    /// Suggested directories that exist and are not listed yet, and the
+    /// highlighted one.
+    suggestions: Vec<PathBuf>,
+    suggest_choice: usize,
+    /// Text typed into the fuzzy finder and the highlighted match.
+    fuzzy_query: String,
+    fuzzy_choice: usize,
+    /// Text waiting to be sent to the clipboard by the terminal loop.
+    clipboard: Option<String>,
+    /// Command the terminal loop runs with the TUI suspended.
+    to_open: Option<Vec<OsString>>,
+    /// Keys recorded with `Q` under each register, replayed with `@`.
+    macros: HashMap<char, Vec<KeyEvent>>,
+    /// Register being recorded into and the keys pressed so far.
+    recording: Option<(char, Vec<KeyEvent>)>,
+    /// Register `@@` replays.
+    last_macro: Option<char>,
+    /// Keys `@` asked to replay once the key that asked is handled.
+    to_replay: Vec<KeyEvent>,
+    /// Set while a macro runs, so its keys count as a single edit.
+    replaying: bool,
+    /// Entries kept in the list but left out of the output.
+    disabled: HashSet<PathBuf>,
+    /// Entries `dd`, `M` and `F` leave alone unless told with `!`.
+    pinned: HashSet<PathBuf>,
+    /// Config file that disabling or pinning an entry is remembered in.
+    config_file: Option<PathBuf>,
+    /// Shade every other row of the list.
+    zebra: bool,
+    /// Colors of the screen, monochrome unless `colors_enabled`.
+    theme: Theme,
+    /// Keys remapped in the config file.
+    keys: Keys,
+    /// Length of the joined value the status bar warns about.
+    length_limit: usize,
+    /// Mounted filesystems, to flag entries on network mounts.
+    mounts: Vec<MountEntry>,
+    /// Show the detail pane beside the list.
+    show_details: bool,
+    /// Entry the detail pane sticks to instead of following the selection.
+    pinned_detail: Option<PathBuf>,
+    /// Ask before `dd` deletes anything.
+    confirm_delete: bool,
+    /// Entries waiting for the deletion, or pruning, to be confirmed.
+    pending_delete: BTreeSet<usize>,
+    /// Pattern the entries waiting for removal matched.
+    removal_pattern: String,
+    /// The pattern being typed for `F`, compiled on every keystroke, or the
+    /// reason it does not compile.
+    removal_regex: Option<Result<Regex, String>>,
+    /// Lines the help or diff popup is scrolled down by.
+    popup_scroll: u16,
+    /// Directories Tab offers for the text being typed, and the one filled in.
+    completions: Vec<String>,
+    completion_choice: usize,
+    /// Store typed entries as written rather than expanded.
+    literal_input: bool,
+    /// Which entries were found missing when last drawn.
+    missing: MissingCache,
+    /// Number of executables in each entry, counted in the background.
+    executable_counts: ExecutableCountCache,
+    /// Show how many executables each entry holds after its text.
+    show_counts: bool,
+    /// Fold runs of adjacent entries sharing a prefix into one row.
+    grouped: bool,
+    /// Prefixes whose fold was opened in the grouped view.
+    unfolded: HashSet<PathBuf>,
+    /// The list as it was when pathctl started, to count changes against.
+    original: Vec<PathBuf>,
+    /// Show the executables of the selected entry beside the list.
+    show_executables: bool,
+    /// Show what each entry resolves to after its text.
+    show_canonical: bool,
+    /// Number the entries from 1, as `12G` jumps to them.
+    show_numbers: bool,
+    /// Highlighted order in the sort popup.
+    sort_choice: usize,
+    /// Executables last listed for the preview and the entry they are of,
+    /// so the directory is only read again once the selection moves.
+    executables: Option<(PathBuf, Result<Vec<OsString>, String>)>,
+}
+
+/// A variable edited in a tab, with the state that goes with it while
+/// another one is shown.
+#[derive(Debug, Default)]
+struct Tab {
+    var: String,
+    paths: Vec<PathBuf>,
+    original: Vec<PathBuf>,
+    list_state: ListState,
+    history: History,
+    disabled: HashSet<PathBuf>,
+    pinned: HashSet<PathBuf>,
+    mandatory_first: Option<PathBuf>,
+    base: Option<Vec<PathBuf>>,
+    system_len: Option<usize>,
+}
+
+/// Most Tab completions listed at once.
+const COMPLETION_ROWS: u16 = 8;
+
+/// Rows one notch of the mouse wheel scrolls by.
+const SCROLL_ROWS: usize = 3;
+
+/// Orders `s` can sort the list by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SortOrder {
+    Alphabetical,
+    /// Existing directories before missing entries.
+    ExistingFirst,
+    /// Entries with the most executables first.
+    MostExecutables,
+    /// Most recently accessed directories first.
+    RecentAccess,
+}
+
+impl SortOrder {
+    const ALL: [SortOrder; 4] = [
+        SortOrder::Alphabetical,
+        SortOrder::ExistingFirst,
+        SortOrder::MostExecutables,
+        SortOrder::RecentAccess,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            SortOrder::Alphabetical => "alphabetically",
+            SortOrder::ExistingFirst => "existing directories first",
+            SortOrder::MostExecutables => "most executables first",
+            SortOrder::RecentAccess => "most recently accessed first",
+        }
+    }
+}
+
+/// How long the first key of a two-key command waits for the second one.
+const PENDING_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Popup {
+    Histogram,
+    /// What the list would look like with the selected entry moved first.
+    FrontPreview,
+    /// Choose where `w` saves the list.
+    SaveTargets,
+    /// Confirm replacing an existing managed block, showing the diff.
+    ConfirmSave,
+    /// The command that would be printed on exit.
+    Command,
+    /// Earlier states of the list to jump back to.
+    History,
+    /// Confirm deleting the entries picked with `dd`.
+    ConfirmDelete,
+    /// Every key binding.
+    Help,
+    /// Pick the order to sort the list by.
+    Sort,
+    /// Confirm removing the missing directories listed.
+    Prune,
+    /// Fuzzy finder to jump to an entry.
+    Fuzzy,
+    /// Confirm inserting a directory that does not exist yet.
+    ConfirmAdd,
+    /// Confirm removing the entries matching a pattern.
+    RemoveMatching,
+    /// Remove or absolutize the entries resolved against the current
+    /// directory.
+    Relative,
+    /// Commands found in several entries and which one wins.
+    Shadowing,
+    /// The list pathctl started with beside the edited one.
+    Diff,
+    /// Pick a directory to insert by walking the filesystem.
+    Browse,
+    /// Well-known tool directories that exist but are not listed.
+    Suggest,
+}
+
+/// How many edits can be undone.
+const HISTORY_LIMIT: usize = 100;
+
+/// The list as it was before an edit.
+#[derive(Debug)]
+struct Snapshot {
+    paths: Vec<PathBuf>,
+    /// What the edit did, e.g. "remove /usr/bin".
+    description: String,
+    at: Instant,
+}
+
+/// Snapshots taken before each edit, oldest first, and the states undone
+/// edits led to, most recently undone last.
+#[derive(Debug, Default)]
+struct History {
+    snapshots: Vec<Snapshot>,
+    redo: Vec<Snapshot>,
+    /// Bumped by every undo and redo, so they are not mistaken for edits.
+    travels: usize,
+}
+
+impl History {
+    fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    fn push(&mut self, snapshot: Snapshot) {
+        if self.snapshots.len() == HISTORY_LIMIT {
+            self.snapshots.remove(0);
+        }
+        self.snapshots.push(snapshot);
+    }
+
+    /// Records the list as it was before a new edit, which also forgets
+    /// whatever could be redone.
+    fn record(&mut self, paths: Vec<PathBuf>, description: String) {
+        self.redo.clear();
+        self.push(Snapshot {
+            paths,
+            description,
+            at: Instant::now(),
+        });
+    }
+
+    /// Undoes the last `steps` edits from the `current` list and returns the
+    /// list as it was before the oldest of them.
+    fn rewind(&mut self, steps: usize, current: Vec<PathBuf>) -> Option<Vec<PathBuf>> {
+        if steps == 0 || steps > self.snapshots.len() {
+            return None;
+        }
+        self.travels += 1;
+        let mut state = current;
+        for snapshot in self.snapshots.drain(self.snapshots.len() - steps..).rev() {
+            self.redo.push(Snapshot {
+                paths: std::mem::replace(&mut state, snapshot.paths),
+                ..snapshot
+            });
+        }
+        Some(state)
+    }
+
+    /// Redoes the last undone edit from the `current` list, returning the
+    /// list after it and what the edit did.
+    fn redo(&mut self, current: Vec<PathBuf>) -> Option<(Vec<PathBuf>, String)> {
+        let next = self.redo.pop()?;
+        self.travels += 1;
+        self.push(Snapshot {
+            paths: current,
+            description: next.description.clone(),
+            at: Instant::now(),
+        });
+        Some((next.paths, next.description))
+    }
+}
+
+/// Summarizes how `after` differs from `before` for the history browser.
+fn describe_change(before: &[PathBuf], after: &[PathBuf]) -> String {
+    let added: Vec<&PathBuf> = after.iter().filter(|p| !before.contains(p)).collect();
+    let removed: Vec<&PathBuf> = before.iter().filter(|p| !after.contains(p)).collect();
+    match (added.as_slice(), removed.as_slice()) {
+        ([], []) if before.len() == after.len() => "reorder".to_string(),
+        ([added], []) => format!("insert {}", display_entry(added)),
+        ([], [removed]) => format!("remove {}", display_entry(removed)),
+        ([added], [removed]) => format!(
+            "change {} → {}",
+            display_entry(removed),
+            display_entry(added)
+        ),
+        _ => format!("change {} → {} entries", before.len(), after.len()),
+    }
+}
+
+/// Turns a `describe_change` description into feedback on what was done.
+fn past_tense(description: &str) -> String {
+    let (verb, rest) = description.split_once(' ').unwrap_or((description, ""));
+    let verb = match verb {
+        "insert" => "inserted",
+        "remove" => "removed",
+        "change" => "changed",
+        "reorder" => "reordered",
+        verb => verb,
+    };
+    format!("{} {}", verb, rest).trim_end().to_string()
+}
+
+/// How long a message stays in the footer.
+const MESSAGE_TTL: Duration = Duration::from_secs(4);
+
+/// Most messages shown at once.
+const MESSAGE_LIMIT: usize = 3;
+
+/// Feedback about recent actions, each kept until it is `MESSAGE_TTL` old.
+#[derive(Default)]
+struct Messages {
+    queue: VecDeque<(String, Instant)>,
+}
+
+impl Messages {
+    fn push(&mut self, text: String) {
+        // Repeating an action refreshes its message rather than stacking it
+        self.queue.retain(|(queued, _)| *queued != text);
+        self.queue.push_back((text, Instant::now()));
+        while self.queue.len() > MESSAGE_LIMIT {
+            self.queue.pop_front();
+        }
+    }
+
+    /// Messages that have not expired by `now`, newest first.
+    fn current(&self, now: Instant) -> Vec<&str> {
+        self.queue
+            .iter()
+            .rev()
+            .filter(|(_, at)| now.duration_since(*at) < MESSAGE_TTL)
+            .map(|(text, _)| text.as_str())
+            .collect()
+    }
+}
+
+impl App {
+    fn new(paths: Vec<PathBuf>) -> App {
+        let mut list_state = ListState::default();
+        if !paths.is_empty() {
+            list_state.select(Some(0));
+        }
+        App {
+            original: paths.clone(),
+            show_executables: false,
+            show_canonical: false,
+            show_numbers: true,
+            sort_choice: 0,
+            executables: None,
+            paths,
+            list_state,
+            input_mode: InputMode::Normal,
+            input: String::new(),
+            raw_view: false,
+            keep_policy: KeepPolicy::First,
+            dedupe_opts: DedupeOpts::default(),
+            popup: None,
+            scratch: Vec::new(),
+            var: "PATH".to_string(),
+            tabs: Vec::new(),
+            tab: 0,
+            single: false,
+            status: None,
+            messages: Messages::default(),
+            hyperlinks: false,
+            links: Vec::new(),
+            list_offset: 0,
+            list_area: Rect::default(),
+            drag: None,
+            register: Vec::new(),
+            marks: BTreeSet::new(),
+            visual_anchor: None,
+            pending: None,
+            count: None,
+            base: None,
+            show_origins: true,
+            shell: None,
+            save_targets: Vec::new(),
+            save_choice: 0,
+            pending_save: None,
+            save_diff: Vec::new(),
+            system_len: None,
+            repo_filter: None,
+            search: None,
+            mandatory_first: None,
+            output: Options::default(),
+            history: History::default(),
+            history_choice: 0,
+            shadowing: Vec::new(),
+            shadow_choice: 0,
+            browse_dir: PathBuf::new(),
+            browse_entries: Vec::new(),
+            browse_choice: 0,
+            extra_suggestions: Vec::new(),
+            suggestions: Vec::new(),
+            suggest_choice: 0,
+            fuzzy_query: String::new(),
+            fuzzy_choice: 0,
+            clipboard: None,
+            to_open: None,
+            macros: HashMap::new(),
+            recording: None,
+            last_macro: None,
+            to_replay: Vec::new(),
+            replaying: false,
+            disabled: HashSet::new(),
+            pinned: HashSet::new(),
+            config_file: None,
+            zebra: false,
+            theme: Theme::default(),
+            keys: Keys::default(),
+            length_limit: DEFAULT_LENGTH_LIMIT,
+            confirm_delete: false,
+            pending_delete: BTreeSet::new(),
+            removal_pattern: String::new(),
+            removal_regex: None,
+            popup_scroll: 0,
+            completions: Vec::new(),
+            completion_choice: 0,
+            literal_input: false,
+            missing: MissingCache::default(),
+            executable_counts: ExecutableCountCache::new(),
+            show_counts: false,
+            grouped: false,
+            unfolded: HashSet::new(),
+            mounts: Vec::new(),
+            show_details: false,
+            pinned_detail: None,
+        }
+    }
+
+    /// Keeps the selection within bounds after entries have been removed.
+    fn clamp_selection(&mut self) {
+        if self.paths.is_empty() {
+            self.list_state.select(None);
+        } else {
+            let selected = self.list_state.selected().unwrap_or(0);
+            self.list_state
+                .select(Some(selected.min(self.paths.len() - 1)));
+        }
+    }
+
+    /// Sorts the entries by `order`, keeping the selection on the same entry.
+    /// Entries that compare equal keep their relative order. Returns how many
+    /// entries were judged by their modification time for want of a usable
+    /// access time.
+    fn sort_entries(&mut self, order: SortOrder) -> usize {
+        let mut fallbacks = 0;
+        let selected = self.list_state.selected().map(|i| self.paths[i].clone());
+        match order {
+            SortOrder::Alphabetical => self.paths.sort(),
+            SortOrder::ExistingFirst => self.paths.sort_by_key(|p| !p.is_dir()),
+            SortOrder::MostExecutables => self
+                .paths
+                .sort_by_cached_key(|p| std::cmp::Reverse(executables(p).map_or(0, |n| n.len()))),
+            SortOrder::RecentAccess => {
+                let times: Vec<Option<(SystemTime, bool)>> = self
+                    .paths
+                    .iter()
+                    .map(|p| dir_atime(p, &self.mounts))
+                    .collect();
+                fallbacks = times.iter().flatten().filter(|(_, mtime)| *mtime).count();
+                let mut entries: Vec<(PathBuf, Option<SystemTime>)> = self
+                    .paths
+                    .drain(..)
+                    .zip(times.into_iter().map(|time| time.map(|(time, _)| time)))
+                    .collect();
+                entries.sort_by(|a, b| compare_recency(a.1, b.1));
+                self.paths = entries.into_iter().map(|(p, _)| p).collect();
+            }
+        }
+        if let Some(selected) = selected {
+            self.list_state
+                .select(self.paths.iter().position(|p| *p == selected));
+        }
+        fallbacks
+    }
+
+    /// Indices of the entries shown in the list, which is all of them unless
+    /// the list is filtered to a repository.
+    fn visible(&self) -> Vec<usize> {
+        let folded = self.folded();
+        (0..self.paths.len())
+            .filter(|&i| match &self.repo_filter {
+                Some(root) => under_repo(&self.paths[i], root),
+                None => true,
+            })
+            .filter(|&i| match &self.search {
+                Some(query) => matches_search(&self.paths[i], query),
+                None => true,
+            })
+            .filter(|&i| {
+                !folded
+                    .iter()
+                    .any(|(_, run)| i > run.start && run.contains(&i))
+            })
+            .collect()
+    }
+
+    /// The runs of entries folded away in the grouped view, by prefix. The
+    /// first entry of each run stands for all of them.
+    fn folded(&self) -> Vec<(PathBuf, Range<usize>)> {
+        if !self.grouped {
+            return Vec::new();
+        }
+        let home = env::var_os("HOME").or_else(|| env::var_os("USERPROFILE"));
+        fold_runs(&self.paths, home.as_deref().map(Path::new))
+            .into_iter()
+            .filter(|(prefix, _)| !self.unfolded.contains(prefix))
+            .collect()
+    }
+
+    /// The `fold_prefix` of each of the entries at `indices`.
+    fn fold_prefixes(&self, indices: &[usize]) -> Vec<Option<PathBuf>> {
+        let home = env::var_os("HOME").or_else(|| env::var_os("USERPROFILE"));
+        indices
+            .iter()
+            .map(|&i| fold_prefix(&self.paths[i], home.as_deref().map(Path::new)))
+            .collect()
+    }
+
+    /// The prefix and run of the group holding the selection in the
+    /// grouped view, folded or not.
+    fn selected_group(&self) -> Option<(PathBuf, Range<usize>)> {
+        let selected = self.list_state.selected()?;
+        let home = env::var_os("HOME").or_else(|| env::var_os("USERPROFILE"));
+        fold_runs(&self.paths, home.as_deref().map(Path::new))
+            .into_iter()
+            .find(|(_, run)| run.contains(&selected))
+    }
+
+    /// Indices of the shown entries matching the fuzzy finder query, best
+    /// match first.
+    fn fuzzy_matches(&self) -> Vec<usize> {
+        let mut scored: Vec<(i64, usize)> = self
+            .visible()
+            .into_iter()
+            .filter_map(|i| {
+                fuzzy_match(&self.paths[i].to_string_lossy(), &self.fuzzy_query)
+                    .map(|(score, _)| (score, i))
+            })
+            .collect();
+        scored.sort_by_key(|&(score, i)| (std::cmp::Reverse(score), i));
+        scored.into_iter().map(|(_, i)| i).collect()
+    }
+
+    /// Moves every marked entry one place up or down, keeping them marked.
+    /// Marked entries already at the edge hold the others back.
+    fn move_marked(&mut self, up: bool) {
+        let marked = self.take_marked();
+        let mut moved = BTreeSet::new();
+        let order: Vec<usize> = match up {
+            true => marked.iter().copied().collect(),
+            false => marked.iter().rev().copied().collect(),
+        };
+        for i in order {
+            let target = match up {
+                true => i.checked_sub(1),
+                false => Some(i + 1).filter(|&t| t < self.paths.len()),
+            };
+            match target.filter(|t| !moved.contains(t)) {
+                Some(t) => {
+                    self.paths.swap(i, t);
+                    if self.list_state.selected() == Some(i) {
+                        self.list_state.select(Some(t));
+                    }
+                    moved.insert(t);
+                }
+                None => {
+                    moved.insert(i);
+                }
+            }
+        }
+        self.marks = moved;
+    }
+
+    /// Selects another shown row, given as a function of the selected row
+    /// and the number of rows. Out of range rows select the last one.
+    fn select_row(&mut self, row: impl FnOnce(usize, usize) -> usize) {
+        let visible = self.visible();
+        if visible.is_empty() {
+            return;
+        }
+        let current = self
+            .list_state
+            .selected()
+            .and_then(|selected| visible.iter().position(|&i| i == selected))
+            .unwrap_or(0);
+        let row = row(current, visible.len()).min(visible.len() - 1);
+        self.list_state.select(Some(visible[row]));
+    }
+
+    /// Selects the entry numbered `number` in the list, counting from 1,
+    /// dropping a search that hides it.
+    fn jump_to_number(&mut self, number: usize) {
+        let Some(index) = number.checked_sub(1).filter(|&i| i < self.paths.len()) else {
+            self.status = Some(format!("no entry {}", number));
+            return;
+        };
+        if !self.visible().contains(&index) {
+            self.search = None;
+        }
+        if !self.visible().contains(&index) {
+            self.status = Some(format!("entry {} is outside this repo", number));
+            return;
+        }
+        self.list_state.select(Some(index));
+    }
+
+    /// Asks the terminal loop to open the selected entry with `opener`.
+    fn open_selected(&mut self, opener: Opener) {
+        let Some(selected) = self.list_state.selected() else {
+            return;
+        };
+        let dir = match self.paths[selected].as_os_str().is_empty() {
+            true => Path::new("."),
+            false => self.paths[selected].as_path(),
+        };
+        if !dir.is_dir() {
+            self.status = Some(missing_reason(dir).to_string());
+            return;
+        }
+        let editor = env::var_os("VISUAL")
+            .filter(|editor| !editor.is_empty())
+            .or_else(|| env::var_os("EDITOR"));
+        match open_command(opener, dir, editor.as_deref()) {
+            Some(command) => self.to_open = Some(command),
+            None => {
+                self.status = Some("set $VISUAL or $EDITOR to open entries in an editor".into())
+            }
+        }
+    }
+
+    /// Shows the subdirectories of `dir` in the directory browser, keeping
+    /// the current directory when it cannot be read.
+    fn browse(&mut self, dir: PathBuf) {
+        match subdirectories(&dir) {
+            Ok(entries) => {
+                self.browse_entries = entries;
+                self.browse_dir = dir;
+                self.browse_choice = 0;
+            }
+            Err(err) => self.status = Some(format!("cannot read {}: {}", dir.display(), err)),
+        }
+    }
+
+    /// Rows a page scrolls by.
+    fn page(&self) -> usize {
+        (self.list_area.height as usize).max(1)
+    }
+
+    /// Cuts the entries at `targets` into the register, leaving the selection
+    /// where the first of them was.
+    fn cut(&mut self, targets: &BTreeSet<usize>) {
+        if targets.is_empty() {
+            return;
+        }
+        self.take_marked();
+        self.register = remove_indices(&mut self.paths, targets);
+        if self.register.len() > 1 {
+            self.status = Some(format!("deleted {} entries", self.register.len()));
+        }
+        self.list_state.select(targets.first().copied());
+        self.clamp_selection();
+    }
+
+    /// Entries bulk commands act on: the marks plus the range being marked.
+    fn marked(&self) -> BTreeSet<usize> {
+        let mut marked = self.marks.clone();
+        if let (Some(anchor), Some(selected)) = (self.visual_anchor, self.list_state.selected()) {
+            marked.extend(anchor.min(selected)..=anchor.max(selected));
+        }
+        marked
+    }
+
+    /// Takes the entries bulk commands act on, ending any range marking.
+    fn take_marked(&mut self) -> BTreeSet<usize> {
+        let marked = self.marked();
+        self.marks.clear();
+        self.visual_anchor = None;
+        marked
+    }
+
+    /// Whether some entries are hidden from the list.
+    fn filtering(&self) -> bool {
+        self.repo_filter.is_some() || self.search.is_some() || self.grouped
+    }
+
+    /// Moves the selection to the closest shown entry if it is hidden.
+    fn select_visible(&mut self) {
+        let visible = self.visible();
+        let selected = self.list_state.selected();
+        if !matches!(selected, Some(i) if visible.contains(&i)) {
+            let closest = visible
+                .iter()
+                .find(|&&i| Some(i) > selected)
+                .or_else(|| visible.last());
+            self.list_state.select(closest.copied());
+        }
+    }
+
+    /// The command printed on exit: the one for the variable pathctl
+    /// started with, then one for each other tab that was changed.
+    fn command(&self) -> String {
+        if self.tabs.is_empty() {
+            return render_command(&self.enabled(&self.paths), &self.output);
+        }
+        let mut commands = Vec::new();
+        for (i, tab) in self.tabs.iter().enumerate() {
+            let (var, paths, original, disabled) = match i == self.tab {
+                true => (&self.var, &self.paths, &self.original, &self.disabled),
+                false => (&tab.var, &tab.paths, &tab.original, &tab.disabled),
+            };
+            if i > 0 && paths == original && disabled.is_empty() {
+                continue;
+            }
+            let enabled: Vec<PathBuf> = paths
+                .iter()
+                .filter(|p| !disabled.contains(*p))
+                .cloned()
+                .collect();
+            let options = Options {
+                var: Some(var.clone()),
+                ..self.output.clone()
+            };
+            commands.push(render_command(&enabled, &options));
+        }
+        commands.join("\n")
+    }
+
+    /// Adds a tab for each of `vars` next to the variable already shown.
+    fn open_tabs(&mut self, vars: &[String], keep_empty: bool) {
+        if vars.is_empty() {
+            return;
+        }
+        self.tabs.push(Tab {
+            var: self.var.clone(),
+            ..Tab::default()
+        });
+        for var in vars {
+            let mut paths = get_var_entries(var, false);
+            clean_entries(&mut paths, keep_empty);
+            let mut list_state = ListState::default();
+            list_state.select(Some(0).filter(|_| !paths.is_empty()));
+            self.tabs.push(Tab {
+                var: var.clone(),
+                original: paths.clone(),
+                paths,
+                list_state,
+                ..Tab::default()
+            });
+        }
+    }
+
+    /// Shows the variable of tab `to`, keeping the state of the one shown
+    /// so far in its tab.
+    fn switch_tab(&mut self, to: usize) {
+        if to >= self.tabs.len() || to == self.tab {
+            return;
+        }
+        let mut tabs = std::mem::take(&mut self.tabs);
+        self.exchange_tab(&mut tabs[self.tab]);
+        self.exchange_tab(&mut tabs[to]);
+        self.tabs = tabs;
+        self.tab = to;
+        // What is marked, searched for or cached was about the other list
+        self.marks.clear();
+        self.visual_anchor = None;
+        self.search = None;
+        self.repo_filter = None;
+        self.pinned_detail = None;
+        self.list_offset = 0;
+        self.executables = None;
+    }
+
+    fn exchange_tab(&mut self, tab: &mut Tab) {
+        std::mem::swap(&mut self.var, &mut tab.var);
+        std::mem::swap(&mut self.paths, &mut tab.paths);
+        std::mem::swap(&mut self.original, &mut tab.original);
+        std::mem::swap(&mut self.list_state, &mut tab.list_state);
+        std::mem::swap(&mut self.history, &mut tab.history);
+        std::mem::swap(&mut self.disabled, &mut tab.disabled);
+        std::mem::swap(&mut self.pinned, &mut tab.pinned);
+        std::mem::swap(&mut self.mandatory_first, &mut tab.mandatory_first);
+        std::mem::swap(&mut self.base, &mut tab.base);
+        std::mem::swap(&mut self.system_len, &mut tab.system_len);
+    }
+
+    /// The entries of `paths` that are not disabled.
+    fn enabled(&self, paths: &[PathBuf]) -> Vec<PathBuf> {
+        paths
+            .iter()
+            .filter(|p| !self.disabled.contains(*p))
+            .cloned()
+            .collect()
+    }
+
+    /// Swaps the selected entry with the one above, keeping it selected.
+    /// With entries marked, moves all of them instead.
+    fn move_selected_up(&mut self) {
+        if !self.marked().is_empty() {
+            return self.move_marked(true);
+        }
+        if let Some(selected) = self.list_state.selected().filter(|&i| i > 0) {
+            self.paths.swap(selected, selected - 1);
+            self.list_state.select(Some(selected - 1));
+        }
+    }
+
+    /// Swaps the selected entry with the one below, keeping it selected.
+    fn move_selected_down(&mut self) {
+        if !self.marked().is_empty() {
+            return self.move_marked(false);
+        }
+        if let Some(selected) = self.list_state.selected() {
+            if selected + 1 < self.paths.len() {
+                self.paths.swap(selected, selected + 1);
+                self.list_state.select(Some(selected + 1));
+            }
+        }
+    }
+
+    /// Inserts the register's entries next to the selection, selecting the
+    /// first of them.
+    fn put(&mut self, insertion_point: InsertionPoint) {
+        let mut first = None;
+        for path in self.register.clone() {
+            let point = match first {
+                Some(_) => InsertionPoint::After,
+                None => insertion_point,
+            };
+            insert_path_at_selection(&mut self.paths, &mut self.list_state, path, point);
+            first = first.or(self.list_state.selected());
+        }
+        if first.is_some() {
+            self.list_state.select(first);
+        }
+    }
+
+    /// Applies a key press to the application state. Returns `true` when the
+    /// user asked to quit.
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        self.status = None;
+        if let Some((_, keys)) = self.recording.as_mut().filter(|_| !self.replaying) {
+            keys.push(key);
+        }
+        let before = self.paths.clone();
+        let travels = self.history.travels;
+        let (mut len, mut selected) = (self.paths.len(), self.list_state.selected());
+        let mut marks = self.marks.clone();
+        let tab = self.tab;
+        let quit = match self.input_mode {
+            _ if self.popup.is_some() => {
+                if let Some(popup) = self.popup.take() {
+                    self.handle_popup_key(popup, key);
+                }
+                false
+            }
+            InputMode::Normal => match self.keys.translate(key) {
+                Some(key) => self.handle_normal_mode(key),
+                None => false,
+            },
+            InputMode::InsertAfter => {
+                self.handle_input_mode(key, InsertionPoint::After);
+                false
+            }
+            InputMode::InsertBefore => {
+                self.handle_input_mode(key, InsertionPoint::Before);
+                false
+            }
+            InputMode::InsertFirst => {
+                self.handle_input_mode(key, InsertionPoint::First);
+                false
+            }
+            InputMode::InsertLast => {
+                self.handle_input_mode(key, InsertionPoint::Last);
+                false
+            }
+            InputMode::Paste => {
+                self.handle_paste_mode(key);
+                false
+            }
+            InputMode::ExportScript => {
+                self.handle_export_mode(key);
+                false
+            }
+            InputMode::Sibling => {
+                self.handle_sibling_mode(key);
+                false
+            }
+            InputMode::Edit => {
+                self.handle_edit_mode(key);
+                false
+            }
+            InputMode::Search => {
+                self.handle_search_mode(key);
+                false
+            }
+            InputMode::Which => {
+                self.handle_which_mode(key);
+                false
+            }
+            InputMode::RemoveMatching => {
+                self.handle_remove_matching_mode(key);
+                false
+            }
+        };
+        if !self.to_replay.is_empty() {
+            // Each key tidies up after itself, only the record is left
+            self.replaying = true;
+            for key in std::mem::take(&mut self.to_replay) {
+                if self.handle_key(key) {
+                    break;
+                }
+            }
+            self.replaying = false;
+            self.status = None;
+            (len, selected) = (self.paths.len(), self.list_state.selected());
+            marks = self.marks.clone();
+        }
+        if self.tab != tab {
+            // Another list is shown, nothing was edited
+            return quit;
+        }
+        self.keep_invariants(len, selected);
+        if self.paths != before && self.marks == marks {
+            // Marks are indices, which an edit unaware of them invalidates
+            self.marks.clear();
+            self.visual_anchor = None;
+        }
+        if self.filtering() {
+            // Edits may leave the selection on an entry that is not shown
+            self.select_visible();
+        }
+        if self.replaying {
+            return quit;
+        }
+        if let Some(status) = &self.status {
+            self.messages.push(status.clone());
+        } else if self.paths != before && self.history.travels == travels {
+            self.messages.push(format!(
+                "{} (press {} to undo)",
+                past_tense(&describe_change(&before, &self.paths)),
+                self.keys.labels(&["u"])
+            ));
+        }
+        if self.paths != before && self.history.travels == travels {
+            let description = describe_change(&before, &self.paths);
+            self.history.record(before, description);
+        }
+        quit
+    }
+
+    /// Restores what every edit must leave true, given the length and the
+    /// selection from before it: the system/user boundary follows the
+    /// entries added or removed, and `mandatory_first` stays on top.
+    fn keep_invariants(&mut self, len: usize, selected: Option<usize>) {
+        if let Some(system_len) = self.system_len {
+            self.system_len = Some(shift_boundary(system_len, selected, len, self.paths.len()));
+        }
+        if let Some(first) = &self.mandatory_first {
+            if let Some(from) = pin_first(&mut self.paths, first) {
+                // Keep the selection on the same entry
+                match self.list_state.selected() {
+                    Some(i) if i == from => self.list_state.select(Some(0)),
+                    Some(i) if i < from => self.list_state.select(Some(i + 1)),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Applies a mouse event: clicking a row selects it, dragging moves the
+    /// selected entry along with the pointer, and the wheel scrolls.
+    fn handle_mouse(&mut self, mouse: MouseEvent) {
+        if self.popup.is_some() || !matches!(self.input_mode, InputMode::Normal) {
+            return;
+        }
+        let visible = self.visible();
+        let area = self.list_area;
+        let inside = (area.x..area.x + area.width).contains(&mouse.column)
+            && (area.y..area.y + area.height).contains(&mouse.row);
+        let row = Some(self.list_offset + mouse.row.saturating_sub(area.y) as usize)
+            .filter(|&row| inside && row < visible.len());
+        match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                if let Some(row) = row {
+                    self.list_state.select(Some(visible[row]));
+                    self.drag = Some(self.paths.clone());
+                }
+            }
+            MouseEventKind::Drag(MouseButton::Left) if self.drag.is_some() => {
+                if let (Some(row), Some(selected)) = (row, self.list_state.selected()) {
+                    move_to(&mut self.paths, selected, visible[row]);
+                    self.list_state.select(Some(visible[row]));
+                    self.keep_invariants(self.paths.len(), Some(selected));
+                }
+            }
+            MouseEventKind::Up(MouseButton::Left) => {
+                // The whole drag is one edit
+                if let Some(before) = self.drag.take().filter(|before| *before != self.paths) {
+                    self.marks.clear();
+                    self.visual_anchor = None;
+                    let description = describe_change(&before, &self.paths);
+                    self.history.record(before, description);
+                }
+            }
+            MouseEventKind::ScrollDown => self.select_row(|row, _| row + SCROLL_ROWS),
+            MouseEventKind::ScrollUp => self.select_row(|row, _| row.saturating_sub(SCROLL_ROWS)),
+            _ => {}
+        }
+    }
+
+    /// Goes back `steps` edits, as far as the history allows. Returns the
+    /// number of edits undone.
+    fn undo(&mut self, steps: usize) -> usize {
+        let steps = steps.min(self.history.len());
+        if let Some(paths) = self.history.rewind(steps, self.paths.clone()) {
+            self.paths = paths;
+            self.clamp_selection();
+        }
+        steps
+    }
+
+    /// Reapplies the last undone edit, if any.
+    fn redo(&mut self) {
+        match self.history.redo(self.paths.clone()) {
+            Some((paths, description)) => {
+                self.paths = paths;
+                self.clamp_selection();
+                self.status = Some(format!("redid {}", description));
+            }
+            None => self.status = Some("nothing to redo".to_string()),
+        }
+    }
+
+    /// Handles a key while a popup is shown. Popups close on any key unless
+    /// they handle it themselves.
+    fn handle_popup_key(&mut self, popup: Popup, key: KeyEvent) {
+        match (popup, key.code) {
+            (Popup::FrontPreview, KeyCode::Enter) => {
+                if let Some(selected) = self.list_state.selected() {
+                    move_to(&mut self.paths, selected, 0);
+                    self.list_state.select(Some(0));
+                }
+            }
+            (Popup::SaveTargets, KeyCode::Up | KeyCode::Char('k')) => {
+                self.save_choice = self.save_choice.saturating_sub(1);
+                self.popup = Some(popup);
+            }
+            (Popup::SaveTargets, KeyCode::Down | KeyCode::Char('j')) => {
+                self.save_choice = (self.save_choice + 1).min(self.save_targets.len() - 1);
+                self.popup = Some(popup);
+            }
+            (Popup::SaveTargets, KeyCode::Enter) => {
+                let target = self.save_targets[self.save_choice].clone();
+                self.begin_save(target);
+            }
+            (Popup::History, KeyCode::Up | KeyCode::Char('k')) => {
+                self.history_choice = self.history_choice.saturating_sub(1);
+                self.popup = Some(popup);
+            }
+            (Popup::History, KeyCode::Down | KeyCode::Char('j')) => {
+                self.history_choice = (self.history_choice + 1).min(self.history.len() - 1);
+                self.popup = Some(popup);
+            }
+            (Popup::History, KeyCode::Enter) => {
+                let undone = self.undo(self.history_choice + 1);
+                self.status = Some(format!("undid {} edits", undone));
+            }
+            (Popup::Shadowing, KeyCode::Up | KeyCode::Char('k')) => {
+                self.shadow_choice = self.shadow_choice.saturating_sub(1);
+                self.popup = Some(popup);
+            }
+            (Popup::Shadowing, KeyCode::Down | KeyCode::Char('j')) => {
+                self.shadow_choice = (self.shadow_choice + 1).min(self.shadowing.len() - 1);
+                self.popup = Some(popup);
+            }
+            (Popup::Shadowing, KeyCode::Enter) => {
+                // Show the winner among the entries it shadows
+                if let Some(shadowed) = self.shadowing.get(self.shadow_choice) {
+                    self.list_state.select(Some(shadowed.winner));
+                    self.marks = shadowed.shadowed.iter().copied().collect();
+                    self.visual_anchor = None;
+                }
+            }
+            (Popup::Browse, KeyCode::Up | KeyCode::Char('k')) => {
+                self.browse_choice = self.browse_choice.saturating_sub(1);
+                self.popup = Some(popup);
+            }
+            (Popup::Browse, KeyCode::Down | KeyCode::Char('j')) => {
+                let last = self.browse_entries.len().saturating_sub(1);
+                self.browse_choice = (self.browse_choice + 1).min(last);
+                self.popup = Some(popup);
+            }
+            (Popup::Browse, KeyCode::Enter | KeyCode::Right | KeyCode::Char('l')) => {
+                if let Some(dir) = self.browse_entries.get(self.browse_choice) {
+                    self.browse(dir.clone());
+                }
+                self.popup = Some(popup);
+            }
+            (Popup::Browse, KeyCode::Backspace | KeyCode::Left | KeyCode::Char('h')) => {
+                if let Some(parent) = self.browse_dir.parent() {
+                    let from = self.browse_dir.clone();
+                    self.browse(parent.to_path_buf());
+                    // Keep the directory just left highlighted
+                    if let Some(i) = self.browse_entries.iter().position(|dir| *dir == from) {
+                        self.browse_choice = i;
+                    }
+                }
+                self.popup = Some(popup);
+            }
+            (Popup::Browse, KeyCode::Char(c @ ('a' | 'b'))) => {
+                if let Some(dir) = self.browse_entries.get(self.browse_choice) {
+                    let point = match c {
+                        'a' => InsertionPoint::After,
+                        _ => InsertionPoint::Before,
+                    };
+                    self.insert_entered(dir.clone(), point);
+                }
+            }
+            (Popup::Suggest, KeyCode::Up | KeyCode::Char('k')) => {
+                self.suggest_choice = self.suggest_choice.saturating_sub(1);
+                self.popup = Some(popup);
+            }
+            (Popup::Suggest, KeyCode::Down | KeyCode::Char('j')) => {
+                let last = self.suggestions.len().saturating_sub(1);
+                self.suggest_choice = (self.suggest_choice + 1).min(last);
+                self.popup = Some(popup);
+            }
+            (Popup::Suggest, KeyCode::Enter | KeyCode::Char('A' | '1'..='9')) => {
+                let choice = match key.code {
+                    KeyCode::Char(c @ '1'..='9') => c as usize - '1' as usize,
+                    _ => self.suggest_choice,
+                };
+                if choice < self.suggestions.len() {
+                    let dir = self.suggestions.remove(choice);
+                    let point = match key.code {
+                        KeyCode::Char('A') => InsertionPoint::Last,
+                        _ => InsertionPoint::First,
+                    };
+                    self.insert_entered(dir, point);
+                    self.suggest_choice = self
+                        .suggest_choice
+                        .min(self.suggestions.len().saturating_sub(1));
+                }
+                // Stay open so several can be added in a row
+                if !self.suggestions.is_empty() {
+                    self.popup = Some(popup);
+                }
+            }
+            (Popup::Fuzzy, KeyCode::Enter) => {
+                let matches = self.fuzzy_matches();
+                if let Some(&i) = matches.get(self.fuzzy_choice) {
+                    self.list_state.select(Some(i));
+                }
+            }
+            (Popup::Fuzzy, KeyCode::Esc) => {}
+            (Popup::Fuzzy, code) => {
+                let ctrl = key.modifiers == KeyModifiers::CONTROL;
+                match code {
+                    KeyCode::Up => self.fuzzy_choice = self.fuzzy_choice.saturating_sub(1),
+                    KeyCode::Char('p') if ctrl => {
+                        self.fuzzy_choice = self.fuzzy_choice.saturating_sub(1)
+                    }
+                    KeyCode::Down => self.fuzzy_choice += 1,
+                    KeyCode::Char('n') if ctrl => self.fuzzy_choice += 1,
+                    KeyCode::Char(c) if !ctrl => {
+                        self.fuzzy_query.push(c);
+                        self.fuzzy_choice = 0;
+                    }
+                    KeyCode::Backspace => {
+                        self.fuzzy_query.pop();
+                        self.fuzzy_choice = 0;
+                    }
+                    _ => {}
+                }
+                let last = self.fuzzy_matches().len().saturating_sub(1);
+                self.fuzzy_choice = self.fuzzy_choice.min(last);
+                self.popup = Some(popup);
+            }
+            (Popup::Sort, KeyCode::Up | KeyCode::Char('k')) => {
+                self.sort_choice = self.sort_choice.saturating_sub(1);
+                self.popup = Some(popup);
+            }
+            (Popup::Sort, KeyCode::Down | KeyCode::Char('j')) => {
+                self.sort_choice = (self.sort_choice + 1).min(SortOrder::ALL.len() - 1);
+                self.popup = Some(popup);
+            }
+            (Popup::Sort, KeyCode::Enter) => {
+                let order = SortOrder::ALL[self.sort_choice];
+                self.status = Some(match self.sort_entries(order) {
+                    0 => format!("sorted {}", order.label()),
+                    n => format!(
+                        "sorted {}, using the modification time of {} entries without a usable access time",
+                        order.label(),
+                        n
+                    ),
+                });
+            }
+            (Popup::Help | Popup::Diff, KeyCode::Down | KeyCode::Char('j')) => {
+                self.popup_scroll = self.popup_scroll.saturating_add(1);
+                self.popup = Some(popup);
+            }
+            (Popup::Help | Popup::Diff, KeyCode::Up | KeyCode::Char('k')) => {
+                self.popup_scroll = self.popup_scroll.saturating_sub(1);
+                self.popup = Some(popup);
+            }
+            (Popup::Help | Popup::Diff, KeyCode::PageDown) => {
+                self.popup_scroll = self.popup_scroll.saturating_add(self.page() as u16);
+                self.popup = Some(popup);
+            }
+            (Popup::Help | Popup::Diff, KeyCode::PageUp) => {
+                self.popup_scroll = self.popup_scroll.saturating_sub(self.page() as u16);
+                self.popup = Some(popup);
+            }
+            (Popup::RemoveMatching, KeyCode::Char(c @ ('y' | '!'))) => {
+                let (targets, kept) = self.take_pending_delete(c == '!');
+                let removed = remove_indices(&mut self.paths, &targets);
+                self.status = Some(format!(
+                    "removed {} entries matching {}{}",
+                    removed.len(),
+                    self.removal_pattern,
+                    kept_pinned(kept)
+                ));
+                self.clamp_selection();
+            }
+            (Popup::RemoveMatching, _) => {
+                self.pending_delete.clear();
+                self.status = Some("removal cancelled".to_string());
+            }
+            (Popup::Relative, KeyCode::Char('r')) => {
+                let targets = std::mem::take(&mut self.pending_delete);
+                let removed = remove_indices(&mut self.paths, &targets);
+                self.status = Some(format!("removed {} relative entries", removed.len()));
+                self.clamp_selection();
+            }
+            (Popup::Relative, KeyCode::Char('a')) => {
+                let targets = std::mem::take(&mut self.pending_delete);
+                match env::current_dir() {
+                    Ok(cwd) => {
+                        for &i in &targets {
+                            self.paths[i] = absolutize(&self.paths[i], &cwd);
+                        }
+                        self.status = Some(format!(
+                            "made {} entries absolute against {}",
+                            targets.len(),
+                            cwd.display()
+                        ));
+                    }
+                    Err(err) => self.status = Some(format!("no current directory: {}", err)),
+                }
+            }
+            (Popup::Relative, _) => {
+                self.pending_delete.clear();
+                self.status = Some("relative entries kept".to_string());
+            }
+            (Popup::Prune, KeyCode::Char(c @ ('y' | '!'))) => {
+                let (targets, kept) = self.take_pending_delete(c == '!');
+                let removed = remove_indices(&mut self.paths, &targets);
+                self.status = Some(format!(
+                    "pruned {} missing entries{}",
+                    removed.len(),
+                    kept_pinned(kept)
+                ));
+                self.clamp_selection();
+            }
+            (Popup::Prune, _) => {
+                self.pending_delete.clear();
+                self.status = Some("prune cancelled".to_string());
+            }
+            (Popup::ConfirmDelete, KeyCode::Char(c @ ('y' | '!'))) => {
+                let (targets, kept) = self.take_pending_delete(c == '!');
+                self.cut(&targets);
+                if kept > 0 {
+                    let deleted = format!("deleted {} entries", targets.len());
+                    self.status = Some(format!("{}{}", deleted, kept_pinned(kept)));
+                }
+            }
+            (Popup::ConfirmDelete, _) => {
+                self.pending_delete.clear();
+                self.status = Some("delete cancelled".to_string());
+            }
+            (Popup::ConfirmAdd, KeyCode::Char('y')) => {
+                let point = match self.input_mode {
+                    InputMode::InsertBefore => InsertionPoint::Before,
+                    InputMode::InsertFirst => InsertionPoint::First,
+                    InputMode::InsertLast => InsertionPoint::Last,
+                    _ => InsertionPoint::After,
+                };
+                self.insert_entered(self.typed_path(), point);
+                self.input.clear();
+                self.input_mode = InputMode::Normal;
+            }
+            (Popup::ConfirmAdd, _) => {
+                // Back to the input box, to fix a typo
+                let expanded = expand_path(Path::new(self.input.trim()));
+                self.status = Some(format!("{} does not exist — not added", expanded.display()));
+            }
+            (Popup::ConfirmSave, KeyCode::Char('y')) => {
+                if let Some(target) = self.pending_save.take() {
+                    self.finish_save(&target);
+                }
+            }
+            (Popup::ConfirmSave, _) => {
+                self.pending_save = None;
+                self.status = Some("save cancelled".to_string());
+            }
+            _ => {}
+        }
+    }
+
+    /// Remembers the disabled entries in the config file, if there is one,
+    /// so they stay disabled in later sessions.
+    fn persist_disabled(&mut self) {
+        let Some(file) = &self.config_file else {
+            return;
+        };
+        if let Err(err) = save_entries(file, "disabled", &self.var, &self.paths, &self.disabled) {
+            self.status = Some(format!(
+                "cannot remember disabled entries in {}: {}",
+                file.display(),
+                err
+            ));
+        }
+    }
+
+    /// Remembers the pinned entries in the config file, if there is one.
+    fn persist_pinned(&mut self) {
+        let Some(file) = &self.config_file else {
+            return;
+        };
+        if let Err(err) = save_entries(file, "pinned", &self.var, &self.paths, &self.pinned) {
+            self.status = Some(format!(
+                "cannot remember pinned entries in {}: {}",
+                file.display(),
+                err
+            ));
+        }
+    }
+
+    /// Takes the entries a delete popup asked about, leaving out the pinned
+    /// ones unless `include_pinned`. The second value counts those left out.
+    fn take_pending_delete(&mut self, include_pinned: bool) -> (BTreeSet<usize>, usize) {
+        let pending = std::mem::take(&mut self.pending_delete);
+        let (pinned, unpinned): (BTreeSet<usize>, BTreeSet<usize>) = pending
+            .into_iter()
+            .partition(|&i| self.pinned.contains(&self.paths[i]));
+        match include_pinned {
+            true => (pinned.union(&unpinned).copied().collect(), 0),
+            false => (unpinned, pinned.len()),
+        }
+    }
+
+    /// The command written inside the managed block of a startup file.
+    fn save_command(&self, shell: &str) -> String {
+        let options = Options {
+            var: Some(self.var.clone()),
+            ..Options::default()
+        };
+        shell_command(&self.enabled(&self.paths), Some(shell), &options)
+    }
+
+    /// Saves to `target`, first showing a diff and asking for confirmation
+    /// when a different managed block would be overwritten.
+    fn begin_save(&mut self, target: SaveTarget) {
+        if let SaveTarget::File { path, shell } = &target {
+            let command = self.save_command(shell);
+            let contents = std::fs::read_to_string(path).unwrap_or_default();
+            if let Some(old) = managed_block(&contents) {
+                if old != command {
+                    self.save_diff = line_diff(old, &command);
+                    self.pending_save = Some(target);
+                    self.popup = Some(Popup::ConfirmSave);
+                    return;
+                }
+            }
+        }
+        self.finish_save(&target);
+    }
+
+    fn finish_save(&mut self, target: &SaveTarget) {
+        self.status = Some(match self.save(target) {
+            Ok(()) => format!("saved to {}", target),
+            Err(err) => format!("cannot save to {}: {}", target, err),
+        });
+    }
+
+    /// Writes the list to `target`, inside a managed block for files.
+    fn save(&self, target: &SaveTarget) -> io::Result<()> {
+        match target {
+            SaveTarget::File { path, shell } => {
+                let command = self.save_command(shell);
+                let contents = match std::fs::read_to_string(path) {
+                    Ok(contents) => contents,
+                    Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+                    Err(err) => return Err(err),
+                };
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::write(path, upsert_managed_block(&contents, &command))
+            }
+            SaveTarget::Registry => match self.system_len {
+                Some(system_len) => {
+                    let (system, user) = self.paths.split_at(system_len);
+                    save_to_registry(Hive::System, &self.var, &self.enabled(system))?;
+                    save_to_registry(Hive::User, &self.var, &self.enabled(user))
+                }
+                None => save_to_registry(Hive::User, &self.var, &self.enabled(&self.paths)),
+            },
+        }
+    }
+
+    /// Writes the session as an executable script for the app's shell.
+    fn export_script(&self, file: &Path) -> io::Result<()> {
+        let shell = self.shell.as_deref().unwrap_or("sh");
+        let options = Options {
+            var: Some(self.var.clone()),
+            single: self.single,
+            ..Options::default()
+        };
+        let command = shell_command(&self.enabled(&self.paths), Some(shell), &options);
+        std::fs::write(file, build_script(shell, &command))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(file, std::fs::Permissions::from_mode(0o755))?;
+        }
+        Ok(())
+    }
+
+    fn handle_normal_mode(&mut self, key: KeyEvent) -> bool {
+        // A pending key only combines with the very next one, if typed in time
+        let pending = self
+            .pending
+            .take()
+            .filter(|(_, since)| since.elapsed() < PENDING_TIMEOUT)
+            .map(|(c, _)| c);
+
+        if let KeyCode::Char(c @ '1'..='9') = key.code {
+            if key.modifiers == KeyModifiers::ALT {
+                self.switch_tab(c as usize - '1' as usize);
+                return false;
+            }
+        }
+
+        // Digits build up a count for the next command
+        let count = self.count.take();
+        if let KeyCode::Char(c @ '0'..='9') = key.code {
+            if c != '0' || count.is_some() {
+                let digit = c.to_digit(10).unwrap_or_default() as usize;
+                let count = count.unwrap_or(0).saturating_mul(10).saturating_add(digit);
+                self.count = Some(count);
+                // Keep waiting for the second key of `3dd`
+                self.pending = pending.map(|c| (c, Instant::now()));
+                return false;
+            }
+        }
+        let times = count.unwrap_or(1);
+
+        if key.code == KeyCode::Esc && self.search.take().is_some() {
+            return false;
+        }
+        if key.code == KeyCode::Esc && !self.marked().is_empty() {
+            self.take_marked();
+            return false;
+        }
+
+        // Handle quitting the application
+        if key.code == KeyCode::Char('q')
+            || key.code == KeyCode::Esc
+            || (key.code == KeyCode::Char('c') && key.modifiers == KeyModifiers::CONTROL)
+        {
+            return true;
+        }
+
+        match key.code {
+            KeyCode::Char('d') if key.modifiers == KeyModifiers::CONTROL => {
+                let half = (self.page() / 2).max(1);
+                self.select_row(|row, _| row + half);
+            }
+            KeyCode::Char('u') if key.modifiers == KeyModifiers::CONTROL => {
+                let half = (self.page() / 2).max(1);
+                self.select_row(|row, _| row.saturating_sub(half));
+            }
+            KeyCode::PageDown => {
+                let page = self.page();
+                self.select_row(|row, _| row + page);
+            }
+            KeyCode::PageUp => {
+                let page = self.page();
+                self.select_row(|row, _| row.saturating_sub(page));
+            }
+            KeyCode::Home => self.select_row(|_, _| 0),
+            KeyCode::Char('g') if pending == Some('g') && key.modifiers == KeyModifiers::NONE => {
+                self.select_row(|_, _| 0)
+            }
+            KeyCode::Char(register) if pending == Some('Q') && register.is_ascii_lowercase() => {
+                self.recording = Some((register, Vec::new()));
+                self.status = Some(format!("recording macro {} (Q to stop)", register));
+            }
+            KeyCode::Char(register) if pending == Some('@') => {
+                let register = match register {
+                    '@' => self.last_macro.unwrap_or('@'),
+                    register => register,
+                };
+                match self.macros.get(&register) {
+                    _ if self.replaying => {
+                        self.status = Some("macros cannot run other macros".to_string())
+                    }
+                    Some(keys) => {
+                        self.to_replay = keys.repeat(times);
+                        self.last_macro = Some(register);
+                    }
+                    None => self.status = Some(format!("no macro {}", register)),
+                }
+            }
+            KeyCode::Char('G') | KeyCode::Enter if count.is_some() => self.jump_to_number(times),
+            KeyCode::End | KeyCode::Char('G') => self.select_row(|_, len| len - 1),
+            KeyCode::Char('a') => {
+                // Enter input mode to insert after
+                self.input_mode = InputMode::InsertAfter;
+                self.input.clear();
+            }
+            KeyCode::Char('b') => {
+                // Enter input mode to insert before
+                self.input_mode = InputMode::InsertBefore;
+                self.input.clear();
+            }
+            KeyCode::Char('I') => {
+                self.input_mode = InputMode::InsertFirst;
+                self.input.clear();
+            }
+            KeyCode::Char('A') => {
+                self.input_mode = InputMode::InsertLast;
+                self.input.clear();
+            }
+            KeyCode::Char('p') if pending == Some('g') => {
+                if let Some(selected) = self.list_state.selected() {
+                    let path = &self.paths[selected];
+                    if !self.pinned.remove(path) {
+                        self.pinned.insert(path.clone());
+                    }
+                    self.persist_pinned();
+                }
+            }
+            KeyCode::Char('x') if pending == Some('g') => self.open_selected(Opener::FileManager),
+            KeyCode::Char('e') if pending == Some('g') => self.open_selected(Opener::Editor),
+            KeyCode::Char('d') if pending == Some('g') => {
+                self.popup_scroll = 0;
+                self.popup = Some(Popup::Diff);
+            }
+            KeyCode::Char('d') if pending == Some('d') => {
+                // `dd` cuts the marked entries, or `times` from the selection on
+                let targets = match self.marked() {
+                    marked if !marked.is_empty() => marked,
+                    _ => match self.list_state.selected() {
+                        Some(selected) => {
+                            (selected..(selected + times).min(self.paths.len())).collect()
+                        }
+                        None => BTreeSet::new(),
+                    },
+                };
+                let pinned = targets
+                    .iter()
+                    .any(|&i| self.pinned.contains(&self.paths[i]));
+                if (self.confirm_delete || pinned) && !targets.is_empty() {
+                    self.pending_delete = targets;
+                    self.popup = Some(Popup::ConfirmDelete);
+                } else {
+                    self.cut(&targets);
+                }
+            }
+            KeyCode::Char('d') => {
+                self.pending = Some(('d', Instant::now()));
+                self.count = count;
+            }
+            KeyCode::Char('Q') => match self.recording.take() {
+                Some((register, mut keys)) => {
+                    // Drop the Q that stopped the recording
+                    keys.pop();
+                    self.status = Some(format!(
+                        "recorded {} keys into macro {}, replay with @{}",
+                        keys.len(),
+                        register,
+                        register
+                    ));
+                    self.macros.insert(register, keys);
+                }
+                None => self.pending = Some(('Q', Instant::now())),
+            },
+            KeyCode::Char('@') => {
+                self.pending = Some(('@', Instant::now()));
+                self.count = count;
+            }
+            KeyCode::Char('y') if pending == Some('y') => {
+                // `yy` copies the selected or marked entries into the register
+                // and onto the clipboard, one per line
+                let marked = self.take_marked();
+                let yanked: Vec<PathBuf> = match marked.is_empty() {
+                    true => self
+                        .list_state
+                        .selected()
+                        .map(|i| self.paths[i].clone())
+                        .into_iter()
+                        .collect(),
+                    false => marked.into_iter().map(|i| self.paths[i].clone()).collect(),
+                };
+                if !yanked.is_empty() {
+                    self.status = Some(format!("yanked {} entries", yanked.len()));
+                    let lines: Vec<_> = yanked.iter().map(|p| p.to_string_lossy()).collect();
+                    self.clipboard = Some(lines.join("\n"));
+                    self.register = yanked;
+                }
+            }
+            KeyCode::Char('y') => {
+                self.pending = Some(('y', Instant::now()));
+            }
+            KeyCode::Char('Y') => {
+                let joined = join_entries(&self.enabled(&self.paths));
+                self.status = Some(format!("copied {} ({} bytes)", self.var, joined.len()));
+                self.clipboard = Some(joined);
+            }
+            KeyCode::Char('p') if key.modifiers == KeyModifiers::CONTROL => {
+                self.fuzzy_query.clear();
+                self.fuzzy_choice = 0;
+                self.popup = Some(Popup::Fuzzy);
+            }
+            KeyCode::Char('p') => self.put(InsertionPoint::After),
+            KeyCode::Char('P') => self.put(InsertionPoint::Before),
+            KeyCode::Char('r') if key.modifiers == KeyModifiers::CONTROL => self.redo(),
+            KeyCode::Char('r') => {
+                self.raw_view = !self.raw_view;
+            }
+            KeyCode::Char('>') if !self.marked().is_empty() => {
+                let marked = self.take_marked();
+                self.scratch
+                    .extend(remove_indices(&mut self.paths, &marked).into_iter().rev());
+                self.list_state.select(marked.first().copied());
+                self.clamp_selection();
+            }
+            KeyCode::Char('>') => {
+                if let Some(selected) = self.list_state.selected() {
+                    self.scratch.push(self.paths.remove(selected));
+                    self.clamp_selection();
+                }
+            }
+            KeyCode::Char('<') => {
+                // Restore the most recently parked entry after the selection
+                if let Some(path) = self.scratch.pop() {
+                    insert_path_at_selection(
+                        &mut self.paths,
+                        &mut self.list_state,
+                        path,
+                        InsertionPoint::After,
+                    );
+                }
+            }
+            KeyCode::Char('s') => {
+                self.sort_choice = 0;
+                self.popup = Some(Popup::Sort);
+            }
+            KeyCode::Char('M') => {
+                self.pending_delete = (0..self.paths.len())
+                    .filter(|&i| is_missing_dir(&self.paths[i]))
+                    .collect();
+                if self.pending_delete.is_empty() {
+                    self.status = Some("no missing entries".to_string());
+                } else {
+                    self.popup = Some(Popup::Prune);
+                }
+            }
+            KeyCode::Char('z') => {
+                self.pending_delete = (0..self.paths.len())
+                    .filter(|&i| is_relative_entry(&self.paths[i]))
+                    .collect();
+                if self.pending_delete.is_empty() {
+                    self.status = Some("no relative entries".to_string());
+                } else {
+                    self.popup = Some(Popup::Relative);
+                }
+            }
+            KeyCode::Char('F') => {
+                self.input = self.removal_pattern.clone();
+                self.compile_removal_pattern();
+                self.input_mode = InputMode::RemoveMatching;
+            }
+            KeyCode::Char('R') => {
+                // Enter paste mode to replace the whole list
+                self.input_mode = InputMode::Paste;
+                self.input.clear();
+            }
+            KeyCode::Char('T') => {
+                if let Some(selected) = self.list_state.selected() {
+                    let trimmed = trim_entry(&self.paths[selected]);
+                    if trimmed == self.paths[selected] {
+                        self.status = Some("nothing to trim".to_string());
+                    } else {
+                        self.paths[selected] = trimmed;
+                    }
+                }
+            }
+            KeyCode::Char('B') if self.base.is_some() => {
+                self.show_origins = !self.show_origins;
+            }
+            KeyCode::Char('E') => {
+                let before = self.paths.clone();
+                expand_entries(&mut self.paths);
+                let changed = before
+                    .iter()
+                    .zip(&self.paths)
+                    .filter(|(a, b)| a != b)
+                    .count();
+                self.status = Some(format!("expanded {} entries", changed));
+            }
+            KeyCode::Char('w') => {
+                self.save_targets = save_targets(self.shell.as_deref());
+                self.save_choice = 0;
+                if self.save_targets.is_empty() {
+                    self.status = Some("no save target for this shell".to_string());
+                } else {
+                    self.popup = Some(Popup::SaveTargets);
+                }
+            }
+            KeyCode::Char('X') => {
+                self.input_mode = InputMode::ExportScript;
+                self.input.clear();
+            }
+            KeyCode::Char(' ') => {
+                if let Some(selected) = self.list_state.selected() {
+                    if !self.marks.remove(&selected) {
+                        self.marks.insert(selected);
+                    }
+                    if selected + 1 < self.paths.len() {
+                        self.list_state.select(Some(selected + 1));
+                    }
+                }
+            }
+            KeyCode::Char('V') => match self.visual_anchor {
+                Some(_) => {
+                    self.marks = self.marked();
+                    self.visual_anchor = None;
+                }
+                None => self.visual_anchor = self.list_state.selected(),
+            },
+            KeyCode::Char('K') => (0..times).for_each(|_| self.move_selected_up()),
+            KeyCode::Up if key.modifiers == KeyModifiers::SHIFT => {
+                (0..times).for_each(|_| self.move_selected_up())
+            }
+            KeyCode::Char('J') => (0..times).for_each(|_| self.move_selected_down()),
+            KeyCode::Down if key.modifiers == KeyModifiers::SHIFT => {
+                (0..times).for_each(|_| self.move_selected_down())
+            }
+            KeyCode::Up | KeyCode::Char('k') if count.is_some() => {
+                self.select_row(|row, _| row.saturating_sub(times))
+            }
+            KeyCode::Down | KeyCode::Char('j') if count.is_some() => {
+                self.select_row(|row, _| row + times)
+            }
+            KeyCode::Char('i') => {
+                self.show_details = !self.show_details;
+            }
+            KeyCode::BackTab if !self.tabs.is_empty() => {
+                self.switch_tab((self.tab + 1) % self.tabs.len());
+            }
+            KeyCode::Tab => {
+                self.show_executables = !self.show_executables;
+            }
+            KeyCode::Char('v') => {
+                self.show_canonical = !self.show_canonical;
+                self.missing.resolved.clear();
+            }
+            KeyCode::Char('#') => {
+                self.show_numbers = !self.show_numbers;
+            }
+            KeyCode::Char('=') => {
+                self.show_counts = !self.show_counts;
+                if self.show_counts {
+                    // Directories may have changed since they were counted
+                    self.executable_counts.clear();
+                }
+            }
+            KeyCode::Char('m') => match self.pinned_detail {
+                Some(_) => self.pinned_detail = None,
+                None => {
+                    self.pinned_detail = self.list_state.selected().map(|i| self.paths[i].clone());
+                    self.show_details = true;
+                }
+            },
+            KeyCode::Char('^') => {
+                self.grouped = !self.grouped;
+                self.unfolded.clear();
+                if let Some((_, run)) = self.selected_group().filter(|_| self.grouped) {
+                    self.list_state.select(Some(run.start));
+                }
+            }
+            KeyCode::Right if self.grouped => {
+                if let Some((prefix, _)) = self.selected_group() {
+                    self.unfolded.insert(prefix);
+                }
+            }
+            KeyCode::Left if self.grouped => {
+                if let Some((prefix, run)) = self.selected_group() {
+                    self.unfolded.remove(&prefix);
+                    self.list_state.select(Some(run.start));
+                }
+            }
+            KeyCode::Char('}') | KeyCode::Char('{') => {
+                // Groups are looked for among the rows shown, as folded
+                let visible = self.visible();
+                let prefixes = self.fold_prefixes(&visible);
+                let selected = self.list_state.selected().unwrap_or(0);
+                let row = visible.iter().position(|&i| i == selected).unwrap_or(0);
+                let start = if key.code == KeyCode::Char('}') {
+                    next_group_start(&prefixes, row)
+                } else {
+                    prev_group_start(&prefixes, row)
+                };
+                if let Some(row) = start {
+                    self.list_state.select(Some(visible[row]));
+                }
+            }
+            KeyCode::Char('/') => {
+                self.input = self.search.clone().unwrap_or_default();
+                self.input_mode = InputMode::Search;
+            }
+            KeyCode::Char(':') => {
+                self.input.clear();
+                self.input_mode = InputMode::Which;
+            }
+            KeyCode::Char('n') if self.search.is_some() => {
+                // Next match, wrapping around to the first
+                let visible = self.visible();
+                let current = self.list_state.selected().unwrap_or(0);
+                let next = visible
+                    .iter()
+                    .find(|&&i| i > current)
+                    .or_else(|| visible.first());
+                self.list_state.select(next.copied());
+            }
+            KeyCode::Char('C') => {
+                // Edit a copy placed right after the entry
+                if let Some(selected) = self.list_state.selected() {
+                    let copy = self.paths[selected].clone();
+                    self.input = copy.to_string_lossy().into_owned();
+                    self.paths.insert(selected + 1, copy);
+                    self.list_state.select(Some(selected + 1));
+                    self.input_mode = InputMode::Edit;
+                }
+            }
+            KeyCode::Char('e') => {
+                if let Some(selected) = self.list_state.selected() {
+                    self.input = self.paths[selected].to_string_lossy().into_owned();
+                    self.input_mode = InputMode::Edit;
+                }
+            }
+            KeyCode::Char('N') if self.list_state.selected().is_some() => {
+                self.input_mode = InputMode::Sibling;
+                self.input.clear();
+            }
+            KeyCode::Char('S') => {
+                if let Some(selected) = self.list_state.selected() {
+                    let sep = SEPARATOR.chars().next().unwrap_or(':');
+                    let parts = split_joined_entry(&self.paths[selected], sep);
+                    if parts.len() > 1 {
+                        self.status = Some(format!("split into {} entries", parts.len()));
+                        self.paths.splice(selected..=selected, parts);
+                    } else {
+                        self.status = Some("entry holds no separator".to_string());
+                    }
+                }
+            }
+            KeyCode::Char('-') => {
+                if let Some(selected) = self.list_state.selected() {
+                    match parent_dir(&self.paths[selected]) {
+                        Some(parent) if parent.is_dir() => self.paths[selected] = parent,
+                        _ => self.status = Some("no parent directory".to_string()),
+                    }
+                }
+            }
+            KeyCode::Char('+') => {
+                if let Some(selected) = self.list_state.selected() {
+                    match unique_subdir(&self.paths[selected]) {
+                        Some(child) => self.paths[selected] = child,
+                        None => self.status = Some("no unique subdirectory".to_string()),
+                    }
+                }
+            }
+            KeyCode::Enter => {
+                if let Some(selected) = self.list_state.selected() {
+                    let path = &self.paths[selected];
+                    if !path.as_os_str().is_empty() && !path.is_dir() {
+                        self.status = Some(missing_reason(path).to_string());
+                    }
+                }
+            }
+            KeyCode::Char('H') => {
+                self.popup = Some(Popup::Histogram);
+            }
+            KeyCode::Char('?') => {
+                self.popup_scroll = 0;
+                self.popup = Some(Popup::Help);
+            }
+            KeyCode::Char('f') if self.list_state.selected().is_some() => {
+                self.popup = Some(Popup::FrontPreview);
+            }
+            KeyCode::Char('D') if !self.marked().is_empty() => {
+                let marked = self.take_marked();
+                let duplicates =
+                    marked_duplicates(&self.paths, &marked, self.keep_policy, &self.dedupe_opts);
+                let removed = remove_indices(&mut self.paths, &duplicates);
+                self.status = Some(format!("removed {} marked duplicates", removed.len()));
+                self.clamp_selection();
+            }
+            KeyCode::Char('D') => {
+                let removed = dedupe_paths(&mut self.paths, self.keep_policy, &self.dedupe_opts);
+                self.status = Some(match removed {
+                    0 => "no duplicates".to_string(),
+                    1 => "removed 1 duplicate".to_string(),
+                    n => format!("removed {} duplicates", n),
+                });
+                self.clamp_selection();
+            }
+            KeyCode::Char('L') => {
+                self.keep_policy = match self.keep_policy {
+                    KeepPolicy::First => KeepPolicy::Last,
+                    KeepPolicy::Last => KeepPolicy::First,
+                };
+            }
+            KeyCode::Char('x') if !self.marked().is_empty() => {
+                // Disable them all unless all are disabled already
+                let marked: Vec<PathBuf> = self
+                    .take_marked()
+                    .into_iter()
+                    .map(|i| self.paths[i].clone())
+                    .collect();
+                if marked.iter().all(|path| self.disabled.contains(path)) {
+                    for path in &marked {
+                        self.disabled.remove(path);
+                    }
+                } else {
+                    self.disabled.extend(marked);
+                }
+                self.persist_disabled();
+            }
+            KeyCode::Char('x') => {
+                if let Some(selected) = self.list_state.selected() {
+                    let path = &self.paths[selected];
+                    if !self.disabled.remove(path) {
+                        self.disabled.insert(path.clone());
+                    }
+                    self.persist_disabled();
+                }
+            }
+            KeyCode::Char('c') => {
+                if let Some(selected) = self.list_state.selected() {
+                    let command = add_command(&self.paths[selected]);
+                    self.status = Some(format!("copied: {}", command));
+                    self.clipboard = Some(command);
+                }
+            }
+            KeyCode::Char('u') => match self.history.snapshots.last() {
+                Some(snapshot) => {
+                    self.status = Some(format!("undid {}", snapshot.description));
+                    self.undo(1);
+                }
+                None => self.status = Some("nothing to undo".to_string()),
+            },
+            KeyCode::Char('U') => {
+                if self.history.len() == 0 {
+                    self.status = Some("nothing to undo".to_string());
+                } else {
+                    self.history_choice = 0;
+                    self.popup = Some(Popup::History);
+                }
+            }
+            KeyCode::Char('g') if key.modifiers == KeyModifiers::CONTROL => {
+                self.popup = Some(Popup::Command);
+            }
+            KeyCode::Char('W') => {
+                self.shadowing = find_shadowed(&self.paths).shadowed;
+                self.shadow_choice = 0;
+                if self.shadowing.is_empty() {
+                    self.status = Some("no command is shadowed".to_string());
+                } else {
+                    self.popup = Some(Popup::Shadowing);
+                }
+            }
+            KeyCode::Char('g') => {
+                self.pending = Some(('g', Instant::now()));
+            }
+            KeyCode::Char('t') => {
+                self.suggestions =
+                    suggested_dirs(&self.paths, &self.extra_suggestions, &self.dedupe_opts);
+                self.suggest_choice = 0;
+                match self.suggestions.is_empty() {
+                    true => {
+                        self.status =
+                            Some("every suggested directory is listed or missing".to_string())
+                    }
+                    false => self.popup = Some(Popup::Suggest),
+                }
+            }
+            KeyCode::Char('o') => {
+                let home = env::var_os("HOME").or_else(|| env::var_os("USERPROFILE"));
+                self.browse(home.map_or_else(|| PathBuf::from("/"), PathBuf::from));
+                if self.status.is_none() {
+                    self.popup = Some(Popup::Browse);
+                }
+            }
+            KeyCode::Char('!') => {
+                if let Some(selected) = self.list_state.selected() {
+                    let path = &self.paths[selected];
+                    if self.mandatory_first.as_ref() == Some(path) {
+                        self.mandatory_first = None;
+                        self.status = Some("entry no longer pinned first".to_string());
+                    } else {
+                        self.mandatory_first = Some(path.clone());
+                        self.status = Some(format!("{} always stays first", path.display()));
+                    }
+                }
+            }
+            KeyCode::Char('O') => match self.repo_filter.take() {
+                Some(_) => {}
+                None => match env::current_dir().ok().as_deref().and_then(find_git_root) {
+                    Some(root) => {
+                        self.repo_filter = Some(root);
+                        let visible = self.visible();
+                        match self.list_state.selected() {
+                            Some(i) if visible.contains(&i) => {}
+                            _ => self.list_state.select(visible.first().copied()),
+                        }
+                        if visible.is_empty() {
+                            self.status = Some("no entries inside this repository".to_string());
+                        }
+                    }
+                    None => self.status = Some("not inside a git repository".to_string()),
+                },
+            },
+            KeyCode::Up | KeyCode::Char('k') if self.filtering() => {
+                let visible = self.visible();
+                let current = self.list_state.selected().unwrap_or(0);
+                if let Some(&i) = visible.iter().rev().find(|&&i| i < current) {
+                    self.list_state.select(Some(i));
+                }
+            }
+            KeyCode::Down | KeyCode::Char('j') if self.filtering() => {
+                let visible = self.visible();
+                let next = match self.list_state.selected() {
+                    Some(current) => visible.iter().find(|&&i| i > current),
+                    None => visible.first(),
+                };
+                if let Some(&i) = next {
+                    self.list_state.select(Some(i));
+                }
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                let i = match self.list_state.selected() {
+                    Some(i) => {
+                        if i > 0 {
+                            Some(i - 1)
+                        } else {
+                            Some(0)
+                        }
+                    }
+                    None => Some(0),
+                };
+                self.list_state.select(i);
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                let i = match self.list_state.selected() {
+                    Some(i) => {
+                        if i < self.paths.len() - 1 {
+                            Some(i + 1)
+                        } else {
+                            Some(self.paths.len() - 1)
+                        }
+                    }
+                    None => Some(0),
+                };
+                self.list_state.select(i);
+            }
+            _ => {}
+        }
+        false
+    }
+
+    /// Completes the input to a directory, like a shell does. A single match
+    /// is filled in; otherwise each Tab (or BackTab, backwards) fills in the
+    /// next one.
+    fn complete(&mut self, key: KeyEvent) {
+        if self.completions.is_empty() {
+            self.completions = complete_dir(&self.input);
+            match self.completions.len() {
+                0 => {}
+                1 => self.input = self.completions.remove(0),
+                _ => {
+                    self.completion_choice = 0;
+                    self.input = self.completions[0].clone();
+                }
+            }
+            return;
+        }
+        let len = self.completions.len();
+        self.completion_choice = match key.code {
+            KeyCode::BackTab => (self.completion_choice + len - 1) % len,
+            _ => (self.completion_choice + 1) % len,
+        };
+        self.input = self.completions[self.completion_choice].clone();
+    }
+
+    /// The entry typed into the input box, checked with `~` and variables
+    /// expanded and stored expanded unless asked to keep it literal. `None`
+    /// when the directory does not exist.
+    fn entered_path(&mut self) -> Option<PathBuf> {
+        let expanded = expand_path(Path::new(self.input.trim()));
+        if !expanded.exists() {
+            self.status = Some(format!("{} does not exist", expanded.display()));
+            return None;
+        }
+        Some(self.typed_path())
+    }
+
+    /// The entry typed into the input box as it is stored, whether or not
+    /// the directory exists.
+    fn typed_path(&self) -> PathBuf {
+        let typed = PathBuf::from(self.input.trim());
+        match self.literal_input {
+            true => typed,
+            false => expand_path(&typed),
+        }
+    }
+
+    /// Inserts an entry the user picked, unless it duplicates one already
+    /// in the list, which is selected instead.
+    fn insert_entered(&mut self, new_path: PathBuf, insertion_point: InsertionPoint) {
+        let key = dedupe_key(&new_path, &self.dedupe_opts);
+        let existing = (self.paths.iter())
+            .position(|p| dedupe_key(p, &self.dedupe_opts) == key)
+            .filter(|_| !self.single);
+        if let Some(i) = existing {
+            self.list_state.select(Some(i));
+            self.status = Some(format!(
+                "{} is already in the list",
+                display_entry(&self.paths[i])
+            ));
+            return;
+        }
+        if self.single {
+            // A single-valued variable only ever holds one entry
+            self.paths.clear();
+        }
+        insert_path_at_selection(
+            &mut self.paths,
+            &mut self.list_state,
+            new_path,
+            insertion_point,
+        );
+    }
+
+    fn handle_input_mode(&mut self, key: KeyEvent, insertion_point: InsertionPoint) {
+        if !matches!(key.code, KeyCode::Tab | KeyCode::BackTab) {
+            self.completions.clear();
+        }
+        match key.code {
+            KeyCode::Tab | KeyCode::BackTab => self.complete(key),
+            KeyCode::Enter if self.input.trim().is_empty() => {
+                self.input_mode = InputMode::Normal;
+            }
+            KeyCode::Enter => {
+                let Some(new_path) = self.entered_path() else {
+                    // It may be about to be created, so ask rather than drop it
+                    self.popup = Some(Popup::ConfirmAdd);
+                    return;
+                };
+                self.insert_entered(new_path, insertion_point);
+                self.input.clear();
+                self.input_mode = InputMode::Normal;
+            }
+            KeyCode::Esc => {
+                self.input.clear();
+                self.input_mode = InputMode::Normal;
+            }
+            KeyCode::Char(c) => {
+                self.input.push(c);
+            }
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            _ => {}
+        }
+    }
+
+    /// Selects the entry a command resolves to and marks the others that
+    /// also provide it, like `which -a`.
+    fn handle_which_mode(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Enter => {
+                let name = self.input.trim().to_string();
+                self.input.clear();
+                self.input_mode = InputMode::Normal;
+                if name.is_empty() {
+                    return;
+                }
+                let found = providers(&self.paths, &name);
+                let Some((&first, others)) = found.split_first() else {
+                    self.status = Some(format!("{} is not in any entry", name));
+                    return;
+                };
+                self.list_state.select(Some(first));
+                self.marks = others.iter().copied().collect();
+                self.visual_anchor = None;
+                self.status = Some(match others.len() {
+                    0 => format!("{} comes from {}", name, display_entry(&self.paths[first])),
+                    n => format!(
+                        "{} comes from {}, shadowing {} more",
+                        name,
+                        display_entry(&self.paths[first]),
+                        n
+                    ),
+                });
+            }
+            KeyCode::Esc => {
+                self.input.clear();
+                self.input_mode = InputMode::Normal;
+            }
+            KeyCode::Char(c) => {
+                self.input.push(c);
+            }
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            _ => {}
+        }
+    }
+
+    /// Previews removing every entry matching the typed pattern.
+    fn handle_remove_matching_mode(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Enter => {
+                let pattern = self.input.trim().to_string();
+                if pattern.is_empty() {
+                    self.input_mode = InputMode::Normal;
+                    return;
+                }
+                let regex = match &self.removal_regex {
+                    Some(Ok(regex)) => regex,
+                    // Keep the pattern so it can be fixed, the box shows why
+                    _ => return,
+                };
+                self.pending_delete = (0..self.paths.len())
+                    .filter(|&i| regex.is_match(&self.paths[i].to_string_lossy()))
+                    .collect();
+                self.removal_pattern = pattern;
+                self.input.clear();
+                self.input_mode = InputMode::Normal;
+                if self.pending_delete.is_empty() {
+                    self.status = Some(format!("no entries match {}", self.removal_pattern));
+                } else {
+                    self.popup = Some(Popup::RemoveMatching);
+                }
+            }
+            KeyCode::Esc => {
+                self.input.clear();
+                self.input_mode = InputMode::Normal;
+            }
+            KeyCode::Char(c) => {
+                self.input.push(c);
+                self.compile_removal_pattern();
+            }
+            KeyCode::Backspace => {
+                self.input.pop();
+                self.compile_removal_pattern();
+            }
+            _ => {}
+        }
+    }
+
+    /// Compiles the pattern typed for `F`, so the box can say what is wrong
+    /// with it before Enter.
+    fn compile_removal_pattern(&mut self) {
+        let pattern = self.input.trim();
+        self.removal_regex = (!pattern.is_empty()).then(|| {
+            Regex::new(pattern).map_err(|err| {
+                // Syntax errors end with a one-line summary under the caret
+                let message = err.to_string();
+                let summary = message.lines().last().unwrap_or_default().trim();
+                summary.trim_start_matches("error: ").to_string()
+            })
+        });
+    }
+
+    fn handle_export_mode(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Enter => {
+                let file = expand_path(Path::new(self.input.trim()));
+                self.status = Some(match self.export_script(&file) {
+                    Ok(()) => format!("exported to {}", file.display()),
+                    Err(err) => format!("cannot export to {}: {}", file.display(), err),
+                });
+                self.input.clear();
+                self.input_mode = InputMode::Normal;
+            }
+            KeyCode::Esc => {
+                self.input.clear();
+                self.input_mode = InputMode::Normal;
+            }
+            KeyCode::Char(c) => {
+                self.input.push(c);
+            }
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_edit_mode(&mut self, key: KeyEvent) {
+        if !matches!(key.code, KeyCode::Tab | KeyCode::BackTab) {
+            self.completions.clear();
+        }
+        match key.code {
+            KeyCode::Tab | KeyCode::BackTab => self.complete(key),
+            KeyCode::Enter => {
+                // Keep the text so a typo can be fixed
+                let Some(new_path) = self.entered_path() else {
+                    return;
+                };
+                if let Some(selected) = self.list_state.selected() {
+                    self.paths[selected] = new_path;
+                }
+                self.input.clear();
+                self.input_mode = InputMode::Normal;
+            }
+            KeyCode::Esc => {
+                self.input.clear();
+                self.input_mode = InputMode::Normal;
+            }
+            KeyCode::Char(c) => {
+                self.input.push(c);
+            }
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_search_mode(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Enter => {
+                self.input.clear();
+                self.input_mode = InputMode::Normal;
+                if self.visible().is_empty() {
+                    self.search = None;
+                    self.status = Some("no matching entries".to_string());
+                }
+            }
+            KeyCode::Esc => {
+                self.search = None;
+                self.input.clear();
+                self.input_mode = InputMode::Normal;
+            }
+            KeyCode::Char(c) => {
+                self.input.push(c);
+                self.search = Some(self.input.clone());
+            }
+            KeyCode::Backspace => {
+                self.input.pop();
+                self.search = Some(self.input.clone()).filter(|query| !query.is_empty());
+            }
+            _ => {}
+        }
+        self.select_visible();
+    }
+
+    fn handle_sibling_mode(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Enter => {
+                let selected = self.list_state.selected().unwrap_or(0);
+                let sibling = self
+                    .paths
+                    .get(selected)
+                    .and_then(|base| sibling_path(base, self.input.trim()));
+                match sibling {
+                    Some(sibling) if sibling.is_dir() => insert_path_at_selection(
+                        &mut self.paths,
+                        &mut self.list_state,
+                        sibling,
+                        InsertionPoint::After,
+                    ),
+                    Some(sibling) => {
+                        self.status = Some(format!("{} does not exist", sibling.display()));
+                    }
+                    None => self.status = Some("no sibling for this entry".to_string()),
+                }
+                self.input.clear();
+                self.input_mode = InputMode::Normal;
+            }
+            KeyCode::Esc => {
+                self.input.clear();
+                self.input_mode = InputMode::Normal;
+            }
+            KeyCode::Char(c) => {
+                self.input.push(c);
+            }
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_paste_mode(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Char('s') if key.modifiers == KeyModifiers::CONTROL => {
+                let paths = parse_block(&self.input);
+                let missing = paths.iter().filter(|p| !p.exists()).count();
+                if paths.is_empty() {
+                    self.status = Some("nothing pasted — list not replaced".to_string());
+                } else if missing > 0 {
+                    // Keep the block so it can be fixed
+                    self.status = Some(format!(
+                        "{} pasted entries do not exist — list not replaced",
+                        missing
+                    ));
+                    return;
+                } else {
+                    self.paths = paths;
+                    self.list_state.select(Some(0));
+                }
+                self.input.clear();
+                self.input_mode = InputMode::Normal;
+            }
+            KeyCode::Enter => {
+                self.input.push('\n');
+            }
+            KeyCode::Esc => {
+                self.input.clear();
+                self.input_mode = InputMode::Normal;
+            }
+            KeyCode::Char(c) => {
+                self.input.push(c);
+            }
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            _ => {}
+        }
+    }
+
+    /// Inserts text pasted into the terminal all at once. The block editor
+    /// keeps its lines; the one-line input boxes take the first line only,
+    /// so a trailing newline does not submit them.
+    fn handle_paste(&mut self, text: &str) {
+        self.status = None;
+        if self.popup.is_some() {
+            return;
+        }
+        match self.input_mode {
+            InputMode::Normal => {}
+            InputMode::Paste => {
+                let block = text.replace("\r\n", "\n").replace('\r', "\n");
+                self.input
+                    .extend(block.chars().filter(|c| *c == '\n' || !c.is_control()));
+            }
+            InputMode::InsertAfter
+            | InputMode::InsertBefore
+            | InputMode::InsertFirst
+            | InputMode::InsertLast
+            | InputMode::ExportScript
+            | InputMode::Sibling
+            | InputMode::Edit
+            | InputMode::Search
+            | InputMode::Which
+            | InputMode::RemoveMatching => {
+                let (line, rest) = sanitize_paste(text);
+                self.input.push_str(&line);
+                if rest > 0 {
+                    self.status = Some(format!("pasted the first line, {} more ignored", rest));
+                }
+                self.completions.clear();
+                if matches!(self.input_mode, InputMode::Search) {
+                    self.search = Some(self.input.clone()).filter(|query| !query.is_empty());
+                    self.select_visible();
+                }
+                if matches!(self.input_mode, InputMode::RemoveMatching) {
+                    self.compile_removal_pattern();
+                }
+            }
+        }
+        if let Some(status) = &self.status {
+            self.messages.push(status.clone());
+        }
+    }
+}
+
+/// The first non-blank line of pasted text, trimmed and without control
+/// characters, and how many more non-blank lines were dropped.
+fn sanitize_paste(text: &str) -> (String, usize) {
+    let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+    let first = lines
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| !c.is_control())
+        .collect();
+    (first, lines.count())
+}
+
+/// Parses a block of text holding one entry per line, ignoring blank lines
+/// and surrounding whitespace.
+fn parse_block(text: &str) -> Vec<PathBuf> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(PathBuf::from)
+        .collect()
+}
+
+fn run_app<B: Backend + io::Write>(terminal: &mut Terminal<B>, mut app: App) -> io::Result<String> {
+    loop {
+        let frame = terminal.draw(|f| draw(f, &mut app))?;
+        if !app.links.is_empty() {
+            let cells = hyperlink_cells(frame.buffer, &app.links);
+            // Put the cursor back where an input box may have placed it
+            let backend = terminal.backend_mut();
+            queue!(backend, SavePosition)?;
+            backend.draw(cells.iter().map(|(x, y, cell)| (*x, *y, cell)))?;
+            execute!(backend, RestorePosition)?;
+        }
+
+        if event::poll(Duration::from_millis(100))? {
+            match event::read()? {
+                Event::Key(key) => {
+                    let raw_view = app.raw_view;
+                    if app.handle_key(key) {
+                        return Ok(app.command());
+                    }
+                    if let Some(text) = app.clipboard.take() {
+                        let copied = copy_to_clipboard(&text, clipboard_tools(), &mut io::stdout());
+                        if let Err(err) = copied {
+                            app.messages
+                                .push(format!("cannot copy to the clipboard: {}", err));
+                        }
+                    }
+                    if let Some(command) = app.to_open.take() {
+                        if let Some(failure) = run_suspended(terminal, &command)? {
+                            app.messages.push(failure);
+                        }
+                    }
+                    // Release the mouse while the raw string is shown so the
+                    // terminal's own text selection can be used to copy it.
+                    if app.raw_view != raw_view {
+                        if app.raw_view {
+                            execute!(io::stdout(), DisableMouseCapture)?;
+                        } else {
+                            execute!(io::stdout(), EnableMouseCapture)?;
+                        }
+                    }
+                }
+                Event::Mouse(mouse) => app.handle_mouse(mouse),
+                Event::Paste(text) => app.handle_paste(&text),
+                // Redraw at the new size straight away, over a cleared screen
+                Event::Resize(_, _) => terminal.autoresize()?,
+                _ => {}
+            }
+        }
+    }
+}
+
+/// What `gx` and `ge` open the selected entry with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Opener {
+    /// The desktop's file manager.
+    FileManager,
+    /// `$VISUAL` or `$EDITOR`, which may carry arguments of its own.
+    Editor,
+}
+
+/// The command line opening `dir` with `opener`, or `None` when no editor
+/// is set.
+fn open_command(opener: Opener, dir: &Path, editor: Option<&OsStr>) -> Option<Vec<OsString>> {
+    let mut command: Vec<OsString> = match opener {
+        Opener::FileManager if cfg!(target_os = "macos") => vec!["open".into()],
+        Opener::FileManager if cfg!(windows) => vec!["explorer".into()],
+        Opener::FileManager => vec!["xdg-open".into()],
+        Opener::Editor => editor?
+            .to_string_lossy()
+            .split_whitespace()
+            .map(OsString::from)
+            .collect(),
+    };
+    if command.is_empty() {
+        return None;
+    }
+    command.push(dir.as_os_str().to_owned());
+    Some(command)
+}
+
+/// Leaves the alternate screen, runs `command` in the terminal and comes
+/// back, redrawing everything. Describes a failure to start or a non-zero
+/// exit.
+fn run_suspended<B: Backend>(
+    terminal: &mut Terminal<B>,
+    command: &[OsString],
+) -> io::Result<Option<String>> {
+    disable_raw_mode()?;
+    execute!(
+        io::stdout(),
+        LeaveAlternateScreen,
+        DisableMouseCapture,
+        DisableBracketedPaste
+    )?;
+    let program = command[0].to_string_lossy().into_owned();
+    let outcome = match std::process::Command::new(&command[0])
+        .args(&command[1..])
+        .status()
+    {
+        Ok(status) if status.success() => None,
+        Ok(status) => Some(format!("{} exited with {}", program, status)),
+        Err(err) => Some(format!("cannot run {}: {}", program, err)),
+    };
+    enable_raw_mode()?;
+    execute!(
+        io::stdout(),
+        EnterAlternateScreen,
+        EnableMouseCapture,
+        EnableBracketedPaste
+    )?;
+    terminal.clear()?;
+    Ok(outcome)
+}
+
+/// Clipboard programs tried in order, with their arguments.
+#[cfg(target_os = "macos")]
+const CLIPBOARD_TOOLS: &[(&str, &[&str])] = &[("pbcopy", &[])];
+#[cfg(windows)]
+const CLIPBOARD_TOOLS: &[(&str, &[&str])] = &[("clip", &[])];
+#[cfg(not(any(target_os = "macos", windows)))]
+const CLIPBOARD_TOOLS: &[(&str, &[&str])] = &[
+    ("wl-copy", &[]),
+    ("xclip", &["-selection", "clipboard"]),
+    ("xsel", &["--clipboard", "--input"]),
+];
+
+/// The clipboard programs worth trying. Over SSH they would fill the remote
+/// machine's clipboard, so none are and OSC 52 is used straight away.
+fn clipboard_tools() -> &'static [(&'static str, &'static [&'static str])] {
+    let ssh = env::var_os("SSH_CONNECTION").is_some() || env::var_os("SSH_TTY").is_some();
+    if ssh {
+        &[]
+    } else {
+        CLIPBOARD_TOOLS
+    }
+}
+
+/// Puts `text` on the system clipboard through the first of `tools` that
+/// works, or else asks the terminal to by writing an OSC 52 sequence to
+/// `terminal`.
+fn copy_to_clipboard(
+    text: &str,
+    tools: &[(&str, &[&str])],
+    terminal: &mut impl io::Write,
+) -> io::Result<()> {
+    use io::Write;
+    use std::process::{Command, Stdio};
+    for (program, args) in tools {
+        let child = Command::new(program)
+            .args(*args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        let Ok(mut child) = child else {
+            continue;
+        };
+        let written = child
+            .stdin
+            .take()
+            .map(|mut stdin| stdin.write_all(text.as_bytes()));
+        // Reap the program even when it would not take the text
+        let status = child.wait();
+        if matches!(written, Some(Ok(()))) && status.is_ok_and(|status| status.success()) {
+            return Ok(());
+        }
+    }
+    write!(terminal, "\x1b]52;c;{}\x07", base64_encode(text.as_bytes()))?;
+    terminal.flush()
+}
+
+/// Standard base64 with padding, as OSC 52 expects.
+fn base64_encode(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Replays the key names listed in `script` (whitespace separated, `#`
+/// starting a comment) without drawing anything. Stops early on a quit key.
+fn run_script(app: &mut App, script: &Path) -> Result<(), String> {
+    let contents = std::fs::read_to_string(script)
+        .map_err(|err| format!("cannot read {}: {}", script.display(), err))?;
+    for line in contents.lines() {
+        let line = line.split('#').next().unwrap_or_default();
+        for name in line.split_whitespace() {
+            let key = parse_key_name(name).ok_or_else(|| format!("unknown key: {}", name))?;
+            if app.handle_key(key) {
+                return Ok(());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Parses key names such as `a`, `Down`, `Esc` or `Ctrl+D` into key events.
+fn parse_key_name(s: &str) -> Option<KeyEvent> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut name = s;
+    while let Some((prefix, rest)) = name.split_once('+') {
+        if rest.is_empty() {
+            break;
+        }
+        modifiers |= match prefix.to_ascii_lowercase().as_str() {
+            "ctrl" => KeyModifiers::CONTROL,
+            "alt" => KeyModifiers::ALT,
+            "shift" => KeyModifiers::SHIFT,
+            _ => return None,
+        };
+        name = rest;
+    }
+
+    let mut chars = name.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(c), None) => {
+            if modifiers.contains(KeyModifiers::CONTROL) {
+                KeyCode::Char(c.to_ascii_lowercase())
+            } else {
+                if c.is_uppercase() {
+                    modifiers |= KeyModifiers::SHIFT;
+                }
+                KeyCode::Char(c)
+            }
+        }
+        _ => match name.to_ascii_lowercase().as_str() {
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            "enter" => KeyCode::Enter,
+            "esc" => KeyCode::Esc,
+            "tab" => KeyCode::Tab,
+            "backtab" => KeyCode::BackTab,
+            "backspace" => KeyCode::Backspace,
+            "delete" => KeyCode::Delete,
+            "space" => KeyCode::Char(' '),
+            _ => return None,
+        },
+    };
+    Some(KeyEvent::new(code, modifiers))
+}
+
+/// Returns when the directory was last accessed, and whether that is really
+/// its modification time: filesystems mounted with `noatime` keep a stale
+/// access time, and some platforms keep none at all.
+fn dir_atime(p: &Path, mounts: &[MountEntry]) -> Option<(SystemTime, bool)> {
+    let metadata = std::fs::metadata(p).ok()?;
+    let stale = mount_of(p, mounts).is_some_and(|mount| mount.stale_atime);
+    match metadata.accessed() {
+        Ok(accessed) if !stale => Some((accessed, false)),
+        _ => metadata.modified().ok().map(|modified| (modified, true)),
+    }
+}
+
+/// Orders most recent first, with entries whose time is unknown last.
+fn compare_recency(a: Option<SystemTime>, b: Option<SystemTime>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => b.cmp(&a),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+#[derive(Clone, Copy)]
+enum InputMode {
+    /// Typing a multi-line block that replaces the whole list.
+    Paste,
+    Normal,
+    InsertAfter,
+    InsertBefore,
+    /// Typing an entry to prepend to the list.
+    InsertFirst,
+    /// Typing an entry to append to the list.
+    InsertLast,
+    /// Typing the file to export the session to as a script.
+    ExportScript,
+    /// Typing the name of a sibling of the selected entry to insert.
+    Sibling,
+    /// Changing the selected entry.
+    Edit,
+    /// Typing text to narrow the list to.
+    Search,
+    /// Typing a command to find the entries providing it.
+    Which,
+    /// Typing a pattern for the entries to remove.
+    RemoveMatching,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum KeepPolicy {
+    /// Keep the first occurrence, which is the one that currently wins.
+    #[default]
+    First,
+    /// Keep the last occurrence, letting later entries override earlier ones.
+    Last,
+}
+
+/// Whether `p` is looked up from the current directory: an empty entry,
+/// `.` or any other path that stays relative once `~` and variables are
+/// expanded. Whoever controls that directory controls which commands run.
+fn is_relative_entry(p: &Path) -> bool {
+    p.as_os_str().is_empty() || expand_path(p).is_relative()
+}
+
+/// The absolute directory a relative entry resolves to from `cwd`.
+fn absolutize(p: &Path, cwd: &Path) -> PathBuf {
+    lexically_normalize(&cwd.join(expand_path(p)))
+}
+
+/// Resolves `.` and `..` components without touching the filesystem, so
+/// `/usr/bin/.` and `/usr/lib/../bin` both become `/usr/bin`. A `..` that
+/// would climb above the root is dropped; leading `..` of relative paths are
+/// kept. Symlinks are not followed, which is why this is only "lexical".
+fn lexically_normalize(p: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in p.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                Some(Component::RootDir) | Some(Component::Prefix(_)) => {}
+                _ => normalized.push(".."),
+            },
+            component => normalized.push(component),
+        }
+    }
+    if normalized.as_os_str().is_empty() && !p.as_os_str().is_empty() {
+        // `.` or `a/..` still refer to the current directory
+        normalized.push(".");
+    }
+    normalized
+}
+
+/// Rules deciding when two entries count as duplicates. `.`/`..` components
+/// and trailing slashes are always normalized away; the rest depends on the
+/// platform's filesystem conventions and can be overridden from the CLI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct DedupeOpts {
+    /// Compare entries case-insensitively.
+    ignore_case: bool,
+    /// Treat `\` and `/` as the same separator.
+    unify_separators: bool,
+}
+
+/// Whether this platform's filesystems are usually case-insensitive.
+const CASE_INSENSITIVE_FS: bool = cfg!(any(windows, target_os = "macos"));
+/// Whether `\` is a path separator on this platform.
+const BACKSLASH_SEPARATES: bool = cfg!(windows);
+
+impl Default for DedupeOpts {
+    fn default() -> Self {
+        DedupeOpts {
+            ignore_case: CASE_INSENSITIVE_FS,
+            unify_separators: BACKSLASH_SEPARATES,
+        }
+    }
+}
+
+/// The key under which entries are compared for duplicates: two entries
+/// are duplicates exactly when their keys are equal. Case folding and
+/// separator unification only apply to entries that are valid UTF-8.
+fn dedupe_key(p: &Path, opts: &DedupeOpts) -> OsString {
+    let unified;
+    let p = match p.to_str() {
+        Some(s) if opts.unify_separators && s.contains('\\') => {
+            unified = PathBuf::from(s.replace('\\', "/"));
+            unified.as_path()
+        }
+        _ => p,
+    };
+    let key = lexically_normalize(p).into_os_string();
+    match key.to_str() {
+        Some(s) if opts.ignore_case => OsString::from(s.to_lowercase()),
+        _ => key,
+    }
+}
+
+/// Flags the entries repeating an earlier one, by `dedupe_key`.
+fn duplicate_flags(paths: &[PathBuf], opts: &DedupeOpts) -> Vec<bool> {
+    let mut seen = HashSet::new();
+    paths
+        .iter()
+        .map(|p| !seen.insert(dedupe_key(p, opts)))
+        .collect()
+}
+
+/// Removes repeated entries according to `policy`, preserving the relative
+/// order of the survivors. Entries marked with `.pathctl-ignore` are always
+/// kept. Returns the number of entries removed.
+fn dedupe_paths(paths: &mut Vec<PathBuf>, policy: KeepPolicy, opts: &DedupeOpts) -> usize {
+    let before = paths.len();
+    let mut seen = HashSet::new();
+    let mut keep = |p: &PathBuf| is_pathctl_ignored(p) || seen.insert(dedupe_key(p, opts));
+    match policy {
+        KeepPolicy::First => paths.retain(keep),
+        KeepPolicy::Last => {
+            let keep: Vec<bool> = paths.iter().rev().map(&mut keep).collect();
+            let mut keep = keep.into_iter().rev();
+            paths.retain(|_| keep.next().unwrap_or(true));
+        }
+    }
+    before - paths.len()
+}
+
+/// How an entry of the current list relates to a base list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DiffTag {
+    /// Present in both lists, in the same relative order.
+    Unchanged,
+    /// Present in both lists, but reordered relative to the other entries.
+    Moved,
+    /// Only present in the current list.
+    Added,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct PathDiff {
+    /// One tag per entry of the current list.
+    tags: Vec<DiffTag>,
+    /// Base entries missing from the current list, in base order.
+    removed: Vec<PathBuf>,
+}
+
+/// Compares `current` against `base`. Entries on the longest common
+/// subsequence keep their relative order and are unchanged; other shared
+/// entries are reported as moved.
+fn diff_paths(base: &[PathBuf], current: &[PathBuf], opts: &DedupeOpts) -> PathDiff {
+    let base_keys: Vec<OsString> = base.iter().map(|p| dedupe_key(p, opts)).collect();
+    let current_keys: Vec<OsString> = current.iter().map(|p| dedupe_key(p, opts)).collect();
+
+    // lcs[i][j] is the LCS length of base_keys[i..] and current_keys[j..]
+    let mut lcs = vec![vec![0usize; current_keys.len() + 1]; base_keys.len() + 1];
+    for i in (0..base_keys.len()).rev() {
+        for j in (0..current_keys.len()).rev() {
+            lcs[i][j] = if base_keys[i] == current_keys[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut tags = vec![DiffTag::Moved; current_keys.len()];
+    let (mut i, mut j) = (0, 0);
+    while i < base_keys.len() && j < current_keys.len() {
+        if base_keys[i] == current_keys[j] {
+            tags[j] = DiffTag::Unchanged;
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    for (tag, key) in tags.iter_mut().zip(&current_keys) {
+        if !base_keys.contains(key) {
+            *tag = DiffTag::Added;
+        }
+    }
+
+    let removed = base
+        .iter()
+        .zip(&base_keys)
+        .filter(|(_, key)| !current_keys.contains(key))
+        .map(|(p, _)| p.clone())
+        .collect();
+    PathDiff { tags, removed }
+}
+
+/// Describes how `current` drifted from `expected`, one `+` (added), `-`
+/// (removed) or `~` (moved) line per entry, or returns `None` when they
+/// match. With `ignore_order` moved entries are not reported.
+fn drift_report(
+    expected: &[PathBuf],
+    current: &[PathBuf],
+    ignore_order: bool,
+    opts: &DedupeOpts,
+) -> Option<String> {
+    let diff = diff_paths(expected, current, opts);
+    let mut lines = Vec::new();
+    for (p, tag) in current.iter().zip(&diff.tags) {
+        match tag {
+            DiffTag::Added => lines.push(format!("+ {}", display_entry(p))),
+            DiffTag::Moved if !ignore_order => lines.push(format!("~ {}", display_entry(p))),
+            _ => {}
+        }
+    }
+    for p in &diff.removed {
+        lines.push(format!("- {}", display_entry(p)));
+    }
+    match lines.is_empty() {
+        true => None,
+        false => Some(lines.join("\n")),
+    }
+}
+
+/// Whether an entry came from the base PATH or was added on top of it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Origin {
+    Inherited,
+    Added,
+}
+
+fn classify_origins(base: &[PathBuf], current: &[PathBuf], opts: &DedupeOpts) -> Vec<Origin> {
+    diff_paths(base, current, opts)
+        .tags
+        .into_iter()
+        .map(|tag| match tag {
+            DiffTag::Added => Origin::Added,
+            DiffTag::Unchanged | DiffTag::Moved => Origin::Inherited,
+        })
+        .collect()
+}
+
+/// Parses a captured PATH: either a single separator-joined line, as written
+/// by `echo "$PATH" > file`, or one entry per line.
+fn parse_path_file(contents: &str) -> Vec<PathBuf> {
+    let contents = contents.trim();
+    if contents.contains('\n') {
+        parse_block(contents)
+    } else {
+        env::split_paths(contents).collect()
+    }
+}
+
+#[derive(Clone, Copy)]
+enum InsertionPoint {
+    Before,
+    After,
+    /// At the top of the list, wherever the selection is.
+    First,
+    /// At the bottom of the list, wherever the selection is.
+    Last,
+}
+
+/// Directories whose path starts with `input`, as `input` would read when
+/// completed to each of them, sorted. Hidden directories are only offered
+/// once a `.` is typed.
+fn complete_dir(input: &str) -> Vec<String> {
+    let split = input.rfind(std::path::is_separator).map_or(0, |i| i + 1);
+    let (dir, partial) = input.split_at(split);
+    let read_from = match dir {
+        "" => PathBuf::from("."),
+        dir => expand_path(Path::new(dir)),
+    };
+    let Ok(entries) = std::fs::read_dir(read_from) else {
+        return Vec::new();
+    };
+    let mut completions: Vec<String> = entries
+        .filter_map(Result::ok)
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| {
+            name.starts_with(partial) && (partial.starts_with('.') || !name.starts_with('.'))
+        })
+        .map(|name| format!("{}{}{}", dir, name, std::path::MAIN_SEPARATOR))
+        .collect();
+    completions.sort();
+    completions
+}
+
+/// Where package managers and language toolchains commonly install
+/// commands, offered by `t` when they exist but are not listed.
+const SUGGESTED_DIRS: &[&str] = &[
+    "~/.local/bin",
+    "~/bin",
+    "~/.cargo/bin",
+    "~/go/bin",
+    "/usr/local/go/bin",
+    "~/.deno/bin",
+    "~/.bun/bin",
+    "~/.npm-global/bin",
+    "~/.yarn/bin",
+    "~/.dotnet/tools",
+    "~/.pyenv/shims",
+    "~/.rbenv/shims",
+    "~/.nix-profile/bin",
+    "/opt/homebrew/bin",
+    "/opt/homebrew/sbin",
+    "/usr/local/bin",
+    "/snap/bin",
+];
+
+/// The directories of `SUGGESTED_DIRS` and then `extra` that exist and are
+/// not in `paths`, expanded and without repeats.
+fn suggested_dirs(paths: &[PathBuf], extra: &[PathBuf], opts: &DedupeOpts) -> Vec<PathBuf> {
+    let mut seen: HashSet<OsString> = paths
+        .iter()
+        .map(|p| dedupe_key(&expand_path(p), opts))
+        .collect();
+    SUGGESTED_DIRS
+        .iter()
+        .map(PathBuf::from)
+        .chain(extra.iter().cloned())
+        .map(|dir| expand_path(&dir))
+        .filter(|dir| dir.is_dir() && seen.insert(dedupe_key(dir, opts)))
+        .collect()
+}
+
+/// The directories directly inside `dir`, hidden ones included since tool
+/// directories such as `~/.cargo/bin` often live there, sorted by name.
+fn subdirectories(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut dirs: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect();
+    dirs.sort();
+    Ok(dirs)
+}
+
+/// Expands a leading `~` and `$VAR`, `${VAR}` or `%VAR%` references.
+/// References to undefined variables are kept literally, as are entries that
+/// are not valid UTF-8.
+fn expand_path(p: &Path) -> PathBuf {
+    let Some(s) = p.to_str() else {
+        return p.to_path_buf();
+    };
+    let home = env::var("HOME").or_else(|_| env::var("USERPROFILE")).ok();
+    let s = match (s.strip_prefix('~'), home) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with(['/', '\\']) => {
+            format!("{}{}", home, rest)
+        }
+        _ => s.to_string(),
+    };
+
+    let is_name = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut expanded = String::with_capacity(s.len());
+    let mut rest = s.as_str();
+    while let Some(start) = rest.find(['$', '%']) {
+        expanded.push_str(&rest[..start]);
+        let sigil = &rest[start..start + 1];
+        let after = &rest[start + 1..];
+        let (name, reference) = if sigil == "%" {
+            match after.find('%') {
+                Some(end) if end > 0 && after[..end].chars().all(is_name) => {
+                    (&after[..end], &rest[start..start + end + 2])
+                }
+                _ => ("", sigil),
+            }
+        } else if let Some(braced) = after.strip_prefix('{') {
+            match braced.find('}') {
+                Some(end) => (&braced[..end], &rest[start..start + end + 3]),
+                None => ("", sigil),
+            }
+        } else {
+            let end = after.find(|c: char| !is_name(c)).unwrap_or(after.len());
+            (&after[..end], &rest[start..start + end + 1])
+        };
+        match env::var(name) {
+            Ok(value) if !name.is_empty() => expanded.push_str(&value),
+            _ => expanded.push_str(reference),
+        }
+        rest = &rest[start + reference.len()..];
+    }
+    expanded.push_str(rest);
+    PathBuf::from(expanded)
+}
+
+/// Expands variable references in every entry, see `expand_path`.
+fn expand_entries(paths: &mut [PathBuf]) {
+    for path in paths.iter_mut() {
+        *path = expand_path(path);
+    }
+}
+
+/// Splits an entry that accidentally holds a whole joined PATH back into its
+/// components, dropping empty pieces. Other entries come back as they are.
+fn split_joined_entry(p: &Path, sep: char) -> Vec<PathBuf> {
+    let parts: Vec<PathBuf> = match p.to_str() {
+        Some(s) => s
+            .split(sep)
+            .filter(|part| !part.is_empty())
+            .map(PathBuf::from)
+            .collect(),
+        None => vec![],
+    };
+    if parts.is_empty() {
+        vec![p.to_path_buf()]
+    } else {
+        parts
+    }
+}
+
+/// Why an entry does not point at a usable directory.
+#[derive(Debug, PartialEq, Eq)]
+enum MissingReason {
+    /// Something exists there, but it is not a directory.
+    NotADirectory,
+    /// The entry is a symlink whose target does not exist.
+    DanglingSymlink(PathBuf),
+    /// Nothing exists there. Holds the closest ancestor that does exist.
+    Missing(Option<PathBuf>),
+}
+
+impl std::fmt::Display for MissingReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MissingReason::NotADirectory => write!(f, "not a directory"),
+            MissingReason::DanglingSymlink(target) => {
+                write!(f, "dangling symlink to {}", target.display())
+            }
+            MissingReason::Missing(Some(existing)) => {
+                write!(
+                    f,
+                    "missing; closest existing ancestor: {}",
+                    existing.display()
+                )
+            }
+            MissingReason::Missing(None) => write!(f, "missing; no ancestor exists"),
+        }
+    }
+}
+
+/// Works out why `p` is not a directory, walking up its ancestors to find
+/// the closest one that exists.
+fn missing_reason(p: &Path) -> MissingReason {
+    if let Ok(metadata) = std::fs::symlink_metadata(p) {
+        if metadata.file_type().is_symlink() && !p.exists() {
+            let target = std::fs::read_link(p).unwrap_or_default();
+            return MissingReason::DanglingSymlink(target);
+        }
+        return MissingReason::NotADirectory;
+    }
+    let existing = p
+        .ancestors()
+        .skip(1)
+        .find(|ancestor| !ancestor.as_os_str().is_empty() && ancestor.exists());
+    MissingReason::Missing(existing.map(Path::to_path_buf))
+}
+
+/// Strips whitespace surrounding an entry, as left behind by sloppy imports.
+/// Entries that are not valid UTF-8 are returned unchanged.
+fn trim_entry(p: &Path) -> PathBuf {
+    match p.to_str() {
+        Some(s) => PathBuf::from(s.trim()),
+        None => p.to_path_buf(),
+    }
+}
+
+/// Which entry the detail pane describes: the pinned one while it is still
+/// in the list, wherever edits moved it, the selection otherwise.
+fn detail_target(
+    pinned: Option<&Path>,
+    selected: Option<usize>,
+    paths: &[PathBuf],
+) -> Option<usize> {
+    pinned
+        .and_then(|pinned| paths.iter().position(|p| p == pinned))
+        .or(selected)
+        .filter(|&i| i < paths.len())
+}
+
+/// Lines of the detail pane for `p`.
+fn entry_details(p: &Path, mounts: &[MountEntry]) -> Vec<String> {
+    let mut lines = vec![display_entry(p)];
+    if p.as_os_str().is_empty() || p.is_dir() {
+        if let Ok(names) = executables(p) {
+            lines.push(format!("{} executables", names.len()));
+        }
+    } else {
+        lines.push(missing_reason(p).to_string());
+    }
+    if let Ok(target) = std::fs::read_link(p) {
+        lines.push(format!("symlink to {}", target.display()));
+    }
+    if let Ok(canonical) = std::fs::canonicalize(p) {
+        lines.push(format!("canonical path {}", canonical.display()));
+    }
+    if let Ok(metadata) = std::fs::metadata(p) {
+        lines.extend(ownership_details(&metadata));
+    }
+    if is_network_fs(p, mounts) {
+        lines.push("on a network filesystem".to_string());
+    }
+    if is_pathctl_ignored(p) {
+        lines.push(format!("ignored by cleanup ({})", IGNORE_MARKER));
+    }
+    lines
+}
+
+/// Permissions and owner of an entry, as `ls -ld` would show them.
+#[cfg(unix)]
+fn ownership_details(metadata: &std::fs::Metadata) -> Vec<String> {
+    use std::os::unix::fs::MetadataExt;
+    let name = |db: &str, id: u32| {
+        std::fs::read_to_string(db)
+            .ok()
+            .and_then(|contents| id_name(&contents, id).map(str::to_string))
+            .unwrap_or_else(|| id.to_string())
+    };
+    vec![
+        format!("permissions {}", mode_string(metadata.mode())),
+        format!(
+            "owner {}:{}",
+            name("/etc/passwd", metadata.uid()),
+            name("/etc/group", metadata.gid())
+        ),
+    ]
+}
+
+#[cfg(not(unix))]
+fn ownership_details(metadata: &std::fs::Metadata) -> Vec<String> {
+    match metadata.permissions().readonly() {
+        true => vec!["permissions read-only".to_string()],
+        false => vec!["permissions read-write".to_string()],
+    }
+}
+
+/// `ls -l` style rendering of a file mode, e.g. `drwxr-xr-x`.
+#[cfg(unix)]
+fn mode_string(mode: u32) -> String {
+    let kind = match mode & 0o170000 {
+        0o040000 => 'd',
+        0o120000 => 'l',
+        _ => '-',
+    };
+    let bits = (0..9).rev().map(|bit| match mode & (1 << bit) {
+        0 => '-',
+        _ => ['x', 'w', 'r'][bit % 3],
+    });
+    std::iter::once(kind).chain(bits).collect()
+}
+
+/// Name of the user or group `id` in the contents of `/etc/passwd` or
+/// `/etc/group`, whose lines both start with `name:password:id:`.
+#[cfg(unix)]
+fn id_name(db: &str, id: u32) -> Option<&str> {
+    db.lines().find_map(|line| {
+        let mut fields = line.split(':');
+        let name = fields.next()?;
+        let found = fields.nth(1)?.parse::<u32>().ok()?;
+        (found == id).then_some(name)
+    })
+}
+
+/// What `p` resolves to once symlinks and `..` are followed, as shown after
+/// the entry, or `None` when that is the entry itself or nothing at all.
+fn resolved_label(p: &Path) -> Option<String> {
+    let canonical = std::fs::canonicalize(p).ok()?;
+    (canonical != p).then(|| format!("  → {}", canonical.display()))
+}
+
+/// What the grouped view folds `p` under: the first directory below `home`
+/// for entries inside it, such as `~/.asdf`, or else the first directory
+/// below the root, such as `/opt`.
+fn fold_prefix(p: &Path, home: Option<&Path>) -> Option<PathBuf> {
+    let (base, rest) = match home.and_then(|home| Some((home, p.strip_prefix(home).ok()?))) {
+        Some((home, rest)) => (home.to_path_buf(), rest),
+        None => (PathBuf::new(), p),
+    };
+    let mut prefix = base;
+    for component in rest.components() {
+        prefix.push(component);
+        if matches!(component, Component::Normal(_)) {
+            return Some(prefix);
+        }
+    }
+    None
+}
+
+/// Runs of at least two adjacent entries with the same `fold_prefix`. Only
+/// adjacent entries are folded together, as folding would otherwise hide
+/// where in the order an entry sits.
+fn fold_runs(paths: &[PathBuf], home: Option<&Path>) -> Vec<(PathBuf, Range<usize>)> {
+    let prefixes: Vec<Option<PathBuf>> = paths.iter().map(|p| fold_prefix(p, home)).collect();
+    let mut runs = Vec::new();
+    let mut start = 0;
+    for end in 1..=paths.len() {
+        if end < paths.len() && prefixes[end].is_some() && prefixes[end] == prefixes[start] {
+            continue;
+        }
+        if let Some(prefix) = prefixes[start].as_ref().filter(|_| end - start > 1) {
+            runs.push((prefix.clone(), start..end));
+        }
+        start = end;
+    }
+    runs
+}
+
+/// Whether the entry at `index` starts a group, i.e. a run of adjacent
+/// entries with the same `fold_prefix`, as the grouped view folds them.
+/// Entries without a prefix are groups of their own.
+fn is_group_start(prefixes: &[Option<PathBuf>], index: usize) -> bool {
+    index == 0 || prefixes[index].is_none() || prefixes[index] != prefixes[index - 1]
+}
+
+/// Index of the first group starting after `position`.
+fn next_group_start(prefixes: &[Option<PathBuf>], position: usize) -> Option<usize> {
+    (position + 1..prefixes.len()).find(|&i| is_group_start(prefixes, i))
+}
+
+/// Index of the start of the group holding `position`, or of the previous
+/// group when `position` already is a group start.
+fn prev_group_start(prefixes: &[Option<PathBuf>], position: usize) -> Option<usize> {
+    (0..position.min(prefixes.len()))
+        .rev()
+        .find(|&i| is_group_start(prefixes, i))
+}
+
+/// Returns `name` next to `base`, e.g. `/opt/tool/lib` for `/opt/tool/bin`
+/// and `lib`. Entries at the root get a sibling at the root; the root itself
+/// and bare names have none.
+fn sibling_path(base: &Path, name: &str) -> Option<PathBuf> {
+    if name.is_empty() {
+        return None;
+    }
+    let parent = base.parent()?;
+    if parent.as_os_str().is_empty() {
+        return None;
+    }
+    Some(parent.join(name))
+}
+
+/// Returns the directory one level above `p`, or `None` at the root and for
+/// entries with no parent such as `bin` or the empty entry.
+fn parent_dir(p: &Path) -> Option<PathBuf> {
+    let parent = p.parent()?;
+    if parent.as_os_str().is_empty() {
+        return None;
+    }
+    Some(parent.to_path_buf())
+}
+
+/// Returns the only subdirectory of `p`, or `None` if it has zero or several.
+fn unique_subdir(p: &Path) -> Option<PathBuf> {
+    let mut subdirs = std::fs::read_dir(p)
+        .ok()?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir());
+    let child = subdirs.next()?;
+    match subdirs.next() {
+        Some(_) => None,
+        None => Some(child),
+    }
+}
+
+/// Moves `first` back to index 0 if an edit displaced it, returning where it
+/// was found. Nothing happens when the entry is no longer in the list.
+fn pin_first(paths: &mut Vec<PathBuf>, first: &Path) -> Option<usize> {
+    let from = paths.iter().position(|p| p == first)?;
+    if from == 0 {
+        return None;
+    }
+    move_to(paths, from, 0);
+    Some(from)
+}
+
+/// Moves the entry at `from` so that it ends up at index `to`, shifting the
+/// entries in between.
+fn move_to(paths: &mut Vec<PathBuf>, from: usize, to: usize) {
+    let path = paths.remove(from);
+    paths.insert(to.min(paths.len()), path);
+}
+
+/// Returns a copy of `paths` with the entry at `index` moved to the front,
+/// leaving the original untouched.
+fn preview_move_to_front(paths: &[PathBuf], index: usize) -> Vec<PathBuf> {
+    let mut preview = paths.to_vec();
+    move_to(&mut preview, index, 0);
+    preview
+}
+
+/// Whether `path` contains `query`, ignoring case.
+fn matches_search(path: &Path, query: &str) -> bool {
+    path.to_string_lossy()
+        .to_lowercase()
+        .contains(&query.to_lowercase())
+}
+
+/// Matches `query` against `candidate` the way fuzzy finders do: every
+/// query character must appear in order, ignoring case. Runs of consecutive
+/// characters and characters starting a path component score higher, skipped
+/// characters lower. Returns the score and the char positions matched.
+fn fuzzy_match(candidate: &str, query: &str) -> Option<(i64, Vec<usize>)> {
+    let chars: Vec<char> = candidate.chars().collect();
+    let mut positions: Vec<usize> = Vec::new();
+    let mut score = 0;
+    let mut from = 0;
+    for q in query.chars() {
+        let pos = (from..chars.len()).find(|&i| chars[i].to_lowercase().eq(q.to_lowercase()))?;
+        score += 16;
+        match positions.last() {
+            Some(&last) if last + 1 == pos => score += 8,
+            Some(&last) => score -= (pos - last - 1) as i64,
+            None => {}
+        }
+        if pos == 0 || matches!(chars[pos - 1], '/' | '\\' | '.' | '-' | '_' | ' ') {
+            score += 8;
+        }
+        positions.push(pos);
+        from = pos + 1;
+    }
+    Some((score, positions))
+}
+
+/// Finds the root of the git repository containing `dir` by walking up to
+/// the first directory holding a `.git` entry, which is a file in worktrees
+/// and submodules.
+fn find_git_root(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .find(|ancestor| ancestor.join(".git").exists())
+        .map(Path::to_path_buf)
+}
+
+/// Whether `entry` lies inside `repo_root`, comparing whole components so
+/// `/src/app-tools` is not inside `/src/app`.
+fn under_repo(entry: &Path, repo_root: &Path) -> bool {
+    lexically_normalize(entry).starts_with(repo_root)
+}
+
+/// Whether `path` is a file the shell would run from PATH.
+fn is_executable(path: &Path) -> bool {
+    let Ok(metadata) = std::fs::metadata(path) else {
+        return false;
+    };
+    if !metadata.is_file() {
+        return false;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        metadata.permissions().mode() & 0o111 != 0
+    }
+    #[cfg(not(unix))]
+    {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default();
+        ["exe", "com", "bat", "cmd", "ps1"]
+            .iter()
+            .any(|known| extension.eq_ignore_ascii_case(known))
+    }
+}
+
+/// Names of the executables directly inside `dir`, sorted.
+fn executables(dir: &Path) -> io::Result<Vec<OsString>> {
+    let mut names: Vec<OsString> = std::fs::read_dir(dir)?
+        .filter_map(Result::ok)
+        .filter(|entry| is_executable(&entry.path()))
+        .map(|entry| entry.file_name())
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+/// Entries holding an executable called `name`, in lookup order. On
+/// Windows the usual extensions may be left out, as in a shell.
+fn providers(paths: &[PathBuf], name: &str) -> Vec<usize> {
+    let names: Vec<String> = match cfg!(windows) && Path::new(name).extension().is_none() {
+        true => ["exe", "com", "bat", "cmd", "ps1"]
+            .iter()
+            .map(|extension| format!("{}.{}", name, extension))
+            .collect(),
+        false => vec![name.to_string()],
+    };
+    (0..paths.len())
+        .filter(|&i| names.iter().any(|name| is_executable(&paths[i].join(name))))
+        .collect()
+}
+
+/// Lists the executables of every entry that is a readable directory.
+/// Missing entries, files and unreadable directories are skipped rather
+/// than reported as errors; the second value counts them.
+fn scan_executables(paths: &[PathBuf]) -> (Vec<Option<Vec<OsString>>>, usize) {
+    let mut skipped = 0;
+    let scanned = paths
+        .iter()
+        .map(|p| {
+            let names = match p.is_dir() {
+                true => executables(p).ok(),
+                false => None,
+            };
+            if names.is_none() {
+                skipped += 1;
+            }
+            names
+        })
+        .collect();
+    (scanned, skipped)
+}
+
+/// Number of executables in each entry, `None` for skipped entries.
+#[derive(Debug, PartialEq, Eq)]
+struct ExecutableCounts {
+    counts: Vec<Option<usize>>,
+    /// Entries that are not readable directories.
+    skipped: usize,
+}
+
+fn count_executables(paths: &[PathBuf]) -> ExecutableCounts {
+    let (scanned, skipped) = scan_executables(paths);
+    ExecutableCounts {
+        counts: scanned
+            .into_iter()
+            .map(|names| names.map(|names| names.len()))
+            .collect(),
+        skipped,
+    }
+}
+
+/// An executable found in several entries.
+#[derive(Debug, PartialEq, Eq)]
+struct Shadowed {
+    name: OsString,
+    /// Index of the entry the shell picks.
+    winner: usize,
+    /// Indices of the later entries whose copy is never run.
+    shadowed: Vec<usize>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct ShadowReport {
+    /// Shadowed executables, sorted by name.
+    shadowed: Vec<Shadowed>,
+    /// Entries that are not readable directories.
+    skipped: usize,
+}
+
+/// Finds executables present in more than one entry. A directory listed
+/// twice, possibly through a symlink such as `/bin` → `/usr/bin`, does not
+/// shadow itself.
+fn find_shadowed(paths: &[PathBuf]) -> ShadowReport {
+    let (scanned, skipped) = scan_executables(paths);
+    let mut seen = HashSet::new();
+    let mut found: std::collections::BTreeMap<OsString, Vec<usize>> = Default::default();
+    for (i, names) in scanned.into_iter().enumerate() {
+        let Some(names) = names else { continue };
+        let dir = std::fs::canonicalize(&paths[i]).unwrap_or_else(|_| paths[i].clone());
+        if !seen.insert(dir) {
+            continue;
+        }
+        for name in names {
+            found.entry(name).or_default().push(i);
+        }
+    }
+    let shadowed = found
+        .into_iter()
+        .filter(|(_, entries)| entries.len() > 1)
+        .map(|(name, entries)| Shadowed {
+            name,
+            winner: entries[0],
+            shadowed: entries[1..].to_vec(),
+        })
+        .collect();
+    ShadowReport { shadowed, skipped }
+}
+
+/// Lists each entry with its number of executables, then the executables
+/// shadowed by an earlier entry.
+fn audit_report(paths: &[PathBuf]) -> String {
+    let counts = count_executables(paths);
+    let mut lines: Vec<String> = paths
+        .iter()
+        .zip(&counts.counts)
+        .map(|(p, count)| match count {
+            Some(count) => format!("{:>5}  {}", count, display_entry(p)),
+            None => format!("{:>5}  {} (skipped)", "-", display_entry(p)),
+        })
+        .collect();
+    let report = find_shadowed(paths);
+    for shadowed in &report.shadowed {
+        let losers: Vec<String> = shadowed
+            .shadowed
+            .iter()
+            .map(|&i| display_entry(&paths[i]))
+            .collect();
+        lines.push(format!(
+            "{}: {} shadows {}",
+            shadowed.name.to_string_lossy(),
+            display_entry(&paths[shadowed.winner]),
+            losers.join(", ")
+        ));
+    }
+    if counts.skipped > 0 {
+        lines.push(format!(
+            "{} entries skipped (missing or not a directory)",
+            counts.skipped
+        ));
+    }
+    lines.join("\n")
+}
+
+/// Registry hive a PATH section is stored in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Hive {
+    System,
+    User,
+}
+
+/// Which hive the entry at `index` is saved to in the combined view, where
+/// the first `system_len` entries are the system ones.
+fn section_of(index: usize, system_len: usize) -> Hive {
+    if index < system_len {
+        Hive::System
+    } else {
+        Hive::User
+    }
+}
+
+/// Removes the entries at `indices`, returning them in list order.
+fn remove_indices(paths: &mut Vec<PathBuf>, indices: &BTreeSet<usize>) -> Vec<PathBuf> {
+    let mut removed = Vec::new();
+    let mut index = 0;
+    paths.retain(|path| {
+        let keep = !indices.contains(&index);
+        if !keep {
+            removed.push(path.clone());
+        }
+        index += 1;
+        keep
+    });
+    removed
+}
+
+/// Indices among `marked` that duplicate another marked entry, keeping the
+/// first or last one of each as `policy` says.
+fn marked_duplicates(
+    paths: &[PathBuf],
+    marked: &BTreeSet<usize>,
+    policy: KeepPolicy,
+    opts: &DedupeOpts,
+) -> BTreeSet<usize> {
+    let order: Vec<usize> = match policy {
+        KeepPolicy::First => marked.iter().copied().collect(),
+        KeepPolicy::Last => marked.iter().rev().copied().collect(),
+    };
+    let mut seen = HashSet::new();
+    order
+        .into_iter()
+        .filter(|&i| !is_pathctl_ignored(&paths[i]) && !seen.insert(dedupe_key(&paths[i], opts)))
+        .collect()
+}
+
+/// Moves the system/user boundary after an edit changed the list length
+/// from `before` to `after`. Entries added or removed count towards the
+/// section of the entry that was selected, so inserting after the last
+/// system entry grows the system section and cutting a user entry leaves
+/// it alone.
+fn shift_boundary(
+    system_len: usize,
+    selected: Option<usize>,
+    before: usize,
+    after: usize,
+) -> usize {
+    let grown = match selected {
+        Some(index) if section_of(index, system_len) == Hive::System => {
+            (system_len + after).saturating_sub(before)
+        }
+        _ => system_len,
+    };
+    grown.min(after)
+}
+
+fn insert_path_at_selection(
+    paths: &mut Vec<PathBuf>,
+    list_state: &mut ListState,
+    new_path: PathBuf,
+    insertion_point: InsertionPoint,
+) {
+    let selected_index = list_state.selected().unwrap_or(0);
+    let insert_index = match insertion_point {
+        InsertionPoint::Before => selected_index,
+        InsertionPoint::After => selected_index + 1,
+        InsertionPoint::First => 0,
+        InsertionPoint::Last => paths.len(),
+    };
+    let insert_index = insert_index.min(paths.len()); // Ensure we don't go out of bounds
+    paths.insert(insert_index, new_path);
+    list_state.select(Some(insert_index));
+}
+
+fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App) {
+    let size = f.size();
+    let input_mode = app.input_mode;
+    let input = app.input.as_str();
+
+    let fit = match fit_layout(size, !matches!(input_mode, InputMode::Normal)) {
+        Some(fit) => fit,
+        None => {
+            let message = Paragraph::new("Terminal too small").wrap(Wrap { trim: true });
+            f.render_widget(message, size);
+            return;
+        }
+    };
+
+    // Adjust layout to include commands footer
+    let mut constraints = match input_mode {
+        InputMode::Normal => vec![
+            Constraint::Min(3), // List of paths
+        ],
+        InputMode::InsertAfter
+        | InputMode::InsertBefore
+        | InputMode::InsertFirst
+        | InputMode::InsertLast
+        | InputMode::ExportScript
+        | InputMode::Sibling
+        | InputMode::Edit
+        | InputMode::Search
+        | InputMode::Which
+        | InputMode::RemoveMatching => vec![
+            Constraint::Min(3),
+            Constraint::Length(3), // Input box
+        ],
+        InputMode::Paste => vec![
+            Constraint::Min(3),
+            Constraint::Percentage(40), // Paste box
+        ],
+    };
+    if fit.footer {
+        constraints.push(Constraint::Length(3)); // Commands footer
+    }
+    if fit.status {
+        constraints.push(Constraint::Length(1)); // Status bar
+    }
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(fit.margin)
+        .constraints(constraints)
+        .split(size);
+
+    // Name the variables above the list when editing several
+    let list_area = if app.tabs.is_empty() {
+        chunks[0]
+    } else {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(1), Constraint::Min(0)])
+            .split(chunks[0]);
+        draw_tabs(f, app, rows[0]);
+        rows[1]
+    };
+
+    // Give the scratch list a pane of its own while it holds entries
+    let list_area = if app.scratch.is_empty() || !fit.side_panes {
+        list_area
+    } else {
+        let panes = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(65), Constraint::Percentage(35)])
+            .split(list_area);
+        let items: Vec<ListItem> = app
+            .scratch
+            .iter()
+            .rev()
+            .map(|p| ListItem::new(display_entry(p)))
+            .collect();
+        let scratch = List::new(items).block(app.theme.block().title("Scratch (latest first)"));
+        f.render_widget(scratch, panes[1]);
+        panes[0]
+    };
+
+    // Describe the selected, or pinned, entry beside the list
+    let list_area = if app.show_details && fit.side_panes {
+        let panes = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+            .split(list_area);
+        draw_details(f, app, panes[1]);
+        panes[0]
+    } else {
+        list_area
+    };
+
+    // List what the selected entry provides beside the list
+    let list_area = if app.show_executables && fit.side_panes {
+        let panes = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+            .split(list_area);
+        let selected = app.list_state.selected().map(|i| app.paths[i].as_path());
+        draw_executables(f, selected, &mut app.executables, &app.theme, panes[1]);
+        panes[0]
+    } else {
+        list_area
+    };
+
+    if app.raw_view {
+        // Render the joined PATH string as exported, wrapped to the available width
+        let raw = Paragraph::new(join_entries(&app.enabled(&app.paths)))
+            .block(app.theme.block().title("PATH (raw)"))
+            .wrap(Wrap { trim: false });
+        f.render_widget(raw, list_area);
+    } else {
+        // Create the list items, colouring session-added entries if asked to
+        let origins = app
+            .base
+            .as_ref()
+            .filter(|_| app.show_origins)
+            .map(|base| classify_origins(base, &app.paths, &app.dedupe_opts));
+        app.list_area = app.theme.block().inner(list_area);
+        app.links.clear();
+        let visible = app.visible();
+        let marked = app.marked();
+        let duplicates = duplicate_flags(&app.paths, &app.dedupe_opts);
+        let missing: Vec<bool> = visible
+            .iter()
+            .map(|&i| app.missing.is_missing(&app.paths[i]))
+            .collect();
+        let ignored: Vec<bool> = visible
+            .iter()
+            .map(|&i| app.missing.is_ignored(&app.paths[i]))
+            .collect();
+
+        // Scroll so the selection stays in view, and only build the rows shown
+        let selected = app
+            .list_state
+            .selected()
+            .and_then(|selected| visible.iter().position(|&i| i == selected));
+        let height = app.list_area.height as usize;
+        app.list_offset = list_offset(app.list_offset, selected, height, visible.len());
+        let number_width = app.paths.len().to_string().len();
+        let folded = app.folded();
+        if app.show_counts {
+            app.executable_counts.request(&app.paths);
+        }
+        let items: Vec<ListItem> = visible
+            .iter()
+            .enumerate()
+            .skip(app.list_offset)
+            .take(height)
+            .map(|(row, &i)| {
+                let mut style = match origins.as_ref().map(|origins| origins[i]) {
+                    _ if missing[row] => Style::default().fg(app.theme.error),
+                    _ if duplicates[i] => Style::default().fg(app.theme.duplicate),
+                    Some(Origin::Added) => Style::default().fg(app.theme.added),
+                    _ => match app.system_len.map(|len| section_of(i, len)) {
+                        Some(Hive::System) => Style::default().fg(app.theme.system),
+                        _ => Style::default(),
+                    },
+                };
+                if app.mandatory_first.as_ref() == Some(&app.paths[i]) {
+                    style = style.add_modifier(Modifier::UNDERLINED);
+                }
+                if app.disabled.contains(&app.paths[i]) {
+                    style = style.add_modifier(Modifier::DIM | Modifier::CROSSED_OUT);
+                }
+                if marked.contains(&i) {
+                    style = style.add_modifier(Modifier::REVERSED);
+                }
+                if let Some(background) = row_background(row, app.zebra, &app.theme) {
+                    style = style.bg(background);
+                }
+                let mut label = display_entry(&app.paths[i]);
+                if let Some((prefix, run)) = folded.iter().find(|(_, run)| run.start == i) {
+                    label = format!("▸ {} ({} entries)", prefix.display(), run.len());
+                    style = style.add_modifier(Modifier::BOLD);
+                }
+                if app.show_numbers {
+                    label = format!("{:>width$} {}", i + 1, label, width = number_width);
+                }
+                if app.show_canonical {
+                    if let Some(resolved) = app.missing.resolved(&app.paths[i]) {
+                        label.push_str(resolved);
+                    }
+                }
+                if ignored[row] {
+                    label.push_str("  [ignored]");
+                    style = style.add_modifier(Modifier::DIM);
+                }
+                if is_network_fs(&app.paths[i], &app.mounts) {
+                    label.push_str("  (network)");
+                }
+                if missing[row] {
+                    label.push_str("  ✗ missing");
+                }
+                if duplicates[i] {
+                    label.push_str("  [dup]");
+                }
+                if is_relative_entry(&app.paths[i]) {
+                    label.push_str("  ⚠ relative");
+                }
+                if app.pinned.contains(&app.paths[i]) {
+                    label.push_str("  [pinned]");
+                }
+                if app.show_counts {
+                    match app.executable_counts.get(&app.paths[i]) {
+                        None => label.push_str("  · counting…"),
+                        Some(None) => {}
+                        Some(Some(0)) => {
+                            label.push_str("  · no executables");
+                            style = style.fg(app.theme.warning);
+                        }
+                        Some(Some(1)) => label.push_str("  · 1 executable"),
+                        Some(Some(n)) => label.push_str(&format!("  · {} executables", n)),
+                    }
+                }
+                ListItem::new(label).style(style)
+            })
+            .collect();
+
+        // Create the list widget
+        let list = List::new(items)
+            .block(app.theme.block().title(match &app.recording {
+                Some((register, _)) => format!("{} · recording @{}", list_title(app), register),
+                None => list_title(app),
+            }))
+            .highlight_style(
+                Style::default()
+                    .fg(app.theme.highlight)
+                    .add_modifier(Modifier::BOLD),
+            )
+            .highlight_symbol(">> ");
+
+        // Render the list widget, selecting by position among the shown rows
+        let mut state = ListState::default();
+        state.select(selected.map(|row| row - app.list_offset));
+        f.render_stateful_widget(list, list_area, &mut state);
+        if visible.len() > height {
+            let scrollbar = Scrollbar {
+                offset: app.list_offset,
+                len: visible.len(),
+            };
+            // Drawn over the right border, next to the rows
+            let column = Rect::new(
+                list_area.right().saturating_sub(1),
+                app.list_area.y,
+                1,
+                app.list_area.height,
+            );
+            f.render_widget(scrollbar, column);
+        }
+
+        if app.hyperlinks {
+            // The list reserves room for the highlight symbol on every row
+            let mut indent = if selected.is_some() { 3 } else { 0 };
+            if app.show_numbers {
+                indent += number_width as u16 + 1;
+            }
+            let rows = visible
+                .iter()
+                .enumerate()
+                .skip(app.list_offset)
+                .take(height);
+            for (y, (row, &i)) in (app.list_area.y..).zip(rows) {
+                // Folded rows show their prefix rather than the entry
+                if missing[row] || folded.iter().any(|(_, run)| run.start == i) {
+                    continue;
+                }
+                let width = display_entry(&app.paths[i]).chars().count() as u16;
+                let width = width.min(app.list_area.width.saturating_sub(indent));
+                let area = Rect::new(app.list_area.x + indent, y, width, 1);
+                app.links.push((area, app.paths[i].clone()));
+            }
+        }
+    }
+
+    // Determine which additional widget to render based on input mode
+    let mut commands_chunk_index = 1;
+    match input_mode {
+        InputMode::Normal => {}
+        InputMode::InsertAfter => {
+            let input_block = Paragraph::new(input)
+                .style(Style::default().fg(app.theme.accent))
+                .block(app.theme.block().title("Insert After"));
+            f.render_widget(input_block, chunks[1]);
+            f.set_cursor(chunks[1].x + input.len() as u16 + 1, chunks[1].y + 1);
+            commands_chunk_index = 2;
+        }
+        InputMode::InsertBefore => {
+            let input_block = Paragraph::new(input)
+                .style(Style::default().fg(app.theme.accent))
+                .block(app.theme.block().title("Insert Before"));
+            f.render_widget(input_block, chunks[1]);
+            f.set_cursor(chunks[1].x + input.len() as u16 + 1, chunks[1].y + 1);
+            commands_chunk_index = 2;
+        }
+        InputMode::InsertFirst | InputMode::InsertLast => {
+            let title = match input_mode {
+                InputMode::InsertFirst => "Insert at Top",
+                _ => "Insert at Bottom",
+            };
+            let input_block = Paragraph::new(input)
+                .style(Style::default().fg(app.theme.accent))
+                .block(app.theme.block().title(title));
+            f.render_widget(input_block, chunks[1]);
+            f.set_cursor(chunks[1].x + input.len() as u16 + 1, chunks[1].y + 1);
+            commands_chunk_index = 2;
+        }
+        InputMode::ExportScript => {
+            let input_block = Paragraph::new(input)
+                .style(Style::default().fg(app.theme.accent))
+                .block(app.theme.block().title("Export script to"));
+            f.render_widget(input_block, chunks[1]);
+            f.set_cursor(chunks[1].x + input.len() as u16 + 1, chunks[1].y + 1);
+            commands_chunk_index = 2;
+        }
+        InputMode::Search => {
+            let input_block = Paragraph::new(input)
+                .style(Style::default().fg(app.theme.accent))
+                .block(app.theme.block().title("Search"));
+            f.render_widget(input_block, chunks[1]);
+            f.set_cursor(chunks[1].x + input.len() as u16 + 1, chunks[1].y + 1);
+            commands_chunk_index = 2;
+        }
+        InputMode::Which => {
+            let input_block = Paragraph::new(input)
+                .style(Style::default().fg(app.theme.accent))
+                .block(app.theme.block().title("Which entry provides"));
+            f.render_widget(input_block, chunks[1]);
+            f.set_cursor(chunks[1].x + input.len() as u16 + 1, chunks[1].y + 1);
+            commands_chunk_index = 2;
+        }
+        InputMode::RemoveMatching => {
+            let mut title = vec![Span::raw("Remove entries matching (regex)")];
+            if let Some(Err(err)) = &app.removal_regex {
+                title.push(Span::styled(
+                    format!(" {}", err),
+                    Style::default().fg(app.theme.error),
+                ));
+            }
+            let input_block = Paragraph::new(input)
+                .style(Style::default().fg(app.theme.accent))
+                .block(app.theme.block().title(Spans::from(title)));
+            f.render_widget(input_block, chunks[1]);
+            f.set_cursor(chunks[1].x + input.len() as u16 + 1, chunks[1].y + 1);
+            commands_chunk_index = 2;
+        }
+        InputMode::Edit => {
+            let input_block = Paragraph::new(input)
+                .style(Style::default().fg(app.theme.accent))
+                .block(app.theme.block().title("Edit"));
+            f.render_widget(input_block, chunks[1]);
+            f.set_cursor(chunks[1].x + input.len() as u16 + 1, chunks[1].y + 1);
+            commands_chunk_index = 2;
+        }
+        InputMode::Sibling => {
+            let input_block = Paragraph::new(input)
+                .style(Style::default().fg(app.theme.accent))
+                .block(app.theme.block().title("Insert sibling named"));
+            f.render_widget(input_block, chunks[1]);
+            f.set_cursor(chunks[1].x + input.len() as u16 + 1, chunks[1].y + 1);
+            commands_chunk_index = 2;
+        }
+        InputMode::Paste => {
+            let input_block = Paragraph::new(input)
+                .style(Style::default().fg(app.theme.accent))
+                .block(
+                    app.theme
+                        .block()
+                        .title("Replace all (one entry per line, Ctrl+S: apply, Esc: cancel)"),
+                );
+            f.render_widget(input_block, chunks[1]);
+            let last_line = input.rsplit('\n').next().unwrap_or_default();
+            let line_count = input.matches('\n').count() as u16;
+            f.set_cursor(
+                chunks[1].x + last_line.len() as u16 + 1,
+                chunks[1].y + line_count + 1,
+            );
+            commands_chunk_index = 2;
+        }
+    }
+
+    if fit.status {
+        let status_bar =
+            Paragraph::new(status_line(app)).style(Style::default().fg(app.theme.status));
+        f.render_widget(status_bar, chunks[chunks.len() - 1]);
+    }
+
+    if !app.completions.is_empty() && !matches!(input_mode, InputMode::Paste) {
+        draw_completions(f, app, chunks[1]);
+    }
+
+    match app.popup {
+        Some(Popup::Histogram) => draw_histogram(f, &app.paths, &app.theme, size),
+        Some(Popup::FrontPreview) => draw_front_preview(f, app, size),
+        Some(Popup::SaveTargets) => draw_save_targets(f, app, size),
+        Some(Popup::ConfirmSave) => draw_confirm_save(f, app, size),
+        Some(Popup::Command) => draw_command(f, app, size),
+        Some(Popup::History) => draw_history(f, app, size),
+        Some(Popup::ConfirmDelete | Popup::Prune | Popup::RemoveMatching | Popup::Relative) => {
+            draw_confirm_delete(f, app, size)
+        }
+        Some(Popup::ConfirmAdd) => draw_confirm_add(f, app, size),
+        Some(Popup::Help) => draw_help(f, app, size),
+        Some(Popup::Sort) => draw_sort(f, app, size),
+        Some(Popup::Fuzzy) => draw_fuzzy(f, app, size),
+        Some(Popup::Shadowing) => draw_shadowing(f, app, size),
+        Some(Popup::Diff) => draw_diff(f, app, size),
+        Some(Popup::Browse) => draw_browse(f, app, size),
+        Some(Popup::Suggest) => draw_suggest(f, app, size),
+        None => {}
+    }
+
+    // Render the commands footer
+    if !fit.footer {
+        return;
+    }
+    let mut spans = Vec::new();
+    for binding in FOOTER.iter().filter_map(|&key| binding_for(key)) {
+        spans.push(Span::styled(
+            app.keys.labels(binding.keys),
+            Style::default().add_modifier(Modifier::BOLD),
+        ));
+        spans.push(Span::raw(format!(": {}   ", binding.action)));
+    }
+    let commands = vec![Spans::from(spans)];
+
+    let mut commands_block = app.theme.block();
+    let length = joined_length(&app.enabled(&app.paths));
+    let warning = near_length_limit(length, app.length_limit).then(|| {
+        format!(
+            "{} is {} bytes long, close to the {} byte limit",
+            app.var, length, app.length_limit
+        )
+    });
+    let messages = app.messages.current(Instant::now()).join(" · ");
+    let message = Some(messages).filter(|m| !m.is_empty()).or(warning);
+    if let Some(message) = message {
+        commands_block = commands_block.title(Span::styled(
+            message,
+            Style::default().fg(app.theme.warning),
+        ));
+    }
+    let commands_paragraph = Paragraph::new(commands).block(commands_block);
+
+    f.render_widget(commands_paragraph, chunks[commands_chunk_index]);
+}
+
+/// Narrowest terminal pathctl will draw its screen in.
+const MIN_WIDTH: u16 = 20;
+
+/// Width below which the side panes are folded away to leave the list room.
+const SIDE_PANES_WIDTH: u16 = 60;
+
+/// Which optional parts of the screen fit in the terminal.
+struct Fit {
+    margin: u16,
+    footer: bool,
+    status: bool,
+    side_panes: bool,
+}
+
+/// Decides what to draw in a terminal of `size`, giving up the margin, then
+/// the status bar, then the footer as it gets shorter. Returns `None` when
+/// not even the list, and the input box if `input` is open, fit.
+fn fit_layout(size: Rect, input: bool) -> Option<Fit> {
+    if size.width < MIN_WIDTH {
+        return None;
+    }
+    // The list's borders and a row, plus the input box while it is open
+    let required = if input { 6 } else { 3 };
+    let mut spare = size.height.checked_sub(required)?;
+    let mut take = |rows: u16| {
+        let fits = spare >= rows;
+        if fits {
+            spare -= rows;
+        }
+        fits
+    };
+    let footer = take(3);
+    let status = take(1);
+    let margin = if take(2) { 1 } else { 0 };
+    Some(Fit {
+        margin,
+        footer,
+        status,
+        side_panes: size.width >= SIDE_PANES_WIDTH,
+    })
+}
+
+/// Where the selection is and how far the list is from the one pathctl
+/// started with, e.g. `entry 12/63 · 3 changes · modified`.
+fn status_line(app: &App) -> String {
+    let position = match app.list_state.selected() {
+        Some(selected) => format!("entry {}/{}", selected + 1, app.paths.len()),
+        None => "no entries".to_string(),
+    };
+    let shown = match app.filtering() {
+        true => format!(" ({} shown)", app.visible().len()),
+        false => String::new(),
+    };
+    let diff = diff_paths(&app.original, &app.enabled(&app.paths), &app.dedupe_opts);
+    let changes = diff.removed.len()
+        + diff
+            .tags
+            .iter()
+            .filter(|tag| **tag != DiffTag::Unchanged)
+            .count();
+    let state = match app.paths != app.original || !app.disabled.is_empty() {
+        true => "modified",
+        false => "unmodified",
+    };
+    format!("{}{} · {} changes · {}", position, shown, changes, state)
+}
+
+/// A key binding as listed in the help popup. Alternatives such as `j` and
+/// the down arrow share one binding.
+struct Binding {
+    keys: &'static [&'static str],
+    action: &'static str,
+}
+
+const fn bind(keys: &'static [&'static str], action: &'static str) -> Binding {
+    Binding { keys, action }
+}
+
+/// Every key binding, grouped by where it applies. `?` shows them all and the
+/// footer picks a few, so this is the one place to list a new key.
+const KEYMAP: &[(&str, &[Binding])] = &[
+    (
+        "Moving around",
+        &[
+            bind(&["↑", "k"], "Up"),
+            bind(&["↓", "j"], "Down"),
+            bind(&["1-9"], "Count for the next j/k, K/J or dd, as in 5j"),
+            bind(&["12G", "12 Enter"], "Entry number 12"),
+            bind(&["gg", "Home"], "First entry"),
+            bind(&["G", "End"], "Last entry"),
+            bind(&["PgUp", "PgDn"], "Page up/down"),
+            bind(&["Ctrl+U", "Ctrl+D"], "Half a page up/down"),
+            bind(
+                &["{", "}"],
+                "Previous/next group, as the grouped view folds them",
+            ),
+            bind(&["/"], "Search"),
+            bind(&["n"], "Next search match"),
+            bind(&["Ctrl+P"], "Jump to entry"),
+            bind(&[":"], "Which entries provide a command"),
+            bind(&["Esc"], "Clear the search or the marks"),
+        ],
+    ),
+    (
+        "Editing",
+        &[
+            bind(&["a"], "Insert after"),
+            bind(&["b"], "Insert before"),
+            bind(&["I", "A"], "Insert at the top/bottom"),
+            bind(&["o"], "Browse for a directory to insert"),
+            bind(&["t"], "Add a common tool directory"),
+            bind(&["e"], "Edit"),
+            bind(&["C"], "Duplicate and edit"),
+            bind(&["N"], "Insert sibling"),
+            bind(&["-", "+"], "Replace with parent/child dir"),
+            bind(&["dd"], "Cut"),
+            bind(&["yy"], "Yank, also to the clipboard"),
+            bind(&["Y"], "Copy the whole PATH to the clipboard"),
+            bind(&["p", "P"], "Put after/before"),
+            bind(&[">", "<"], "To/from scratch"),
+            bind(&["K", "J"], "Move up/down"),
+            bind(&["Shift+↑", "Shift+↓"], "Move up/down"),
+            bind(&["f"], "Move to front"),
+            bind(&["Space"], "Mark entry"),
+            bind(&["V"], "Mark range"),
+            bind(&["D"], "Dedupe"),
+            bind(&["M"], "Remove missing directories"),
+            bind(&["F"], "Remove entries matching a regex"),
+            bind(&["z"], "Remove or absolutize relative entries"),
+            bind(&["L"], "Toggle keeping the first/last duplicate"),
+            bind(&["s"], "Sort"),
+            bind(&["R"], "Replace all"),
+            bind(&["E"], "Expand variables"),
+            bind(&["S"], "Split joined entry"),
+            bind(&["T"], "Trim"),
+            bind(&["x"], "Disable/enable"),
+            bind(&["!"], "Always first"),
+            bind(&["gp"], "Pin against dd, M and F"),
+            bind(&["Qa", "Q"], "Record macro a/stop recording"),
+            bind(&["@a", "@@"], "Replay macro a/the last macro"),
+            bind(&["u", "Ctrl+R"], "Undo/redo"),
+            bind(&["U"], "Undo history"),
+        ],
+    ),
+    (
+        "Viewing",
+        &[
+            bind(&["r"], "Raw view"),
+            bind(&["v"], "Show where entries resolve to"),
+            bind(&["#"], "Line numbers"),
+            bind(&["="], "Executable count of each entry"),
+            bind(&["i"], "Details"),
+            bind(&["m"], "Pin details"),
+            bind(&["Tab"], "Executables of the selected entry"),
+            bind(
+                &["BackTab", "Alt+1-9"],
+                "Next/numbered tab, with --tab (Tab and digits are taken)",
+            ),
+            bind(&["B"], "Origins"),
+            bind(&["O"], "Only this repo"),
+            bind(&["Enter"], "Why missing"),
+            bind(&["H"], "Histogram"),
+            bind(&["^"], "Fold entries sharing a prefix"),
+            bind(&["→", "←"], "Open/close the fold, with ^"),
+            bind(&["gd"], "Original beside edited list"),
+            bind(&["gx", "ge"], "Open in the file manager/$EDITOR"),
+            bind(&["W"], "Shadowed commands"),
+            bind(&["?"], "Help"),
+        ],
+    ),
+    (
+        "Output",
+        &[
+            bind(&["w"], "Save"),
+            bind(&["c"], "Copy add command"),
+            bind(&["Ctrl+G"], "Show command"),
+            bind(&["X"], "Export script"),
+            bind(&["q", "Esc", "Ctrl+C"], "Quit"),
+        ],
+    ),
+    (
+        "Input boxes",
+        &[
+            bind(&["Enter"], "Accept"),
+            bind(&["Esc"], "Cancel"),
+            bind(
+                &["Tab", "BackTab"],
+                "Complete a directory, again for the next/previous one",
+            ),
+            bind(&["Ctrl+S"], "Apply the pasted list (replace all)"),
+        ],
+    ),
+    (
+        "Popups",
+        &[
+            bind(&["↑", "k", "↓", "j"], "Move through pickers"),
+            bind(&["Ctrl+P", "Ctrl+N"], "Move through jump matches"),
+            bind(&["Enter"], "Choose"),
+            bind(&["y"], "Confirm"),
+            bind(&["Esc"], "Close"),
+        ],
+    ),
+];
+
+/// Actions of the list that the `[keys]` section of the config file can
+/// move to another key, with their default key. Two-key commands such as
+/// `dd` repeat the key.
+const ACTIONS: &[(&str, &str)] = &[
+    ("up", "k"),
+    ("down", "j"),
+    ("top", "g"),
+    ("bottom", "G"),
+    ("half-page-down", "Ctrl+D"),
+    ("half-page-up", "Ctrl+U"),
+    ("previous-group", "{"),
+    ("next-group", "}"),
+    ("search", "/"),
+    ("next-match", "n"),
+    ("jump", "Ctrl+P"),
+    ("which", ":"),
+    ("insert-after", "a"),
+    ("insert-before", "b"),
+    ("insert-first", "I"),
+    ("insert-last", "A"),
+    ("browse", "o"),
+    ("suggestions", "t"),
+    ("edit", "e"),
+    ("duplicate", "C"),
+    ("sibling", "N"),
+    ("parent", "-"),
+    ("child", "+"),
+    ("delete", "d"),
+    ("yank", "y"),
+    ("copy-all", "Y"),
+    ("put-after", "p"),
+    ("put-before", "P"),
+    ("to-scratch", ">"),
+    ("from-scratch", "<"),
+    ("move-up", "K"),
+    ("move-down", "J"),
+    ("front", "f"),
+    ("mark", "Space"),
+    ("mark-range", "V"),
+    ("dedupe", "D"),
+    ("prune", "M"),
+    ("remove-matching", "F"),
+    ("fix-relative", "z"),
+    ("keep-policy", "L"),
+    ("sort", "s"),
+    ("replace-all", "R"),
+    ("expand", "E"),
+    ("split", "S"),
+    ("trim", "T"),
+    ("disable", "x"),
+    ("always-first", "!"),
+    ("record-macro", "Q"),
+    ("replay-macro", "@"),
+    ("undo", "u"),
+    ("redo", "Ctrl+R"),
+    ("history", "U"),
+    ("raw", "r"),
+    ("resolve", "v"),
+    ("line-numbers", "#"),
+    ("executable-counts", "="),
+    ("details", "i"),
+    ("pin-details", "m"),
+    ("executables", "Tab"),
+    ("next-tab", "BackTab"),
+    ("origins", "B"),
+    ("repo", "O"),
+    ("why-missing", "Enter"),
+    ("histogram", "H"),
+    ("group", "^"),
+    ("open-group", "Right"),
+    ("close-group", "Left"),
+    ("shadowing", "W"),
+    ("help", "?"),
+    ("save", "w"),
+    ("copy-add", "c"),
+    ("command", "Ctrl+G"),
+    ("export", "X"),
+    ("quit", "q"),
+];
+
+/// A key as the list tells keys apart: Shift is part of the character.
+type KeyId = (KeyCode, KeyModifiers);
+
+fn key_id(key: &KeyEvent) -> KeyId {
+    match key.code {
+        KeyCode::Char(_) => (key.code, key.modifiers - KeyModifiers::SHIFT),
+        _ => (key.code, key.modifiers),
+    }
+}
+
+/// Keys moved away from their default in the config file.
+#[derive(Debug, Default)]
+struct Keys {
+    /// The default key to act on for each remapped key.
+    remap: HashMap<KeyId, KeyEvent>,
+    /// Default keys of remapped actions that nothing took over.
+    unbound: HashSet<KeyId>,
+    /// The key as written in the config, by default key name.
+    labels: HashMap<&'static str, String>,
+}
+
+impl Keys {
+    /// Checks `action = key` pairs and builds the remapping. Fails on
+    /// unknown actions or keys, two actions on one key, and keys still used
+    /// by an action that was not remapped.
+    fn new(bindings: &[(String, String)]) -> Result<Keys, String> {
+        let mut keys = Keys::default();
+        let mut taken: HashMap<KeyId, &str> = HashMap::new();
+        for (action, key) in bindings {
+            let Some(&(_, default)) = ACTIONS.iter().find(|(name, _)| name == action) else {
+                return Err(format!("unknown action: {}", action));
+            };
+            let Some(event) = parse_key_name(key) else {
+                return Err(format!("unknown key for {}: {}", action, key));
+            };
+            let id = key_id(&event);
+            if matches!(id, (KeyCode::Char('0'..='9') | KeyCode::Esc, _))
+                || id == (KeyCode::Char('c'), KeyModifiers::CONTROL)
+            {
+                return Err(format!(
+                    "{} is reserved and cannot be bound to {}",
+                    key, action
+                ));
+            }
+            if let Some(other) = taken.insert(id, action) {
+                return Err(format!("{} is bound to both {} and {}", key, other, action));
+            }
+            let Some(default_key) = parse_key_name(default) else {
+                return Err(format!("unknown key for {}: {}", action, default));
+            };
+            keys.remap.insert(id, default_key);
+            keys.labels.insert(default, key.clone());
+        }
+
+        for &(action, default) in ACTIONS {
+            let Some(id) = parse_key_name(default).map(|key| key_id(&key)) else {
+                continue;
+            };
+            if keys.labels.contains_key(default) {
+                if !taken.contains_key(&id) {
+                    keys.unbound.insert(id);
+                }
+            } else if let Some(other) = taken.get(&id) {
+                return Err(format!(
+                    "{} is already bound to {}, remap that too to bind it to {}",
+                    default, action, other
+                ));
+            }
+        }
+        Ok(keys)
+    }
+
+    /// The key to handle in place of `key`, or `None` when it was left
+    /// without an action.
+    fn translate(&self, key: KeyEvent) -> Option<KeyEvent> {
+        let id = key_id(&key);
+        match self.remap.get(&id) {
+            Some(&default) => Some(default),
+            None if self.unbound.contains(&id) => None,
+            None => Some(key),
+        }
+    }
+
+    /// Key names of a binding as currently mapped, joined for display.
+    fn labels(&self, keys: &[&str]) -> String {
+        let label = |key: &str| {
+            if let Some(label) = self.labels.get(key) {
+                return label.clone();
+            }
+            // Two-key commands such as `dd`
+            let mut chars = key.chars();
+            match (chars.next(), chars.next(), chars.next()) {
+                (Some(a), Some(b), None) if a == b => match self.labels.get(&key[..1]) {
+                    Some(label) if label.chars().count() == 1 => label.repeat(2),
+                    Some(label) => format!("{} {}", label, label),
+                    None => key.to_string(),
+                },
+                _ => key.to_string(),
+            }
+        };
+        keys.iter()
+            .map(|key| label(key))
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+}
+
+/// Keys of the bindings shown in the footer, by their first key.
+const FOOTER: &[&str] = &["a", "b", "e", "dd", "p", "/", "u", "w", "?", "q"];
+
+/// The binding listed first under `key`.
+fn binding_for(key: &str) -> Option<&'static Binding> {
+    KEYMAP
+        .iter()
+        .flat_map(|(_, bindings)| bindings.iter())
+        .find(|binding| binding.keys.first() == Some(&key))
+}
+
+/// Computes the first row to show so the selected one is in view, scrolling
+/// as little as possible from `offset`.
+fn list_offset(offset: usize, selected: Option<usize>, height: usize, len: usize) -> usize {
+    let offset = offset.min(len.saturating_sub(1));
+    match selected {
+        Some(selected) if selected >= offset + height => selected + 1 - height.max(1),
+        Some(selected) if selected < offset => selected,
+        _ => offset,
+    }
+}
+
+/// Where the list is scrolled to, as a track with a thumb spanning the rows
+/// shown, drawn in a one column wide area as tall as the list.
+struct Scrollbar {
+    offset: usize,
+    len: usize,
+}
+
+impl Widget for Scrollbar {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let (start, size) = scrollbar_thumb(self.offset, area.height as usize, self.len);
+        for row in 0..area.height as usize {
+            let symbol = if (start..start + size).contains(&row) {
+                "█"
+            } else {
+                "│"
+            };
+            buf.get_mut(area.x, area.y + row as u16).set_symbol(symbol);
+        }
+    }
+}
+
+/// First row and length of the scrollbar thumb for a list of `len` rows
+/// showing `height` of them from `offset`.
+fn scrollbar_thumb(offset: usize, height: usize, len: usize) -> (usize, usize) {
+    if len <= height || height == 0 {
+        return (0, height);
+    }
+    let size = (height * height / len).max(1);
+    let start = (offset * (height - size) + (len - height) / 2) / (len - height);
+    (start.min(height - size), size)
+}
+
+/// The cells of each row in `links` turned into an OSC 8 hyperlink, to be
+/// drawn again over the finished frame. The escapes go into copies of the
+/// first and last cell, as in the frame's own buffer they would each count
+/// as dozens of columns. Rows something else has been drawn over since,
+/// such as a popup, are left alone.
+fn hyperlink_cells(buffer: &Buffer, links: &[(Rect, PathBuf)]) -> Vec<(u16, u16, Cell)> {
+    let mut cells = Vec::new();
+    for (area, path) in links {
+        let mut row: Vec<(u16, u16, Cell)> = (area.left()..area.right())
+            .map(|x| (x, area.y, buffer.get(x, area.y).clone()))
+            .collect();
+        let shown: String = row
+            .iter()
+            .map(|(_, _, cell)| cell.symbol.as_str())
+            .collect();
+        if row.is_empty() || !display_entry(path).starts_with(&shown) {
+            continue;
+        }
+        row[0].2.symbol = format!("\x1b]8;;{}\x1b\\{}", file_uri(path), row[0].2.symbol);
+        if let Some((_, _, last)) = row.last_mut() {
+            last.symbol.push_str("\x1b]8;;\x1b\\");
+        }
+        cells.extend(row);
+    }
+    cells
+}
+
+/// The `file://` URI of `path`, for OSC 8 hyperlinks.
+fn file_uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+    for byte in path.to_string_lossy().bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => {
+                uri.push(byte as char)
+            }
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
+
+/// Default for `--length-limit`. Linux rejects a single `execve` string over
+/// 128 KiB with `E2BIG` and Windows caps variables at 32767 characters; the
+/// lower of the two keeps the value portable.
+const DEFAULT_LENGTH_LIMIT: usize = 32_767;
+
+/// Length in bytes of the value the list joins into.
+fn joined_length(paths: &[PathBuf]) -> usize {
+    join_entries(paths).len()
+}
+
+/// Whether `length` is within 10% of `limit`, or past it.
+fn near_length_limit(length: usize, limit: usize) -> bool {
+    length.saturating_mul(10) >= limit.saturating_mul(9)
+}
+
+/// How long `MissingCache` trusts what it found.
+const MISSING_TTL: Duration = Duration::from_secs(5);
+
+/// What `MissingCache` found out about an entry.
+#[derive(Debug, Clone, Copy)]
+struct EntryFlags {
+    /// Not an existing directory.
+    missing: bool,
+    /// Holds a `.pathctl-ignore` marker.
+    ignored: bool,
+}
+
+/// Remembers which entries are not existing directories and which are
+/// marked ignored, so drawing does not stat every entry on every frame.
+#[derive(Debug, Default)]
+struct MissingCache {
+    flags: HashMap<PathBuf, EntryFlags>,
+    /// When the remembered results started being collected.
+    since: Option<Instant>,
+    /// The `resolved_label` of each entry shown while `v` is on. Kept until
+    /// `v` is toggled again rather than expiring, since resolving is slower.
+    resolved: HashMap<PathBuf, Option<String>>,
+}
+
+impl MissingCache {
+    fn is_missing(&mut self, path: &Path) -> bool {
+        self.flags(path).missing
+    }
+
+    fn is_ignored(&mut self, path: &Path) -> bool {
+        self.flags(path).ignored
+    }
+
+    fn resolved(&mut self, path: &Path) -> Option<&str> {
+        self.resolved
+            .entry(path.to_path_buf())
+            .or_insert_with(|| resolved_label(path))
+            .as_deref()
+    }
+
+    /// The flags of `path`, looked up the first time it is asked about
+    /// since the remembered results last expired.
+    fn flags(&mut self, path: &Path) -> EntryFlags {
+        let expired = match self.since {
+            Some(since) => since.elapsed() >= MISSING_TTL,
+            None => true,
+        };
+        if expired {
+            self.flags.clear();
+            self.since = Some(Instant::now());
+        }
+        *self.flags.entry(path.to_path_buf()).or_insert_with(|| {
+            let missing = !path.is_dir();
+            EntryFlags {
+                missing,
+                // A missing directory holds no marker, so skip the stat
+                ignored: !missing && is_pathctl_ignored(path),
+            }
+        })
+    }
+}
+
+/// Counts the executables of entries on a background thread, so slow or
+/// network directories do not hold up drawing. Counts are kept until the
+/// cache is cleared.
+struct ExecutableCountCache {
+    /// Finished counts, `None` for entries that are not readable directories.
+    counts: HashMap<PathBuf, Option<usize>>,
+    /// Entries handed to a scanning thread whose count has not arrived yet.
+    scanning: HashSet<PathBuf>,
+    sender: Sender<(PathBuf, Option<usize>)>,
+    receiver: Receiver<(PathBuf, Option<usize>)>,
+}
+
+impl ExecutableCountCache {
+    fn new() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        ExecutableCountCache {
+            counts: HashMap::new(),
+            scanning: HashSet::new(),
+            sender,
+            receiver,
+        }
+    }
+
+    /// Collects the counts finished so far and starts one thread counting
+    /// the entries of `paths` that are neither known nor being counted.
+    fn request(&mut self, paths: &[PathBuf]) {
+        for (path, count) in self.receiver.try_iter() {
+            self.scanning.remove(&path);
+            self.counts.insert(path, count);
+        }
+        let mut unknown: Vec<PathBuf> = Vec::new();
+        for path in paths {
+            if !self.counts.contains_key(path) && self.scanning.insert(path.clone()) {
+                unknown.push(path.clone());
+            }
+        }
+        if unknown.is_empty() {
+            return;
+        }
+        let sender = self.sender.clone();
+        thread::spawn(move || {
+            for path in unknown {
+                let (scanned, _) = scan_executables(std::slice::from_ref(&path));
+                let count = scanned
+                    .into_iter()
+                    .next()
+                    .flatten()
+                    .map(|names| names.len());
+                if sender.send((path, count)).is_err() {
+                    return;
+                }
+            }
+        });
+    }
+
+    /// The count for `path`: `None` while it is still being counted.
+    fn get(&self, path: &Path) -> Option<Option<usize>> {
+        self.counts.get(path).copied()
+    }
+
+    /// Forgets every count so entries are counted again. Threads still
+    /// running report into the fresh cache.
+    fn clear(&mut self) {
+        self.counts.clear();
+        self.scanning.clear();
+    }
+}
+
+/// A line of `/proc/mounts`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct MountEntry {
+    mount_point: PathBuf,
+    fs_type: String,
+    /// Mounted with `noatime` or `nodiratime`, so directories keep a stale
+    /// access time.
+    stale_atime: bool,
+}
+
+/// Filesystem types whose lookups go over the network.
+const NETWORK_FS_TYPES: &[&str] = &[
+    "nfs",
+    "nfs4",
+    "cifs",
+    "smb3",
+    "smbfs",
+    "fuse.sshfs",
+    "sshfs",
+    "afs",
+    "ceph",
+    "glusterfs",
+    "9p",
+];
+
+/// Parses `/proc/mounts`, where spaces and other special characters in the
+/// mount point are written as octal escapes such as `\040`.
+fn parse_mounts(contents: &str) -> Vec<MountEntry> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace().skip(1);
+            let mount_point = fields.next()?;
+            let fs_type = fields.next()?;
+            let options = fields.next().unwrap_or_default();
+            Some(MountEntry {
+                mount_point: PathBuf::from(unescape_octal(mount_point)),
+                fs_type: fs_type.to_string(),
+                stale_atime: options
+                    .split(',')
+                    .any(|option| option == "noatime" || option == "nodiratime"),
+            })
+        })
+        .collect()
+}
+
+fn unescape_octal(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(at) = rest.find('\\') {
+        out.push_str(&rest[..at]);
+        let digits = rest.get(at + 1..at + 4).unwrap_or_default();
+        match u8::from_str_radix(digits, 8) {
+            Ok(byte) if digits.len() == 3 => {
+                out.push(byte as char);
+                rest = &rest[at + 4..];
+            }
+            _ => {
+                out.push('\\');
+                rest = &rest[at + 1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Reads the mount table. Only Linux exposes one in `/proc/mounts`;
+/// elsewhere no entry is flagged.
+fn load_mounts() -> Vec<MountEntry> {
+    if cfg!(target_os = "linux") {
+        std::fs::read_to_string("/proc/mounts")
+            .map(|contents| parse_mounts(&contents))
+            .unwrap_or_default()
+    } else {
+        Vec::new()
+    }
+}
+
+/// The most specific mount point containing `p`.
+fn mount_of<'a>(p: &Path, mounts: &'a [MountEntry]) -> Option<&'a MountEntry> {
+    mounts
+        .iter()
+        .filter(|mount| p.starts_with(&mount.mount_point))
+        .max_by_key(|mount| mount.mount_point.components().count())
+}
+
+/// Whether `p` lives on a network filesystem.
+fn is_network_fs(p: &Path, mounts: &[MountEntry]) -> bool {
+    mount_of(p, mounts).is_some_and(|mount| NETWORK_FS_TYPES.contains(&mount.fs_type.as_str()))
+}
+
+/// Colors used across the screen, picked with `--theme` or the config file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Theme {
+    /// Selected rows and the matched characters of the fuzzy finder.
+    highlight: Color,
+    /// Status messages and warnings in the footer.
+    warning: Color,
+    border: Color,
+    /// Text typed into input boxes and the bars of the histogram.
+    accent: Color,
+    /// The status bar at the bottom.
+    status: Color,
+    /// Missing entries, errors and removed lines.
+    error: Color,
+    /// Entries added on top of `--base` and added lines.
+    added: Color,
+    duplicate: Color,
+    /// Entries of the system section with `--combined`.
+    system: Color,
+    /// Background of every other row with `--zebra`.
+    zebra: Option<Color>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            highlight: Color::Yellow,
+            warning: Color::Yellow,
+            border: Color::Reset,
+            accent: Color::Cyan,
+            status: Color::Gray,
+            error: Color::Red,
+            added: Color::Green,
+            duplicate: Color::Magenta,
+            system: Color::Blue,
+            zebra: Some(Color::Indexed(236)),
+        }
+    }
+}
+
+impl Theme {
+    /// Names accepted by `--theme` and the config file.
+    const NAMES: [&'static str; 4] = ["default", "light", "solarized", "monochrome"];
+
+    /// Leaves everything in the terminal's own colors, relying on the
+    /// markers and text styles alone.
+    const MONOCHROME: Theme = Theme {
+        highlight: Color::Reset,
+        warning: Color::Reset,
+        border: Color::Reset,
+        accent: Color::Reset,
+        status: Color::Reset,
+        error: Color::Reset,
+        added: Color::Reset,
+        duplicate: Color::Reset,
+        system: Color::Reset,
+        zebra: None,
+    };
+
+    fn named(name: &str) -> Option<Theme> {
+        match name {
+            "default" => Some(Theme::default()),
+            // Yellow and cyan are hard to read on a light background
+            "light" => Some(Theme {
+                highlight: Color::Blue,
+                warning: Color::Red,
+                border: Color::DarkGray,
+                accent: Color::Magenta,
+                status: Color::DarkGray,
+                system: Color::Cyan,
+                zebra: Some(Color::Indexed(254)),
+                ..Theme::default()
+            }),
+            "solarized" => Some(Theme {
+                highlight: Color::Rgb(0xb5, 0x89, 0x00),
+                warning: Color::Rgb(0xcb, 0x4b, 0x16),
+                border: Color::Rgb(0x58, 0x6e, 0x75),
+                accent: Color::Rgb(0x2a, 0xa1, 0x98),
+                status: Color::Rgb(0x93, 0xa1, 0xa1),
+                error: Color::Rgb(0xdc, 0x32, 0x2f),
+                added: Color::Rgb(0x85, 0x99, 0x00),
+                duplicate: Color::Rgb(0xd3, 0x36, 0x82),
+                system: Color::Rgb(0x26, 0x8b, 0xd2),
+                zebra: Some(Color::Rgb(0x07, 0x36, 0x42)),
+            }),
+            "monochrome" => Some(Theme::MONOCHROME),
+            _ => None,
+        }
+    }
+
+    /// A bordered block in the theme's border color.
+    fn block(&self) -> Block<'static> {
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(self.border))
+    }
+}
+
+/// Settings read from the config file, see `config_path`.
+#[derive(Debug, Default, PartialEq)]
+struct Config {
+    /// Name of the color theme.
+    theme: Option<String>,
+    /// `action = key` pairs of the `[keys]` section, checked by `Keys::new`.
+    keys: Vec<(String, String)>,
+    /// Entries left disabled, by variable, from the `[disabled]` section.
+    disabled: HashMap<String, Vec<PathBuf>>,
+    /// Entries pinned against removal, by variable, from `[pinned]`.
+    pinned: HashMap<String, Vec<PathBuf>>,
+    /// Directories `t` suggests besides the built-in ones.
+    suggestions: Vec<PathBuf>,
+}
+
+/// Where the config file lives: `$XDG_CONFIG_HOME/pathctl/config.toml`,
+/// falling back to `~/.config`, or `%APPDATA%\pathctl\config.toml` on
+/// Windows.
+fn config_path() -> Option<PathBuf> {
+    let dir = if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else {
+        env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+    };
+    dir.map(|dir| dir.join("pathctl").join("config.toml"))
+}
+
+/// Reads the config file, if there is one.
+fn load_config() -> Result<Config, String> {
+    let Some(file) = config_path() else {
+        return Ok(Config::default());
+    };
+    match std::fs::read_to_string(&file) {
+        Ok(contents) => {
+            parse_config(&contents).map_err(|err| format!("{}: {}", file.display(), err))
+        }
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Config::default()),
+        Err(err) => Err(format!("cannot read {}: {}", file.display(), err)),
+    }
+}
+
+/// Parses the small TOML subset the config file is written in: `key =
+/// value` lines, optionally quoted, under `[section]` headers, with `#`
+/// comments.
+fn parse_config(contents: &str) -> Result<Config, String> {
+    let mut config = Config::default();
+    let mut section = String::new();
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let error = |message: String| format!("line {}: {}", number + 1, message);
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            section = name.trim().to_string();
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(error(format!("expected `key = value`: {}", line)));
+        };
+        let key = key.trim();
+        let raw = value;
+        let value = config_value(value);
+        match (section.as_str(), key) {
+            ("", "theme") => {
+                if Theme::named(&value).is_none() {
+                    return Err(error(format!(
+                        "unknown theme {}, expected one of {}",
+                        value,
+                        Theme::NAMES.join(", ")
+                    )));
+                }
+                config.theme = Some(value);
+            }
+            ("", "suggestions") => {
+                let dirs = config_list(raw).map_err(error)?;
+                config.suggestions = dirs.into_iter().map(PathBuf::from).collect();
+            }
+            ("", _) => return Err(error(format!("unknown setting: {}", key))),
+            ("keys", _) => config.keys.push((key.to_string(), value)),
+            ("disabled" | "pinned", _) => {
+                let entries = config_list(raw).map_err(error)?;
+                let entries = entries.into_iter().map(PathBuf::from).collect();
+                match section.as_str() {
+                    "pinned" => config.pinned.insert(key.to_string(), entries),
+                    _ => config.disabled.insert(key.to_string(), entries),
+                };
+            }
+            _ => return Err(error(format!("unknown setting: {}.{}", section, key))),
+        }
+    }
+    Ok(config)
+}
+
+/// Parses a one-line array of strings such as `["/opt/bin", '/srv/bin']`.
+/// Double-quoted strings may escape `"` and `\` with a backslash.
+fn config_list(value: &str) -> Result<Vec<String>, String> {
+    let value = value.trim();
+    let Some(inner) = value.strip_prefix('[') else {
+        return Err(format!("expected a list such as [\"/opt/bin\"]: {}", value));
+    };
+    let mut items = Vec::new();
+    let mut chars = inner.chars();
+    loop {
+        match chars.by_ref().find(|c| !c.is_whitespace() && *c != ',') {
+            Some(']') => return Ok(items),
+            Some(quote @ ('"' | '\'')) => {
+                let mut item = String::new();
+                loop {
+                    match chars.next() {
+                        Some(c) if c == quote => break,
+                        Some('\\') if quote == '"' => item.extend(chars.next()),
+                        Some(c) => item.push(c),
+                        None => return Err(format!("unterminated string in {}", value)),
+                    }
+                }
+                items.push(item);
+            }
+            _ => return Err(format!("expected a list of quoted strings: {}", value)),
+        }
+    }
+}
+
+/// Writes `values` as a config list, in literal strings unless one holds a
+/// single quote.
+fn format_config_list(values: &[String]) -> String {
+    let items: Vec<String> = values
+        .iter()
+        .map(|value| match value.contains('\'') {
+            true => format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\"")),
+            false => format!("'{}'", value),
+        })
+        .collect();
+    format!("[{}]", items.join(", "))
+}
+
+/// Sets `key` under `[section]` in the config file contents, replacing the
+/// line already there or adding it, and the section, if missing. The rest
+/// of the file is kept as written.
+fn set_config_value(contents: &str, section: &str, key: &str, value: &str) -> String {
+    let setting = format!("{} = {}", key, value);
+    let mut lines: Vec<String> = contents.lines().map(str::to_string).collect();
+    let header = format!("[{}]", section);
+    let Some(start) = lines.iter().position(|line| line.trim() == header) else {
+        if lines.last().is_some_and(|line| !line.trim().is_empty()) {
+            lines.push(String::new());
+        }
+        lines.extend([header, setting]);
+        return lines.join("\n") + "\n";
+    };
+    let end = lines[start + 1..]
+        .iter()
+        .position(|line| line.trim_start().starts_with('['))
+        .map_or(lines.len(), |i| start + 1 + i);
+    let existing = (start + 1..end).find(|&i| {
+        lines[i]
+            .split_once('=')
+            .is_some_and(|(name, _)| name.trim() == key)
+    });
+    match existing {
+        Some(i) => lines[i] = setting,
+        None => lines.insert(start + 1, setting),
+    }
+    lines.join("\n") + "\n"
+}
+
+/// Records the entries of `var` in `chosen` under `section` (`disabled` or
+/// `pinned`) of the config `file`. Entries the file lists that are not in
+/// `paths` are kept, since another session may still have them.
+fn save_entries(
+    file: &Path,
+    section: &str,
+    var: &str,
+    paths: &[PathBuf],
+    chosen: &HashSet<PathBuf>,
+) -> io::Result<()> {
+    let contents = match std::fs::read_to_string(file) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err),
+    };
+    let config =
+        parse_config(&contents).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    let saved = match section {
+        "pinned" => &config.pinned,
+        _ => &config.disabled,
+    };
+    let elsewhere = saved.get(var).into_iter().flatten();
+    let entries: Vec<String> = elsewhere
+        .filter(|p| !paths.contains(p))
+        .chain(paths.iter().filter(|p| chosen.contains(*p)))
+        .map(|p| p.to_string_lossy().into_owned())
+        .collect();
+    let contents = set_config_value(&contents, section, var, &format_config_list(&entries));
+    if let Some(dir) = file.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(file, contents)
+}
+
+/// A config value without its trailing comment and surrounding quotes.
+fn config_value(value: &str) -> String {
+    let value = value.trim();
+    match value.strip_prefix('"') {
+        Some(quoted) => quoted.split('"').next().unwrap_or_default().to_string(),
+        None => value
+            .split('#')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_string(),
+    }
+}
+
+/// Honours the `NO_COLOR` convention (https://no-color.org): any non-empty
+/// value turns colors off.
+fn colors_enabled() -> bool {
+    env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+}
+
+/// Background of the list row at `row`: odd rows are shaded when zebra
+/// striping is on and the theme has a shade for them.
+fn row_background(row: usize, zebra: bool, theme: &Theme) -> Option<Color> {
+    match zebra && row % 2 == 1 {
+        true => theme.zebra,
+        false => None,
+    }
+}
+
+fn list_title(app: &App) -> String {
+    if app.single {
+        return app.var.clone();
+    }
+    if !app.disabled.is_empty() {
+        return format!(
+            "{}: {} entries ({} enabled)",
+            app.var,
+            app.paths.len(),
+            app.enabled(&app.paths).len()
+        );
+    }
+    if let Some(query) = &app.search {
+        return format!(
+            "{} Entries ({} of {} matching \"{}\")",
+            app.var,
+            app.visible().len(),
+            app.paths.len(),
+            query
+        );
+    }
+    if let Some(root) = &app.repo_filter {
+        return format!(
+            "{} Entries ({} of {} inside {})",
+            app.var,
+            app.visible().len(),
+            app.paths.len(),
+            root.display()
+        );
+    }
+    match app.base.as_ref().filter(|_| app.show_origins) {
+        Some(base) => {
+            let origins = classify_origins(base, &app.paths, &app.dedupe_opts);
+            let added = origins.iter().filter(|o| **o == Origin::Added).count();
+            format!(
+                "{} Entries ({} inherited, {} session-added)",
+                app.var,
+                origins.len() - added,
+                added
+            )
+        }
+        None => match app.system_len {
+            Some(system_len) => format!(
+                "{} Entries ({} system, {} user)",
+                app.var,
+                system_len,
+                app.paths.len() - system_len
+            ),
+            None => format!("{} Entries", app.var),
+        },
+    }
+}
+
+fn draw_sort<B: Backend>(f: &mut Frame<B>, app: &App, size: Rect) {
+    let items: Vec<ListItem> = SortOrder::ALL
+        .iter()
+        .map(|order| ListItem::new(format!("Sort {}", order.label())))
+        .collect();
+    let mut state = ListState::default();
+    state.select(Some(app.sort_choice));
+
+    let area = centered_rect(50, 30, size);
+    let picker = List::new(items)
+        .block(
+            app.theme
+                .block()
+                .title("Sort the list? (Enter: sort, Esc: cancel)"),
+        )
+        .highlight_style(
+            Style::default()
+                .fg(app.theme.highlight)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol(">> ");
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(picker, area, &mut state);
+}
+
+fn draw_save_targets<B: Backend>(f: &mut Frame<B>, app: &App, size: Rect) {
+    let items: Vec<ListItem> = app
+        .save_targets
+        .iter()
+        .map(|target| ListItem::new(target.to_string()))
+        .collect();
+    let mut state = ListState::default();
+    state.select(Some(app.save_choice));
+
+    let area = centered_rect(60, 40, size);
+    let picker = List::new(items)
+        .block(
+            app.theme
+                .block()
+                .title("Save to (Enter: save, Esc: cancel)"),
+        )
+        .highlight_style(
+            Style::default()
+                .fg(app.theme.highlight)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol(">> ");
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(picker, area, &mut state);
+}
+
+fn draw_details<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
+    let target = detail_target(
+        app.pinned_detail.as_deref(),
+        app.list_state.selected(),
+        &app.paths,
+    );
+    let lines: Vec<Spans> = match target {
+        Some(i) => entry_details(&app.paths[i], &app.mounts)
+            .into_iter()
+            .map(Spans::from)
+            .collect(),
+        None => vec![],
+    };
+    let title = match target {
+        Some(i) if app.pinned_detail.as_ref() == Some(&app.paths[i]) => {
+            format!("Details of #{} (pinned, m: unpin)", i + 1)
+        }
+        _ => "Details".to_string(),
+    };
+    let details = Paragraph::new(lines)
+        .block(app.theme.block().title(title))
+        .wrap(Wrap { trim: false });
+    f.render_widget(details, area);
+}
+
+/// Lists the executables of `selected`, reusing `cache` while it is still
+/// about the same entry.
+fn draw_executables<B: Backend>(
+    f: &mut Frame<B>,
+    selected: Option<&Path>,
+    cache: &mut Option<(PathBuf, Result<Vec<OsString>, String>)>,
+    theme: &Theme,
+    area: Rect,
+) {
+    let Some(path) = selected else {
+        f.render_widget(theme.block().title("Executables"), area);
+        return;
+    };
+    if cache.as_ref().map(|(of, _)| of.as_path()) != Some(path) {
+        let listed = match path.is_dir() {
+            true => executables(path).map_err(|err| err.to_string()),
+            false => Err("not a directory".to_string()),
+        };
+        *cache = Some((path.to_path_buf(), listed));
+    }
+    let Some((_, listed)) = cache else {
+        return;
+    };
+
+    let (title, items) = match listed {
+        Ok(names) => (
+            format!("Executables ({})", names.len()),
+            names
+                .iter()
+                .map(|name| ListItem::new(name.to_string_lossy().into_owned()))
+                .collect(),
+        ),
+        Err(err) => (
+            "Executables".to_string(),
+            vec![ListItem::new(err.as_str()).style(Style::default().fg(theme.error))],
+        ),
+    };
+    let list = List::new(items).block(theme.block().title(title));
+    f.render_widget(list, area);
+}
+
+fn draw_history<B: Backend>(f: &mut Frame<B>, app: &App, size: Rect) {
+    let items: Vec<ListItem> = app
+        .history
+        .snapshots
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, snapshot)| {
+            ListItem::new(format!(
+                "{:>3}. {} ({}s ago)",
+                i + 1,
+                snapshot.description,
+                snapshot.at.elapsed().as_secs()
+            ))
+        })
+        .collect();
+    let mut state = ListState::default();
+    state.select(Some(app.history_choice));
+
+    let area = centered_rect(70, 60, size);
+    let browser = List::new(items)
+        .block(
+            app.theme
+                .block()
+                .title("Undo back to before (Enter: undo, Esc: cancel)"),
+        )
+        .highlight_style(
+            Style::default()
+                .fg(app.theme.highlight)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol(">> ");
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(browser, area, &mut state);
+}
+
+/// Tags the entries of both lists for the diff view: removed, moved or
+/// unchanged for the original one, and added, moved or unchanged for the
+/// edited one.
+fn side_by_side(
+    original: &[PathBuf],
+    edited: &[PathBuf],
+    opts: &DedupeOpts,
+) -> (Vec<Option<DiffTag>>, Vec<DiffTag>) {
+    let diff = diff_paths(original, edited, opts);
+    let edited_keys: Vec<OsString> = edited.iter().map(|p| dedupe_key(p, opts)).collect();
+    let left = original
+        .iter()
+        .map(|p| {
+            let key = dedupe_key(p, opts);
+            let at = edited_keys.iter().position(|k| *k == key)?;
+            Some(diff.tags[at])
+        })
+        .collect();
+    (left, diff.tags)
+}
+
+/// The original list beside the edited one, as the output will have it,
+/// with removed (`-`), added (`+`) and moved (`~`) entries highlighted.
+fn draw_diff<B: Backend>(f: &mut Frame<B>, app: &App, size: Rect) {
+    let edited = app.enabled(&app.paths);
+    let (left, right) = side_by_side(&app.original, &edited, &app.dedupe_opts);
+    let line = |p: &PathBuf, tag: Option<DiffTag>, removed: &str| {
+        let (marker, style) = match tag {
+            None => (removed, Style::default().fg(app.theme.error)),
+            Some(DiffTag::Added) => ("+", Style::default().fg(app.theme.added)),
+            Some(DiffTag::Moved) => ("~", Style::default().fg(app.theme.warning)),
+            Some(DiffTag::Unchanged) => (" ", Style::default()),
+        };
+        Spans::from(Span::styled(
+            format!("{} {}", marker, display_entry(p)),
+            style,
+        ))
+    };
+    let original: Vec<Spans> = app
+        .original
+        .iter()
+        .zip(left)
+        .map(|(p, tag)| line(p, tag, "-"))
+        .collect();
+    let edited: Vec<Spans> = edited
+        .iter()
+        .zip(right)
+        .map(|(p, tag)| line(p, Some(tag), "+"))
+        .collect();
+
+    let area = centered_rect(90, 80, size);
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(area);
+    // Both sides scroll together until the longer one ends
+    let longest = original.len().max(edited.len()) as u16;
+    let scroll = app
+        .popup_scroll
+        .min(longest.saturating_sub(area.height.saturating_sub(2)));
+    let left = Paragraph::new(original)
+        .block(app.theme.block().title(format!(
+            "Original {} (j/k: scroll, any other key: close)",
+            app.var
+        )))
+        .scroll((scroll, 0));
+    let right = Paragraph::new(edited)
+        .block(app.theme.block().title("Edited, as it will be output"))
+        .scroll((scroll, 0));
+    f.render_widget(Clear, area);
+    f.render_widget(left, columns[0]);
+    f.render_widget(right, columns[1]);
+}
+
+/// The tab bar, marking the variables changed so far with `*`.
+fn draw_tabs<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
+    let titles: Vec<Spans> = app
+        .tabs
+        .iter()
+        .enumerate()
+        .map(|(i, tab)| {
+            let (var, changed) = match i == app.tab {
+                true => (
+                    &app.var,
+                    app.paths != app.original || !app.disabled.is_empty(),
+                ),
+                false => (
+                    &tab.var,
+                    tab.paths != tab.original || !tab.disabled.is_empty(),
+                ),
+            };
+            let marker = if changed { "*" } else { "" };
+            Spans::from(format!("{} {}{}", i + 1, var, marker))
+        })
+        .collect();
+    let tabs = Tabs::new(titles).select(app.tab).highlight_style(
+        Style::default()
+            .fg(app.theme.highlight)
+            .add_modifier(Modifier::BOLD),
+    );
+    f.render_widget(tabs, area);
+}
+
+fn draw_suggest<B: Backend>(f: &mut Frame<B>, app: &App, size: Rect) {
+    let items: Vec<ListItem> = app
+        .suggestions
+        .iter()
+        .enumerate()
+        .map(|(i, dir)| match i {
+            0..=8 => ListItem::new(format!("{}  {}", i + 1, display_entry(dir))),
+            _ => ListItem::new(format!("   {}", display_entry(dir))),
+        })
+        .collect();
+    let mut state = ListState::default();
+    state.select(Some(app.suggest_choice));
+
+    let area = centered_rect(60, 50, size);
+    let view = List::new(items)
+        .block(
+            app.theme
+                .block()
+                .title("Add a directory (Enter/1-9: at the top, A: at the bottom, Esc: close)"),
+        )
+        .highlight_style(
+            Style::default()
+                .fg(app.theme.highlight)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol(">> ");
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(view, area, &mut state);
+}
+
+fn draw_browse<B: Backend>(f: &mut Frame<B>, app: &App, size: Rect) {
+    let items: Vec<ListItem> = app
+        .browse_entries
+        .iter()
+        .map(|dir| {
+            let name = dir.file_name().unwrap_or(dir.as_os_str());
+            let item = ListItem::new(format!(
+                "{}{}",
+                name.to_string_lossy(),
+                std::path::MAIN_SEPARATOR
+            ));
+            match app.paths.contains(dir) {
+                true => item.style(Style::default().fg(app.theme.added)),
+                false => item,
+            }
+        })
+        .collect();
+    let mut state = ListState::default();
+    state.select(Some(app.browse_choice).filter(|_| !items.is_empty()));
+
+    let area = centered_rect(60, 70, size);
+    let title = format!(
+        "{} (Enter/l: open, h: up, a/b: insert after/before, Esc: close)",
+        app.browse_dir.display()
+    );
+    let view = List::new(items)
+        .block(app.theme.block().title(title))
+        .highlight_style(
+            Style::default()
+                .fg(app.theme.highlight)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol(">> ");
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(view, area, &mut state);
+}
+
+fn draw_shadowing<B: Backend>(f: &mut Frame<B>, app: &App, size: Rect) {
+    let items: Vec<ListItem> = app
+        .shadowing
+        .iter()
+        .map(|shadowed| {
+            let losers: Vec<String> = shadowed
+                .shadowed
+                .iter()
+                .map(|&i| display_entry(&app.paths[i]))
+                .collect();
+            ListItem::new(Spans::from(vec![
+                Span::styled(
+                    shadowed.name.to_string_lossy().into_owned(),
+                    Style::default().add_modifier(Modifier::BOLD),
+                ),
+                Span::raw(format!(
+                    " from {}",
+                    display_entry(&app.paths[shadowed.winner])
+                )),
+                Span::styled(
+                    format!(", shadowing {}", losers.join(", ")),
+                    Style::default().fg(app.theme.error),
+                ),
+            ]))
+        })
+        .collect();
+    let mut state = ListState::default();
+    state.select(Some(app.shadow_choice));
+
+    let area = centered_rect(80, 60, size);
+    let title = format!(
+        "{} shadowed commands (Enter: show entries, Esc: close)",
+        app.shadowing.len()
+    );
+    let view = List::new(items)
+        .block(app.theme.block().title(title))
+        .highlight_style(
+            Style::default()
+                .fg(app.theme.highlight)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol(">> ");
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(view, area, &mut state);
+}
+
+fn draw_fuzzy<B: Backend>(f: &mut Frame<B>, app: &App, size: Rect) {
+    let highlight = Style::default()
+        .fg(app.theme.highlight)
+        .add_modifier(Modifier::BOLD);
+    let items: Vec<ListItem> = app
+        .fuzzy_matches()
+        .into_iter()
+        .map(|i| {
+            let path = app.paths[i].to_string_lossy();
+            let matched = fuzzy_match(&path, &app.fuzzy_query)
+                .map(|(_, positions)| positions)
+                .unwrap_or_default();
+            let spans: Vec<Span> = path
+                .chars()
+                .enumerate()
+                .map(|(pos, c)| match matched.contains(&pos) {
+                    true => Span::styled(c.to_string(), highlight),
+                    false => Span::raw(c.to_string()),
+                })
+                .collect();
+            ListItem::new(Spans::from(spans))
+        })
+        .collect();
+    let mut state = ListState::default();
+    state.select(Some(app.fuzzy_choice).filter(|_| !items.is_empty()));
+
+    let area = centered_rect(70, 60, size);
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(0)].as_ref())
+        .split(area);
+    let query = Paragraph::new(format!("> {}", app.fuzzy_query)).block(
+        app.theme
+            .block()
+            .title("Jump to entry (Enter: jump, Esc: cancel)"),
+    );
+    let matches = List::new(items)
+        .block(app.theme.block())
+        .highlight_style(highlight)
+        .highlight_symbol(">> ");
+    f.render_widget(Clear, area);
+    f.render_widget(query, chunks[0]);
+    f.render_stateful_widget(matches, chunks[1], &mut state);
+    f.set_cursor(
+        chunks[0].x + app.fuzzy_query.len() as u16 + 3,
+        chunks[0].y + 1,
+    );
+}
+
+fn draw_command<B: Backend>(f: &mut Frame<B>, app: &App, size: Rect) {
+    let area = centered_rect(80, 40, size);
+    let popup = Paragraph::new(app.command())
+        .block(
+            app.theme
+                .block()
+                .title("Command printed on exit (any key: close)"),
+        )
+        .wrap(Wrap { trim: false });
+    f.render_widget(Clear, area);
+    f.render_widget(popup, area);
+}
+
+/// Lists the Tab completions just above the input box at `input_area`.
+fn draw_completions<B: Backend>(f: &mut Frame<B>, app: &App, input_area: Rect) {
+    let height = (app.completions.len() as u16 + 2)
+        .min(COMPLETION_ROWS + 2)
+        .min(input_area.y);
+    let area = Rect::new(
+        input_area.x,
+        input_area.y - height,
+        input_area.width,
+        height,
+    );
+    let items: Vec<ListItem> = app
+        .completions
+        .iter()
+        .map(|completion| ListItem::new(completion.as_str()))
+        .collect();
+    let mut state = ListState::default();
+    state.select(Some(app.completion_choice));
+    let list = List::new(items)
+        .block(app.theme.block().title("Tab: next"))
+        .highlight_style(
+            Style::default()
+                .fg(app.theme.highlight)
+                .add_modifier(Modifier::BOLD),
+        );
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn draw_help<B: Backend>(f: &mut Frame<B>, app: &App, size: Rect) {
+    let mut lines = Vec::new();
+    for (i, (section, bindings)) in KEYMAP.iter().enumerate() {
+        if i > 0 {
+            lines.push(Spans::from(""));
+        }
+        lines.push(Spans::from(Span::styled(
+            *section,
+            Style::default().add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+        )));
+        // Only keys of the list itself can be remapped
+        let remappable = !matches!(*section, "Input boxes" | "Popups");
+        for binding in bindings.iter() {
+            let keys = match remappable {
+                true => app.keys.labels(binding.keys),
+                false => binding.keys.join("/"),
+            };
+            lines.push(Spans::from(vec![
+                Span::styled(
+                    format!("{:>18}  ", keys),
+                    Style::default().add_modifier(Modifier::BOLD),
+                ),
+                Span::raw(binding.action),
+            ]));
+        }
+    }
+
+    let area = centered_rect(70, 80, size);
+    // Stop scrolling once the last line is at the bottom
+    let last = (lines.len() as u16).saturating_sub(area.height.saturating_sub(2));
+    let popup = Paragraph::new(lines)
+        .block(
+            app.theme
+                .block()
+                .title("Keys (j/k: scroll, any other key: close)"),
+        )
+        .scroll((app.popup_scroll.min(last), 0));
+    f.render_widget(Clear, area);
+    f.render_widget(popup, area);
+}
+
+fn draw_confirm_delete<B: Backend>(f: &mut Frame<B>, app: &App, size: Rect) {
+    // Fixing relative entries does not look at pins
+    let guarded =
+        |i: usize| app.popup != Some(Popup::Relative) && app.pinned.contains(&app.paths[i]);
+    let lines: Vec<Spans> = app
+        .pending_delete
+        .iter()
+        .map(|&i| match guarded(i) {
+            true => Spans::from(Span::styled(
+                format!("{}  [pinned, kept unless !]", display_entry(&app.paths[i])),
+                Style::default().fg(app.theme.warning),
+            )),
+            false => Spans::from(display_entry(&app.paths[i])),
+        })
+        .collect();
+    let title = match (app.popup, lines.len()) {
+        (Some(Popup::Prune), n) => format!("Remove these {} missing entries? (y/n)", n),
+        (Some(Popup::Relative), n) => format!(
+            "{} relative entries: r: remove, a: make absolute, other: keep",
+            n
+        ),
+        (Some(Popup::RemoveMatching), n) => format!(
+            "Remove these {} entries matching {}? (y/n)",
+            n, app.removal_pattern
+        ),
+        (_, 1) => "Delete this entry? (y/n)".to_string(),
+        (_, n) => format!("Delete these {} entries? (y/n)", n),
+    };
+    let title = match app.pending_delete.iter().any(|&i| guarded(i)) {
+        true => title.replace("(y/n)", "(y/n, !: pinned ones too)"),
+        false => title,
+    };
+
+    let area = centered_rect(60, 30, size);
+    let popup = Paragraph::new(lines)
+        .block(app.theme.block().title(title))
+        .wrap(Wrap { trim: false });
+    f.render_widget(Clear, area);
+    f.render_widget(popup, area);
+}
+
+/// What the status says about pinned entries a removal left alone.
+fn kept_pinned(kept: usize) -> String {
+    match kept {
+        0 => String::new(),
+        1 => ", kept 1 pinned entry".to_string(),
+        n => format!(", kept {} pinned entries", n),
+    }
+}
+
+fn draw_confirm_add<B: Backend>(f: &mut Frame<B>, app: &App, size: Rect) {
+    let area = centered_rect(60, 20, size);
+    let popup = Paragraph::new(display_entry(&app.typed_path()))
+        .block(
+            app.theme
+                .block()
+                .title("Directory doesn't exist — add anyway? (y/n)"),
+        )
+        .wrap(Wrap { trim: false });
+    f.render_widget(Clear, area);
+    f.render_widget(popup, area);
+}
+
+fn draw_confirm_save<B: Backend>(f: &mut Frame<B>, app: &App, size: Rect) {
+    let lines: Vec<Spans> = app
+        .save_diff
+        .iter()
+        .map(|line| match line {
+            DiffLine::Same(text) => Spans::from(format!("  {}", text)),
+            DiffLine::Removed(text) => Spans::from(Span::styled(
+                format!("- {}", text),
+                Style::default().fg(app.theme.error),
+            )),
+            DiffLine::Added(text) => Spans::from(Span::styled(
+                format!("+ {}", text),
+                Style::default().fg(app.theme.added),
+            )),
+        })
+        .collect();
+
+    let area = centered_rect(80, 50, size);
+    let title = match &app.pending_save {
+        Some(target) => format!("Replace the pathctl block in {}? (y/n)", target),
+        None => "Replace the pathctl block? (y/n)".to_string(),
+    };
+    let popup = Paragraph::new(lines)
+        .block(app.theme.block().title(title))
+        .wrap(Wrap { trim: false });
+    f.render_widget(Clear, area);
+    f.render_widget(popup, area);
+}
+
+fn draw_front_preview<B: Backend>(f: &mut Frame<B>, app: &App, size: Rect) {
+    let Some(selected) = app.list_state.selected() else {
+        return;
+    };
+    let items: Vec<ListItem> = preview_move_to_front(&app.paths, selected)
+        .iter()
+        .enumerate()
+        .map(|(i, p)| {
+            let item = ListItem::new(display_entry(p));
+            if i == 0 {
+                item.style(
+                    Style::default()
+                        .fg(app.theme.highlight)
+                        .add_modifier(Modifier::BOLD),
+                )
+            } else {
+                item
+            }
+        })
+        .collect();
+
+    let area = centered_rect(70, 70, size);
+    let preview = List::new(items).block(
+        app.theme
+            .block()
+            .title("Move to front? Enter: apply, any other key: cancel"),
+    );
+    f.render_widget(Clear, area);
+    f.render_widget(preview, area);
+}
+
+fn draw_histogram<B: Backend>(f: &mut Frame<B>, paths: &[PathBuf], theme: &Theme, size: Rect) {
+    let histogram = top_level_histogram(paths);
+    let label_width = histogram
+        .iter()
+        .map(|(root, _)| root.chars().count())
+        .max()
+        .unwrap_or(0);
+    let max_count = histogram.first().map(|(_, count)| *count).unwrap_or(1);
+    let area = centered_rect(60, 60, size);
+    let bar_width = (area.width as usize)
+        .saturating_sub(label_width + 10)
+        .max(1);
+
+    let lines: Vec<Spans> = histogram
+        .iter()
+        .map(|(root, count)| {
+            let bar = "█".repeat((count * bar_width).div_ceil(max_count));
+            Spans::from(vec![
+                Span::raw(format!("{:<width$} ", root, width = label_width)),
+                Span::styled(bar, Style::default().fg(theme.accent)),
+                Span::raw(format!(" {}", count)),
+            ])
+        })
+        .collect();
+
+    let popup = Paragraph::new(lines).block(theme.block().title("Entries by top-level directory"));
+    f.render_widget(Clear, area);
+    f.render_widget(popup, area);
+}
+
+/// Returns a rectangle of the given percentage size centered within `r`.
+fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Percentage((100 - percent_y) / 2),
+            Constraint::Percentage(percent_y),
+            Constraint::Percentage((100 - percent_y) / 2),
+        ])
+        .split(r);
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage((100 - percent_x) / 2),
+            Constraint::Percentage(percent_x),
+            Constraint::Percentage((100 - percent_x) / 2),
+        ])
+        .split(vertical[1])[1]
+}
+
+/// Counts entries per top-level directory (`/usr`, `/opt`, ...), most
+/// populated first.
+fn top_level_histogram(paths: &[PathBuf]) -> Vec<(String, usize)> {
+    let mut counts: Vec<(String, usize)> = Vec::new();
+    for path in paths {
+        let mut root = PathBuf::new();
+        for component in path.components() {
+            root.push(component);
+            if let Component::Normal(_) = component {
+                break;
+            }
+        }
+        let root = display_entry(&root);
+        match counts.iter_mut().find(|(r, _)| *r == root) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((root, 1)),
+        }
+    }
+    // Stable sort keeps first-seen order among equal counts
+    counts.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    counts
+}
+
+fn get_path_entries() -> Vec<PathBuf> {
+    #[cfg(target_os = "windows")]
+    {
+        get_windows_path_entries()
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        if let Ok(path_var) = env::var("PATH") {
+            env::split_paths(&path_var).collect()
+        } else {
+            vec![]
+        }
+    }
+}
+
+/// Reads a variable from the environment, either as a list of entries or, in
+/// single mode, as one directory.
+fn get_var_entries(var: &str, single: bool) -> Vec<PathBuf> {
+    match env::var_os(var) {
+        Some(value) if value.is_empty() => vec![],
+        Some(value) if single => vec![PathBuf::from(value)],
+        Some(value) => env::split_paths(&value).collect(),
+        None => vec![],
+    }
+}
+
+/// Renders the command printed by the non-interactive modes, applying the
+/// output-only transforms requested in `options`.
+fn render_batch(mut paths: Vec<PathBuf>, options: &Options) -> String {
+    if options.canonical_order {
+        canonical_order(&mut paths);
+    }
+    render_command(&paths, options)
+}
+
+/// Sorts entries by their raw bytes, giving the same PATH string for the same
+/// set of entries regardless of how they were ordered.
+fn canonical_order(paths: &mut [PathBuf]) {
+    paths.sort_by(|a, b| {
+        a.as_os_str()
+            .as_encoded_bytes()
+            .cmp(b.as_os_str().as_encoded_bytes())
+    });
+}
+
+fn render_command(paths: &[PathBuf], options: &Options) -> String {
+    let command = match &options.format {
+        OutputFormat::Auto => generate_shell_command(paths, options),
+        OutputFormat::Shell(shell) => shell_command(paths, Some(shell), options),
+        OutputFormat::Universal => universal_snippet(paths, options),
+        OutputFormat::Raw => {
+            join_with(paths, options.out_separator.as_deref().unwrap_or(SEPARATOR))
+        }
+    };
+    if !options.comment {
+        return command;
+    }
+    let shell = match &options.format {
+        OutputFormat::Auto => detect_shell().unwrap_or_else(|| "sh".to_string()),
+        OutputFormat::Shell(shell) => shell.clone(),
+        OutputFormat::Universal => "sh".to_string(),
+        // Raw output is not a script, so it cannot hold a comment
+        OutputFormat::Raw => return command,
+    };
+    match comment_header(&shell) {
+        header if header.is_empty() => command,
+        header => format!("{}\n{}", header, command),
+    }
+}
+
+/// Returns a comment line noting that the command was generated by pathctl
+/// and when, or an empty string for shells without line comments.
+fn comment_header(shell: &str) -> String {
+    match shell {
+        "bash" | "zsh" | "sh" | "dash" | "ksh" | "fish" | "csh" | "tcsh" | "pwsh"
+        | "powershell" => {
+            let now = SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default();
+            format!("# generated by pathctl on {}", format_date(now.as_secs()))
+        }
+        _ => String::new(),
+    }
+}
+
+/// Wraps `command` into a self-contained script for `shell`, with a shebang
+/// running that shell.
+fn build_script(shell: &str, command: &str) -> String {
+    let shebang = match shell {
+        "bash" | "zsh" | "fish" | "tcsh" | "ksh" | "dash" => format!("#!/usr/bin/env {}", shell),
+        "csh" => "#!/bin/csh -f".to_string(),
+        "pwsh" | "powershell" => "#!/usr/bin/env pwsh".to_string(),
+        _ => "#!/bin/sh".to_string(),
+    };
+    match comment_header(shell).as_str() {
+        "" => format!("{}\n{}\n", shebang, command),
+        header => format!("{}\n{}\n{}\n", shebang, header, command),
+    }
+}
+
+/// Formats seconds since the Unix epoch as a `YYYY-MM-DD` UTC date.
+fn format_date(secs: u64) -> String {
+    // Civil-from-days conversion, see http://howardhinnant.github.io/date_algorithms.html
+    let days = (secs / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let doe = days.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+fn generate_shell_command(paths: &[PathBuf], options: &Options) -> String {
+    // Detect the shell to output appropriate commands
+    let shell = detect_shell();
+    shell_command(paths, shell.as_deref(), options)
+}
+
+fn shell_command(paths: &[PathBuf], shell: Option<&str>, options: &Options) -> String {
+    let var = options.var_name();
+    if shell == Some("zsh") && options.zsh_array && !options.single {
+        if let Some(array) = zsh_tied_array(var) {
+            return zsh_array_command(array, paths);
+        }
+    }
+    if shell == Some("fish") && options.fish_add_path && var == "PATH" && !options.single {
+        let startup = get_var_entries(var, false);
+        return fish_add_path_command(&startup, paths, options);
+    }
+    let new_path_str = join_entries(paths);
+    match shell {
+        Some("pwsh") | Some("powershell") if options.pwsh_persist => {
+            // Persisted values live in the registry, where `Path` is `;`-separated
+            let name = if var == "PATH" { "Path" } else { var };
+            let value = join_with(paths, ";");
+            format!(
+                "[Environment]::SetEnvironmentVariable(\"{}\", \"{}\", \"User\")",
+                name, value
+            )
+        }
+        Some("pwsh") | Some("powershell") => format!("$env:{} = \"{}\"", var, new_path_str),
+        Some("fish") => format!("set -x {} {}", var, new_path_str),
+        Some("csh") | Some("tcsh") => format!("setenv {} \"{}\"", var, new_path_str),
+        _ => format!("export {}=\"{}\"", var, new_path_str),
+    }
+}
+
+/// Returns the array zsh ties to a PATH-like variable, such as `path` for
+/// `PATH`.
+fn zsh_tied_array(var: &str) -> Option<&'static str> {
+    match var {
+        "PATH" => Some("path"),
+        "MANPATH" => Some("manpath"),
+        "FPATH" => Some("fpath"),
+        "CDPATH" => Some("cdpath"),
+        "MODULE_PATH" => Some("module_path"),
+        _ => None,
+    }
+}
+
+/// Assigns zsh's tied array directly; `typeset -U` makes zsh drop any
+/// duplicate added to it later on.
+fn zsh_array_command(array: &str, paths: &[PathBuf]) -> String {
+    let entries: Vec<String> = paths
+        .iter()
+        .map(|p| sh_quote(&p.to_string_lossy()))
+        .collect();
+    format!("typeset -U {}\n{}=( {} )", array, array, entries.join(" "))
+}
+
+/// Emits one `fish_add_path` line per entry of `paths` missing from
+/// `startup`. Prepended lines come out in reverse so the entries end up in
+/// list order; entries removed from `startup` are left alone, since
+/// `fish_add_path` only adds.
+fn fish_add_path_command(startup: &[PathBuf], paths: &[PathBuf], options: &Options) -> String {
+    let append = options.fish_append;
+    let diff = diff_paths(startup, paths, &options.dedupe_opts);
+    let mut added: Vec<&PathBuf> = paths
+        .iter()
+        .zip(&diff.tags)
+        .filter(|(_, tag)| **tag == DiffTag::Added)
+        .map(|(p, _)| p)
+        .collect();
+    let flag = if append { "--append" } else { "--prepend" };
+    if !append {
+        added.reverse();
+    }
+    added
+        .iter()
+        .map(|p| format!("fish_add_path {} {}", flag, sh_quote(&p.to_string_lossy())))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Quotes a word for POSIX-like shells, leaving it bare when it only holds
+/// characters that need no quoting.
+fn sh_quote(s: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "/._-+:,@%=~".contains(c);
+    if !s.is_empty() && s.chars().all(safe) {
+        s.to_string()
+    } else {
+        format!("'{}'", s.replace('\'', "'\\''"))
+    }
+}
+
+/// Separator between entries of a PATH-like variable on this platform.
+const SEPARATOR: &str = if cfg!(windows) { ";" } else { ":" };
+
+/// Joins entries with the platform separator. Unlike `env::join_paths` this
+/// never fails: an entry that itself contains the separator is emitted as is,
+/// and a lone entry is returned verbatim.
+fn join_entries(paths: &[PathBuf]) -> String {
+    match env::join_paths(paths) {
+        Ok(new_path_var) => new_path_var.to_string_lossy().into_owned(),
+        Err(_) => join_with(paths, SEPARATOR),
+    }
+}
+
+/// Joins entries with an arbitrary separator, which is not escaped where
+/// it appears inside an entry.
+fn join_with(paths: &[PathBuf], separator: &str) -> String {
+    paths
+        .iter()
+        .map(|p| p.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+/// Builds a POSIX sh snippet that picks the right assignment by matching on
+/// `$SHELL`, so a single file can serve bash, zsh, fish and csh users alike.
+/// Only sh-like shells can read it, so the fish and csh arms print their
+/// shell's assignment rather than run it, for `sh file | source` in fish or
+/// ``eval "`sh file`"`` in csh.
+fn universal_snippet(paths: &[PathBuf], options: &Options) -> String {
+    let printed = |shell| {
+        let lines: Vec<String> = shell_command(paths, Some(shell), options)
+            .lines()
+            .map(sh_quote)
+            .collect();
+        format!("printf '%s\\n' {}", lines.join(" "))
+    };
+    let arms = [
+        ("*/fish", printed("fish")),
+        ("*/csh|*/tcsh", printed("csh")),
+        ("*", shell_command(paths, Some("bash"), options)),
+    ];
+    let mut snippet = String::from("case \"$SHELL\" in\n");
+    for (pattern, command) in arms {
+        snippet.push_str(&format!("  {})\n    {}\n    ;;\n", pattern, command));
+    }
+    snippet.push_str("esac");
+    snippet
+}
+
+/// Somewhere the list can be persisted with `w`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum SaveTarget {
+    /// A shell startup file, written with the given shell's syntax.
+    File { path: PathBuf, shell: String },
+    /// The user environment in the Windows registry.
+    Registry,
+}
+
+impl std::fmt::Display for SaveTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SaveTarget::File { path, .. } => write!(f, "{}", path.display()),
+            SaveTarget::Registry => write!(f, "registry (HKCU\\Environment)"),
+        }
+    }
+}
+
+/// Lists where the list can be saved for the current platform and shell, the
+/// most specific target first.
+fn save_targets(shell: Option<&str>) -> Vec<SaveTarget> {
+    if cfg!(windows) {
+        return vec![SaveTarget::Registry];
+    }
+    let Some(home) = env::var_os("HOME").map(PathBuf::from) else {
+        return vec![];
+    };
+    let file = |name: &str, shell: &str| SaveTarget::File {
+        path: home.join(name),
+        shell: shell.to_string(),
+    };
+    match shell {
+        Some("zsh") => vec![file(".zshrc", "zsh"), file(".zprofile", "zsh")],
+        Some("bash") => vec![
+            file(".bashrc", "bash"),
+            file(".bash_profile", "bash"),
+            file(".profile", "sh"),
+        ],
+        Some("fish") => vec![file(".config/fish/conf.d/pathctl.fish", "fish")],
+        Some("tcsh") => vec![file(".tcshrc", "tcsh"), file(".cshrc", "csh")],
+        Some("csh") => vec![file(".cshrc", "csh")],
+        _ => vec![file(".profile", "sh")],
+    }
+}
+
+const BLOCK_START: &str = "# >>> pathctl >>>";
+const BLOCK_END: &str = "# <<< pathctl <<<";
+
+/// Byte range of pathctl's managed block in `contents`, markers and trailing
+/// newline included.
+fn managed_block_range(contents: &str) -> Option<(usize, usize)> {
+    let start = contents.find(BLOCK_START)?;
+    let end = start + contents[start..].find(BLOCK_END)? + BLOCK_END.len();
+    match contents[end..].starts_with('\n') {
+        true => Some((start, end + 1)),
+        false => Some((start, end)),
+    }
+}
+
+/// Returns what is currently inside pathctl's managed block, if any.
+fn managed_block(contents: &str) -> Option<&str> {
+    let (start, end) = managed_block_range(contents)?;
+    let block = &contents[start + BLOCK_START.len()..end];
+    let block = block.trim_end_matches('\n').strip_suffix(BLOCK_END)?;
+    Some(block.trim_matches('\n'))
+}
+
+/// Puts `command` inside pathctl's managed block in `contents`, replacing
+/// the previous block if there is one and appending a new block otherwise.
+fn upsert_managed_block(contents: &str, command: &str) -> String {
+    let block = format!("{}\n{}\n{}\n", BLOCK_START, command, BLOCK_END);
+    if let Some((start, end)) = managed_block_range(contents) {
+        return format!("{}{}{}", &contents[..start], block, &contents[end..]);
+    }
+    match contents {
+        "" => block,
+        _ if contents.ends_with('\n') => format!("{}\n{}", contents, block),
+        _ => format!("{}\n\n{}", contents, block),
+    }
+}
+
+/// A line of a line-based diff.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum DiffLine {
+    Same(String),
+    Removed(String),
+    Added(String),
+}
+
+/// Diffs two texts line by line, keeping their longest common subsequence
+/// and reporting removals before additions.
+fn line_diff(old: &str, new: &str) -> Vec<DiffLine> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut diff = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            diff.push(DiffLine::Same(old[i].to_string()));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            diff.push(DiffLine::Removed(old[i].to_string()));
+            i += 1;
+        } else {
+            diff.push(DiffLine::Added(new[j].to_string()));
+            j += 1;
+        }
+    }
+    diff
+}
+
+/// Registry key holding the environment of `hive`.
+#[cfg(target_os = "windows")]
+fn environment_key(hive: Hive, flags: u32) -> io::Result<winreg::RegKey> {
+    use winreg::enums::*;
+    use winreg::RegKey;
+
+    match hive {
+        Hive::System => RegKey::predef(HKEY_LOCAL_MACHINE).open_subkey_with_flags(
+            r"SYSTEM\CurrentControlSet\Control\Session Manager\Environment",
+            flags,
+        ),
+        Hive::User => {
+            RegKey::predef(HKEY_CURRENT_USER).open_subkey_with_flags("Environment", flags)
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn save_to_registry(hive: Hive, var: &str, paths: &[PathBuf]) -> io::Result<()> {
+    let env = environment_key(hive, winreg::enums::KEY_SET_VALUE)?;
+    let name = if var == "PATH" { "Path" } else { var };
+    env.set_value(name, &join_entries(paths))
+}
+
+#[cfg(not(target_os = "windows"))]
+fn save_to_registry(_hive: Hive, _var: &str, _paths: &[PathBuf]) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "the registry is only available on Windows",
+    ))
+}
+
+/// Runs `launchctl getenv <var>` and returns its raw output.
+fn launchctl_getenv(var: &str) -> Result<String, String> {
+    let output = std::process::Command::new("launchctl")
+        .args(["getenv", var])
+        .output()
+        .map_err(|err| format!("cannot run launchctl: {}", err))?;
+    if !output.status.success() {
+        return Err(format!("launchctl getenv {} failed", var));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Splits the output of `launchctl getenv`, which prints the value followed
+/// by a newline, or nothing at all when the variable is unset.
+fn parse_launchctl_output(output: &str) -> Vec<PathBuf> {
+    let value = output.trim_end_matches(['\n', '\r']);
+    if value.is_empty() {
+        return vec![];
+    }
+    env::split_paths(value).collect()
+}
+
+/// Finds the value assigned to `PATH` by the last `ENV` instruction that sets
+/// it. Both `ENV PATH=value` and the legacy `ENV PATH value` forms are
+/// understood; variable references such as `$PATH` are kept verbatim.
+fn extract_dockerfile_path(contents: &str) -> Option<String> {
+    let mut path_var = None;
+    for line in contents.lines() {
+        let line = line.trim();
+        let Some((instruction, rest)) = line.split_once(char::is_whitespace) else {
+            continue;
+        };
+        if !instruction.eq_ignore_ascii_case("ENV") {
+            continue;
+        }
+        let rest = rest.trim();
+        let words = split_dockerfile_words(rest);
+        if words.first().is_some_and(|word| word.contains('=')) {
+            for word in words {
+                if let Some(value) = word.strip_prefix("PATH=") {
+                    path_var = Some(value.to_string());
+                }
+            }
+        } else if let Some(value) = rest.strip_prefix("PATH") {
+            if value.starts_with(char::is_whitespace) {
+                let value = split_dockerfile_words(value.trim()).join(" ");
+                path_var = Some(value);
+            }
+        }
+    }
+    path_var
+}
+
+/// Splits an instruction's arguments on whitespace, honouring quotes and
+/// backslash escapes the way the Dockerfile parser does.
+fn split_dockerfile_words(s: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match (c, quote) {
+            ('\\', _) => {
+                if let Some(escaped) = chars.next() {
+                    word.push(escaped);
+                }
+                in_word = true;
+            }
+            ('"' | '\'', None) => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (c, Some(q)) if c == q => quote = None,
+            (c, None) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            (c, _) => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    words
+}
+
+#[cfg(target_os = "windows")]
+fn get_windows_path_entries() -> Vec<PathBuf> {
+    use winreg::enums::*;
+    use winreg::RegKey;
+
+    let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+    let env = hkcu.open_subkey("Environment").unwrap();
+    let path_var: String = env.get_value("Path").unwrap_or_default();
+    env::split_paths(&path_var).collect()
+}
+
+/// Reads the system and user PATH separately, in the order Windows
+/// concatenates them into the effective one.
+#[cfg(target_os = "windows")]
+fn load_combined_windows() -> (Vec<PathBuf>, Vec<PathBuf>) {
+    let read = |hive| {
+        let path_var: String = environment_key(hive, winreg::enums::KEY_READ)
+            .and_then(|env| env.get_value("Path"))
+            .unwrap_or_default();
+        env::split_paths(&path_var).collect()
+    };
+    (read(Hive::System), read(Hive::User))
+}
+
+fn load_combined() -> Result<(Vec<PathBuf>, Vec<PathBuf>), String> {
+    #[cfg(target_os = "windows")]
+    {
+        Ok(load_combined_windows())
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        Err("--combined is only available on Windows".to_string())
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+fn detect_shell() -> Option<String> {
+    env::var("SHELL").ok().and_then(|path| {
+        Path::new(&path)
+            .file_name()
+            .and_then(|os_str| os_str.to_str())
+            .map(|s| s.to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_get_path_entries() {
+        // Backup the original PATH
+        let original_path = env::var("PATH").unwrap_or_default();
+
+        // Set a known PATH value
+        let test_paths = ["/usr/bin", "/bin", "/usr/local/bin"];
+        let test_path_var = env::join_paths(test_paths.iter().map(PathBuf::from)).unwrap();
+        env::set_var("PATH", &test_path_var);
+
+        // Call the function
+        let paths = get_path_entries();
+
+        // Verify the result
+        let expected_paths: Vec<PathBuf> = test_paths.iter().map(PathBuf::from).collect();
+        assert_eq!(paths, expected_paths);
+
+        // Restore the original PATH
+        env::set_var("PATH", &original_path);
+    }
+
+    #[test]
+    fn test_generate_shell_command() {
+        // Prepare new paths
+        let new_paths = vec![PathBuf::from("/custom/bin"), PathBuf::from("/another/bin")];
+
+        // Generate the shell command
+        let command = generate_shell_command(&new_paths, &Options::default());
+
+        // Detect shell
+        let shell = detect_shell();
+        let expected_command = {
+            let new_path_var = env::join_paths(&new_paths).expect("Failed to join paths");
+            let new_path_str = new_path_var.to_string_lossy();
+
+            match shell.as_deref() {
+                Some("fish") => format!("set -x PATH {}", new_path_str),
+                _ => format!("export PATH=\"{}\"", new_path_str),
+            }
+        };
+
+        // Verify the command
+        assert_eq!(command, expected_command);
+    }
+
+    #[test]
+    fn test_pwsh_command() {
+        let paths = vec![
+            PathBuf::from(r"C:\Tools\bin"),
+            PathBuf::from(r"C:\Windows\system32"),
+        ];
+        let options = Options {
+            pwsh_persist: true,
+            ..Options::default()
+        };
+
+        assert_eq!(
+            shell_command(&paths, Some("pwsh"), &options),
+            r#"[Environment]::SetEnvironmentVariable("Path", "C:\Tools\bin;C:\Windows\system32", "User")"#
+        );
+        assert!(
+            shell_command(&paths, Some("pwsh"), &Options::default()).starts_with("$env:PATH = \"")
+        );
+    }
+
+    #[test]
+    fn test_canonical_order() {
+        let options = Options {
+            format: OutputFormat::Shell("bash".to_string()),
+            canonical_order: true,
+            ..Options::default()
+        };
+        let one = vec![
+            PathBuf::from("/usr/local/bin"),
+            PathBuf::from("/bin"),
+            PathBuf::from("/usr/bin"),
+        ];
+        let other = vec![
+            PathBuf::from("/usr/bin"),
+            PathBuf::from("/usr/local/bin"),
+            PathBuf::from("/bin"),
+        ];
+
+        let expected = render_batch(one, &options);
+        assert_eq!(render_batch(other, &options), expected);
+        assert_eq!(
+            expected,
+            format!(
+                "export PATH=\"{}\"",
+                join_entries(&[
+                    PathBuf::from("/bin"),
+                    PathBuf::from("/usr/bin"),
+                    PathBuf::from("/usr/local/bin"),
+                ])
+            )
+        );
+    }
+
+    #[test]
+    fn test_comment_header() {
+        let header = comment_header("bash");
+        let date = header
+            .strip_prefix("# generated by pathctl on ")
+            .expect("bash header should be a # comment");
+        assert_eq!(date.len(), "YYYY-MM-DD".len());
+        assert_eq!(date.matches('-').count(), 2);
+
+        assert!(comment_header("fish").starts_with("# "));
+        assert_eq!(comment_header("cmd"), "");
+    }
+
+    #[test]
+    fn test_format_date() {
+        assert_eq!(format_date(0), "1970-01-01");
+        assert_eq!(format_date(951_782_400), "2000-02-29");
+        assert_eq!(format_date(1_735_689_599), "2024-12-31");
+    }
+
+    #[test]
+    fn test_fish_add_path_prepend() {
+        let startup = vec![PathBuf::from("/usr/bin"), PathBuf::from("/bin")];
+        let paths = vec![
+            PathBuf::from("/opt/a/bin"),
+            PathBuf::from("/opt/b bin"),
+            PathBuf::from("/usr/bin"),
+            PathBuf::from("/bin"),
+        ];
+        assert_eq!(
+            fish_add_path_command(&startup, &paths, &Options::default()),
+            "fish_add_path --prepend '/opt/b bin'\nfish_add_path --prepend /opt/a/bin"
+        );
+    }
+
+    #[test]
+    fn test_duplicate_and_edit() {
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        let backspace = KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE);
+        let mut app = App::new(vec![PathBuf::from("/usr/bin"), PathBuf::from("/tmp")]);
+
+        app.handle_key(key('C'));
+        assert!(matches!(app.input_mode, InputMode::Edit));
+        assert_eq!(app.list_state.selected(), Some(1));
+        for _ in 0.."bin".len() {
+            app.handle_key(backspace);
+        }
+        app.handle_key(key('l'));
+        app.handle_key(key('i'));
+        app.handle_key(key('b'));
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert_eq!(
+            app.paths,
+            vec![
+                PathBuf::from("/usr/bin"),
+                PathBuf::from("/usr/lib"),
+                PathBuf::from("/tmp"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_search_filters_entries() {
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        let mut app = App::new(vec![
+            PathBuf::from("/usr/bin"),
+            PathBuf::from("/home/me/.cargo/bin"),
+            PathBuf::from("/bin"),
+            PathBuf::from("/home/me/.pyenv/shims"),
+        ]);
+        app.list_state.select(Some(0));
+
+        app.handle_key(key('/'));
+        for c in "HOME".chars() {
+            app.handle_key(key(c));
+        }
+        assert_eq!(app.visible(), vec![1, 3]);
+        assert_eq!(app.list_state.selected(), Some(1));
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert!(matches!(app.input_mode, InputMode::Normal));
+
+        app.handle_key(key('n'));
+        assert_eq!(app.list_state.selected(), Some(3));
+        app.handle_key(key('n'));
+        assert_eq!(app.list_state.selected(), Some(1));
+
+        // Deleting acts on the underlying entry
+        app.handle_key(key('d'));
+        app.handle_key(key('d'));
+        assert_eq!(
+            app.paths,
+            vec![
+                PathBuf::from("/usr/bin"),
+                PathBuf::from("/bin"),
+                PathBuf::from("/home/me/.pyenv/shims"),
+            ]
+        );
+        assert_eq!(app.list_state.selected(), Some(2));
+
+        // Esc clears the search rather than quitting
+        assert!(!app.handle_key(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE)));
+        assert_eq!(app.visible(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_fuzzy_match() {
+        assert_eq!(
+            fuzzy_match("/usr/bin", "ub").map(|(_, p)| p),
+            Some(vec![1, 5])
+        );
+        assert_eq!(fuzzy_match("/usr/bin", "bu"), None);
+        assert!(fuzzy_match("/usr/bin", "").is_some());
+
+        // Component starts and runs beat scattered characters
+        let score = |candidate| fuzzy_match(candidate, "cb").unwrap().0;
+        assert!(score("/home/me/.cargo/bin") > score("/opt/arcade/lib"));
+        let score = |candidate| fuzzy_match(candidate, "bin").unwrap().0;
+        assert!(score("/usr/bin") > score("/usr/lib/bison/info"));
+    }
+
+    #[test]
+    fn test_fuzzy_finder_jumps() {
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        let mut app = App::new(vec![
+            PathBuf::from("/opt/arcade/lib"),
+            PathBuf::from("/usr/bin"),
+            PathBuf::from("/home/me/.cargo/bin"),
+        ]);
+        app.list_state.select(Some(0));
+
+        app.handle_key(KeyEvent::new(KeyCode::Char('p'), KeyModifiers::CONTROL));
+        assert_eq!(app.popup, Some(Popup::Fuzzy));
+        app.handle_key(key('c'));
+        app.handle_key(key('b'));
+        assert_eq!(app.fuzzy_matches(), vec![2, 0]);
+
+        app.handle_key(KeyEvent::new(KeyCode::Down, KeyModifiers::NONE));
+        app.handle_key(KeyEvent::new(KeyCode::Down, KeyModifiers::NONE));
+        assert_eq!(app.fuzzy_choice, 1);
+        app.handle_key(KeyEvent::new(KeyCode::Char('p'), KeyModifiers::CONTROL));
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert_eq!(app.popup, None);
+        assert_eq!(app.list_state.selected(), Some(2));
+        assert_eq!(app.paths.len(), 3);
+    }
+
+    #[test]
+    fn test_marked_bulk_operations() {
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        let paths = |names: &[&str]| names.iter().map(PathBuf::from).collect::<Vec<_>>();
+        let mut app = App::new(paths(&["/a", "/b", "/c", "/d", "/e"]));
+        app.list_state.select(Some(0));
+
+        // Space marks and moves on; V marks the range walked over
+        app.handle_key(key(' '));
+        app.handle_key(key('j'));
+        app.handle_key(key('V'));
+        app.handle_key(key('j'));
+        assert_eq!(app.marked(), BTreeSet::from([0, 2, 3]));
+        app.handle_key(key('V'));
+        assert_eq!(app.marks, BTreeSet::from([0, 2, 3]));
+
+        // The entry at the top holds the others back
+        app.handle_key(key('K'));
+        assert_eq!(app.paths, paths(&["/a", "/c", "/d", "/b", "/e"]));
+        assert_eq!(app.marks, BTreeSet::from([0, 1, 2]));
+
+        app.handle_key(key('x'));
+        assert_eq!(app.enabled(&app.paths), paths(&["/b", "/e"]));
+        assert!(app.marks.is_empty());
+
+        assert_eq!(app.list_state.selected(), Some(2));
+        app.handle_key(key(' '));
+        app.handle_key(key('j'));
+        app.handle_key(key(' '));
+        app.handle_key(key('d'));
+        app.handle_key(key('d'));
+        assert_eq!(app.paths, paths(&["/a", "/c", "/b"]));
+        assert_eq!(app.register, paths(&["/d", "/e"]));
+
+        // Edits that know nothing of the marks drop them
+        app.handle_key(key(' '));
+        app.handle_key(key('u'));
+        assert!(app.marks.is_empty());
+    }
+
+    #[test]
+    fn test_marked_duplicates() {
+        let paths: Vec<PathBuf> = ["/a", "/b", "/a", "/b", "/a"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+        let marked = BTreeSet::from([0, 2, 3, 4]);
+        let opts = DedupeOpts::default();
+        assert_eq!(
+            marked_duplicates(&paths, &marked, KeepPolicy::First, &opts),
+            BTreeSet::from([2, 4])
+        );
+        assert_eq!(
+            marked_duplicates(&paths, &marked, KeepPolicy::Last, &opts),
+            BTreeSet::from([0, 2])
+        );
+    }
+
+    #[test]
+    fn test_insert_expands_variables() {
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        let enter = KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE);
+        let dir = env::temp_dir();
+        env::set_var("PATHCTL_TEST_INSERT", &dir);
+
+        let mut app = App::new(vec![PathBuf::from("/usr/bin")]);
+        app.handle_key(key('a'));
+        app.input = "$PATHCTL_TEST_INSERT".to_string();
+        app.handle_key(enter);
+        assert_eq!(app.paths[1], dir);
+
+        app.literal_input = true;
+        app.handle_key(key('a'));
+        app.input = "${PATHCTL_TEST_INSERT}".to_string();
+        app.handle_key(enter);
+        assert_eq!(app.paths[2], PathBuf::from("${PATHCTL_TEST_INSERT}"));
+
+        app.handle_key(key('a'));
+        app.input = "$PATHCTL_TEST_INSERT/missing".to_string();
+        app.handle_key(enter);
+        assert_eq!(app.paths.len(), 3);
+        assert!(app.status.is_some());
+
+        // It is only added once confirmed, and the box stays open otherwise
+        assert_eq!(app.popup, Some(Popup::ConfirmAdd));
+        app.handle_key(key('n'));
+        assert!(matches!(app.input_mode, InputMode::InsertAfter));
+        assert_eq!(app.paths.len(), 3);
+        app.handle_key(enter);
+        app.handle_key(key('y'));
+        assert!(matches!(app.input_mode, InputMode::Normal));
+        assert_eq!(app.paths[3], PathBuf::from("$PATHCTL_TEST_INSERT/missing"));
+    }
+
+    #[test]
+    fn test_edit_entry() {
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        let enter = KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE);
+        let backspace = KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE);
+        let mut app = App::new(vec![PathBuf::from("/usr/bim"), PathBuf::from("/bin")]);
+
+        app.handle_key(key('e'));
+        assert_eq!(app.input, "/usr/bim");
+        app.handle_key(backspace);
+        app.handle_key(key('x'));
+        app.handle_key(enter);
+        // A path that does not exist is not accepted
+        assert!(matches!(app.input_mode, InputMode::Edit));
+
+        app.handle_key(backspace);
+        app.handle_key(backspace);
+        app.handle_key(backspace);
+        app.handle_key(enter);
+        assert!(matches!(app.input_mode, InputMode::Normal));
+        assert_eq!(
+            app.paths,
+            vec![PathBuf::from("/usr"), PathBuf::from("/bin")]
+        );
+
+        app.handle_key(key('u'));
+        assert_eq!(app.paths[0], PathBuf::from("/usr/bim"));
+    }
+
+    #[test]
+    fn test_move_selected() {
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        let mut app = App::new(vec![
+            PathBuf::from("/a"),
+            PathBuf::from("/b"),
+            PathBuf::from("/c"),
+        ]);
+        app.handle_key(key('J'));
+        app.handle_key(KeyEvent::new(KeyCode::Down, KeyModifiers::SHIFT));
+        app.handle_key(key('J'));
+        assert_eq!(
+            app.paths,
+            vec![
+                PathBuf::from("/b"),
+                PathBuf::from("/c"),
+                PathBuf::from("/a")
+            ]
+        );
+        assert_eq!(app.list_state.selected(), Some(2));
+
+        app.handle_key(key('K'));
+        assert_eq!(
+            app.paths,
+            vec![
+                PathBuf::from("/b"),
+                PathBuf::from("/a"),
+                PathBuf::from("/c")
+            ]
+        );
+        assert_eq!(app.list_state.selected(), Some(1));
+    }
+
+    #[test]
+    fn test_detail_target() {
+        let paths: Vec<PathBuf> = ["/a", "/b", "/c"].iter().map(PathBuf::from).collect();
+        let pinned = Some(Path::new("/c"));
+        assert_eq!(detail_target(None, Some(2), &paths), Some(2));
+        assert_eq!(detail_target(pinned, Some(0), &paths), Some(2));
+        assert_eq!(detail_target(None, None, &paths), None);
+        // A pinned entry that no longer exists falls back to the selection
+        assert_eq!(detail_target(pinned, Some(1), &paths[..2]), Some(1));
+
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        let mut app = App::new(paths);
+        app.handle_key(key('j'));
+        app.handle_key(key('m'));
+        assert!(app.show_details);
+        assert_eq!(app.pinned_detail, Some(PathBuf::from("/b")));
+
+        // Deleting a row above keeps the pane on the same entry
+        app.handle_key(key('g'));
+        app.handle_key(key('g'));
+        app.handle_key(key('d'));
+        app.handle_key(key('d'));
+        assert_eq!(app.paths, vec![PathBuf::from("/b"), PathBuf::from("/c")]);
+        let target = detail_target(
+            app.pinned_detail.as_deref(),
+            app.list_state.selected(),
+            &app.paths,
+        );
+        assert_eq!(target, Some(0));
+
+        app.handle_key(key('m'));
+        assert_eq!(app.pinned_detail, None);
+    }
+
+    #[test]
+    fn test_group_starts() {
+        let home = Some(Path::new("/home/me"));
+        let paths: Vec<PathBuf> = [
+            "/usr/local/bin",
+            "/usr/bin",
+            "/opt/a/bin",
+            "/opt/b/bin",
+            "/",
+            "/home/me/.cargo/bin",
+            "/home/me/.local/bin",
+            "/usr/sbin",
+        ]
+        .iter()
+        .map(PathBuf::from)
+        .collect();
+        let prefixes: Vec<Option<PathBuf>> = paths.iter().map(|p| fold_prefix(p, home)).collect();
+
+        assert_eq!(next_group_start(&prefixes, 0), Some(2));
+        assert_eq!(next_group_start(&prefixes, 2), Some(4));
+        assert_eq!(next_group_start(&prefixes, 4), Some(5));
+        assert_eq!(next_group_start(&prefixes, 5), Some(6));
+        assert_eq!(next_group_start(&prefixes, 7), None);
+
+        assert_eq!(prev_group_start(&prefixes, 3), Some(2));
+        assert_eq!(prev_group_start(&prefixes, 2), Some(0));
+        assert_eq!(prev_group_start(&prefixes, 1), Some(0));
+        assert_eq!(prev_group_start(&prefixes, 0), None);
+
+        // With folding on, { and } stop on the rows shown
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        let mut app = App::new(paths[..4].to_vec());
+        app.grouped = true;
+        assert_eq!(app.visible(), vec![0, 2]);
+        app.handle_key(key('}'));
+        assert_eq!(app.list_state.selected(), Some(2));
+        app.handle_key(key('}'));
+        assert_eq!(app.list_state.selected(), Some(2));
+        app.handle_key(key('{'));
+        assert_eq!(app.list_state.selected(), Some(0));
+
+        // And skip entries the search hides
+        app.grouped = false;
+        app.search = Some("bin".to_string());
+        app.paths.insert(2, PathBuf::from("/srv/data"));
+        app.handle_key(key('}'));
+        assert_eq!(app.list_state.selected(), Some(3));
+    }
+
+    #[test]
+    fn test_sibling_path() {
+        assert_eq!(
+            sibling_path(Path::new("/opt/tool/bin"), "sbin"),
+            Some(PathBuf::from("/opt/tool/sbin"))
+        );
+        assert_eq!(
+            sibling_path(Path::new("/bin"), "sbin"),
+            Some(PathBuf::from("/sbin"))
+        );
+        assert_eq!(sibling_path(Path::new("/"), "sbin"), None);
+        assert_eq!(sibling_path(Path::new("bin"), "sbin"), None);
+        assert_eq!(sibling_path(Path::new("/opt/tool/bin"), ""), None);
+    }
+
+    #[test]
+    fn test_parent_dir() {
+        assert_eq!(
+            parent_dir(Path::new("/usr/local/bin")),
+            Some(PathBuf::from("/usr/local"))
+        );
+        assert_eq!(parent_dir(Path::new("/usr")), Some(PathBuf::from("/")));
+        assert_eq!(parent_dir(Path::new("/")), None);
+        assert_eq!(parent_dir(Path::new("bin")), None);
+    }
+
+    #[test]
+    fn test_is_network_fs() {
+        let mounts = parse_mounts(
+            "/dev/sda1 / ext4 rw,relatime 0 0\n\
+             proc /proc proc rw 0 0\n\
+             server:/export/tools /opt/tools nfs4 rw 0 0\n\
+             /dev/sdb1 /opt/tools/local ext4 rw 0 0\n\
+             me@host:/home /mnt/my\\040home fuse.sshfs rw 0 0\n",
+        );
+        assert_eq!(mounts[4].mount_point, PathBuf::from("/mnt/my home"));
+
+        assert!(!is_network_fs(Path::new("/usr/bin"), &mounts));
+        assert!(is_network_fs(Path::new("/opt/tools/bin"), &mounts));
+        assert!(!is_network_fs(Path::new("/opt/tools/local/bin"), &mounts));
+        assert!(!is_network_fs(Path::new("/opt/toolsbin"), &mounts));
+        assert!(is_network_fs(Path::new("/mnt/my home/bin"), &mounts));
+        assert!(!is_network_fs(Path::new("/usr/bin"), &[]));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_dir_atime() {
+        let dir = env::temp_dir().join("pathctl-test-atime");
+        std::fs::create_dir_all(&dir).unwrap();
+        let epoch = SystemTime::UNIX_EPOCH;
+        let (accessed, modified) = (
+            epoch + Duration::from_secs(20),
+            epoch + Duration::from_secs(10),
+        );
+        let times = std::fs::FileTimes::new()
+            .set_accessed(accessed)
+            .set_modified(modified);
+        std::fs::File::open(&dir).unwrap().set_times(times).unwrap();
+        assert_eq!(dir_atime(&dir, &[]), Some((accessed, false)));
+
+        // A noatime mount has its access time ignored, which sorting notes
+        let mounts = parse_mounts(&format!(
+            "/dev/sda1 / ext4 rw,relatime 0 0\n/dev/sdb1 {} ext4 rw,noatime 0 0\n",
+            dir.display()
+        ));
+        assert!(!mounts[0].stale_atime && mounts[1].stale_atime);
+        assert_eq!(dir_atime(&dir, &mounts), Some((modified, true)));
+        assert_eq!(dir_atime(&dir.join("missing"), &mounts), None);
+
+        let mut app = App::new(vec![
+            PathBuf::from("/pathctl-test-atime-missing"),
+            dir.clone(),
+        ]);
+        app.mounts = mounts;
+        assert_eq!(app.sort_entries(SortOrder::RecentAccess), 1);
+        assert_eq!(app.paths[0], dir);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_duplicate_flags() {
+        let paths: Vec<PathBuf> = ["/usr/bin", "/bin", "/usr/bin/", "/usr/./bin", "/bin"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+        assert_eq!(
+            duplicate_flags(&paths, &DedupeOpts::default()),
+            vec![false, false, true, true, true]
+        );
+    }
+
+    #[test]
+    fn test_status_line() {
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        let mut app = App::new(vec![
+            PathBuf::from("/usr/bin"),
+            PathBuf::from("/bin"),
+            PathBuf::from("/sbin"),
+        ]);
+        assert_eq!(status_line(&app), "entry 1/3 · 0 changes · unmodified");
+
+        app.handle_key(key('J'));
+        assert_eq!(status_line(&app), "entry 2/3 · 1 changes · modified");
+        app.handle_key(key('x'));
+        assert_eq!(status_line(&app), "entry 2/3 · 1 changes · modified");
+        app.handle_key(key('x'));
+        app.handle_key(key('K'));
+        assert_eq!(status_line(&app), "entry 1/3 · 0 changes · unmodified");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_ownership_details() {
+        assert_eq!(mode_string(0o040755), "drwxr-xr-x");
+        assert_eq!(mode_string(0o100640), "-rw-r-----");
+        assert_eq!(mode_string(0o120777), "lrwxrwxrwx");
+
+        let passwd =
+            "# comment\nroot:x:0:0:root:/root:/bin/sh\nalice:x:1000:1000::/home/alice:/bin/zsh\n";
+        assert_eq!(id_name(passwd, 1000), Some("alice"));
+        assert_eq!(id_name(passwd, 0), Some("root"));
+        assert_eq!(id_name(passwd, 42), None);
+    }
+
+    #[test]
+    fn test_resolved_label() {
+        let dir = env::temp_dir().canonicalize().unwrap();
+        assert_eq!(resolved_label(&dir), None);
+        assert_eq!(
+            resolved_label(&dir.join("..").join(dir.file_name().unwrap())),
+            Some(format!("  → {}", dir.display()))
+        );
+        assert_eq!(resolved_label(&dir.join("pathctl-test-unresolvable")), None);
+
+        // Resolved once and remembered until `v` clears it
+        let mut cache = MissingCache::default();
+        let link = dir.join("..").join(dir.file_name().unwrap());
+        assert_eq!(
+            cache.resolved(&link),
+            Some(format!("  → {}", dir.display()).as_str())
+        );
+        assert!(cache.resolved.contains_key(&link));
+    }
+
+    #[test]
+    fn test_missing_cache() {
+        let dir = env::temp_dir().join("pathctl-test-missing");
+        let _ = std::fs::remove_dir(&dir);
+        let mut cache = MissingCache::default();
+        assert!(cache.is_missing(&dir));
+        assert!(!cache.is_missing(&env::temp_dir()));
+
+        // Remembered until the results expire
+        std::fs::create_dir(&dir).unwrap();
+        assert!(cache.is_missing(&dir));
+        cache.since = Some(Instant::now() - MISSING_TTL);
+        assert!(!cache.is_missing(&dir));
+
+        // So is the ignore marker
+        assert!(!cache.is_ignored(&dir));
+        std::fs::write(dir.join(IGNORE_MARKER), "").unwrap();
+        assert!(!cache.is_ignored(&dir));
+        cache.since = Some(Instant::now() - MISSING_TTL);
+        assert!(cache.is_ignored(&dir));
+        std::fs::remove_file(dir.join(IGNORE_MARKER)).unwrap();
+
+        std::fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_near_length_limit() {
+        assert!(!near_length_limit(0, 1000));
+        assert!(!near_length_limit(899, 1000));
+        assert!(near_length_limit(900, 1000));
+        assert!(near_length_limit(1000, 1000));
+        assert!(near_length_limit(1500, 1000));
+        assert_eq!(
+            joined_length(&[PathBuf::from("/usr/bin"), PathBuf::from("/bin")]),
+            "/usr/bin".len() + SEPARATOR.len() + "/bin".len()
+        );
+    }
+
+    #[test]
+    fn test_parse_config() {
+        assert_eq!(parse_config(""), Ok(Config::default()));
+        let config = parse_config("# colors\ntheme = \"solarized\"  # dark\n").unwrap();
+        assert_eq!(config.theme.as_deref(), Some("solarized"));
+        assert_eq!(
+            parse_config("theme = light").unwrap().theme.as_deref(),
+            Some("light")
+        );
+
+        assert!(parse_config("theme = neon")
+            .unwrap_err()
+            .starts_with("line 1:"));
+        assert!(parse_config("\ncolour = red")
+            .unwrap_err()
+            .starts_with("line 2:"));
+        let config = parse_config("[keys]\nup = \"t\"\ndown = h").unwrap();
+        assert_eq!(
+            config.keys,
+            vec![
+                ("up".to_string(), "t".to_string()),
+                ("down".to_string(), "h".to_string())
+            ]
+        );
+        assert_eq!(
+            parse_config("suggestions = ['~/tools/bin']")
+                .unwrap()
+                .suggestions,
+            vec![PathBuf::from("~/tools/bin")]
+        );
+        assert!(parse_config("suggestions = ~/tools/bin").is_err());
+        assert!(parse_config("[colors]\ntheme = light").is_err());
+        assert!(parse_config("theme").is_err());
+    }
+
+    #[test]
+    fn test_messages() {
+        let mut messages = Messages::default();
+        for i in 0..5 {
+            messages.push(format!("message {}", i));
+        }
+        messages.push("message 3".to_string());
+        let now = Instant::now();
+        assert_eq!(
+            messages.current(now),
+            vec!["message 3", "message 4", "message 2"]
+        );
+        assert!(messages.current(now + MESSAGE_TTL).is_empty());
+    }
+
+    #[test]
+    fn test_action_messages() {
+        let mut app = App::new(vec![PathBuf::from("/usr/bin"), PathBuf::from("/old/bin")]);
+        app.confirm_delete = false;
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        app.handle_key(key('j'));
+        app.handle_key(key('d'));
+        app.handle_key(key('d'));
+        let now = Instant::now();
+        assert_eq!(
+            app.messages.current(now),
+            vec!["removed /old/bin (press u to undo)"]
+        );
+
+        app.handle_key(key('a'));
+        for c in "/pathctl/nonexistent".chars() {
+            app.handle_key(key(c));
+        }
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        app.handle_key(key('n'));
+        assert_eq!(
+            app.messages.current(Instant::now())[0],
+            "/pathctl/nonexistent does not exist — not added"
+        );
+    }
+
+    #[test]
+    fn test_past_tense() {
+        assert_eq!(past_tense("remove /old/bin"), "removed /old/bin");
+        assert_eq!(past_tense("change 3 → 2 entries"), "changed 3 → 2 entries");
+        assert_eq!(past_tense("reorder"), "reordered");
+    }
+
+    #[test]
+    fn test_side_by_side() {
+        let paths =
+            |entries: &[&str]| -> Vec<PathBuf> { entries.iter().map(PathBuf::from).collect() };
+        let original = paths(&["/a", "/b", "/c", "/d"]);
+        let edited = paths(&["/c", "/a", "/d", "/e"]);
+        let (left, right) = side_by_side(&original, &edited, &DedupeOpts::default());
+        assert_eq!(
+            left,
+            vec![
+                Some(DiffTag::Moved),
+                None,
+                Some(DiffTag::Unchanged),
+                Some(DiffTag::Unchanged)
+            ]
+        );
+        assert_eq!(
+            right,
+            vec![
+                DiffTag::Unchanged,
+                DiffTag::Moved,
+                DiffTag::Unchanged,
+                DiffTag::Added
+            ]
+        );
+
+        let mut app = App::new(original);
+        app.handle_key(KeyEvent::new(KeyCode::Char('g'), KeyModifiers::NONE));
+        app.handle_key(KeyEvent::new(KeyCode::Char('d'), KeyModifiers::NONE));
+        assert_eq!(app.popup, Some(Popup::Diff));
+        assert_eq!(app.paths.len(), 4);
+    }
+
+    #[test]
+    fn test_tabs() {
+        env::set_var("PATHCTL_TEST_TAB", "/man/a:/man/b");
+        let mut app = App::new(vec![PathBuf::from("/usr/bin"), PathBuf::from("/bin")]);
+        app.output.format = OutputFormat::Shell("bash".to_string());
+        app.open_tabs(&["PATHCTL_TEST_TAB".to_string()], false);
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+
+        // Unchanged tabs are left out of the output
+        assert_eq!(app.command(), "export PATH=\"/usr/bin:/bin\"");
+
+        app.handle_key(KeyEvent::new(KeyCode::Char('2'), KeyModifiers::ALT));
+        assert_eq!(app.var, "PATHCTL_TEST_TAB");
+        assert_eq!(
+            app.paths,
+            vec![PathBuf::from("/man/a"), PathBuf::from("/man/b")]
+        );
+        assert!(app.history.snapshots.is_empty());
+        app.handle_key(key('J'));
+        assert_eq!(app.history.len(), 1);
+
+        // Each list keeps its own state and undo history
+        app.handle_key(KeyEvent::new(KeyCode::BackTab, KeyModifiers::SHIFT));
+        assert_eq!(app.var, "PATH");
+        assert!(app.history.snapshots.is_empty());
+        assert_eq!(
+            app.command(),
+            "export PATH=\"/usr/bin:/bin\"\nexport PATHCTL_TEST_TAB=\"/man/b:/man/a\""
+        );
+        env::remove_var("PATHCTL_TEST_TAB");
+    }
+
+    #[test]
+    fn test_keys() {
+        let bindings = |pairs: &[(&str, &str)]| -> Vec<(String, String)> {
+            pairs
+                .iter()
+                .map(|(action, key)| (action.to_string(), key.to_string()))
+                .collect()
+        };
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+
+        // Swapping two actions frees neither key
+        let keys = Keys::new(&bindings(&[("up", "j"), ("down", "k")])).unwrap();
+        assert_eq!(
+            keys.translate(key('j')).map(|k| k.code),
+            Some(KeyCode::Char('k'))
+        );
+        assert_eq!(
+            keys.translate(key('k')).map(|k| k.code),
+            Some(KeyCode::Char('j'))
+        );
+
+        // Moving an action to a free key leaves its old one unbound
+        let keys = Keys::new(&bindings(&[("delete", "Ctrl+X"), ("quit", "Z")])).unwrap();
+        let ctrl_x = KeyEvent::new(KeyCode::Char('x'), KeyModifiers::CONTROL);
+        assert_eq!(
+            keys.translate(ctrl_x).map(|k| k.code),
+            Some(KeyCode::Char('d'))
+        );
+        let shift_z = KeyEvent::new(KeyCode::Char('Z'), KeyModifiers::SHIFT);
+        assert_eq!(
+            keys.translate(shift_z).map(|k| k.code),
+            Some(KeyCode::Char('q'))
+        );
+        assert!(keys.translate(key('d')).is_none());
+        assert_eq!(
+            keys.translate(key('a')).map(|k| k.code),
+            Some(KeyCode::Char('a'))
+        );
+        assert_eq!(keys.labels(&["dd"]), "Ctrl+X Ctrl+X");
+        assert_eq!(keys.labels(&["q", "Esc"]), "Z/Esc");
+
+        // Conflicts are reported at startup
+        assert!(Keys::new(&bindings(&[("up", "a")])).is_err());
+        assert!(Keys::new(&bindings(&[("up", "t"), ("down", "t")])).is_err());
+        assert!(Keys::new(&bindings(&[("up", "3")])).is_err());
+        assert!(Keys::new(&bindings(&[("upward", "t")])).is_err());
+        assert!(Keys::new(&bindings(&[("up", "Ctrl+Alt+Nope")])).is_err());
+    }
+
+    #[test]
+    fn test_keys_remap_quit() {
+        let mut app = App::new(vec![PathBuf::from("/usr/bin")]);
+        app.keys = Keys::new(&[("quit".to_string(), "Z".to_string())]).unwrap();
+        assert!(!app.handle_key(KeyEvent::new(KeyCode::Char('q'), KeyModifiers::NONE)));
+        assert!(app.handle_key(KeyEvent::new(KeyCode::Char('Z'), KeyModifiers::SHIFT)));
+    }
+
+    #[test]
+    fn test_config_list() {
+        assert_eq!(
+            config_list(r#"["/opt/bin", '/srv/a,b' , "C:\\bin"] # old"#),
+            Ok(vec![
+                "/opt/bin".to_string(),
+                "/srv/a,b".to_string(),
+                "C:\\bin".to_string()
+            ])
+        );
+        assert_eq!(config_list("[]"), Ok(vec![]));
+        assert!(config_list("/opt/bin").is_err());
+        assert!(config_list("[\"/opt/bin").is_err());
+        assert!(config_list("[/opt/bin]").is_err());
+
+        let values = vec!["/opt/bin".to_string(), "/it's/bin".to_string()];
+        assert_eq!(config_list(&format_config_list(&values)), Ok(values));
+    }
+
+    #[test]
+    fn test_set_config_value() {
+        assert_eq!(
+            set_config_value("", "disabled", "PATH", "[]"),
+            "[disabled]\nPATH = []\n"
+        );
+        let contents = "theme = light\n\n[disabled]\nPATH = ['/a']\n\n[keys]\nup = t\n";
+        assert_eq!(
+            set_config_value(contents, "disabled", "PATH", "['/b']"),
+            "theme = light\n\n[disabled]\nPATH = ['/b']\n\n[keys]\nup = t\n"
+        );
+        assert_eq!(
+            set_config_value(contents, "disabled", "MANPATH", "['/m']"),
+            "theme = light\n\n[disabled]\nMANPATH = ['/m']\nPATH = ['/a']\n\n[keys]\nup = t\n"
+        );
+        assert_eq!(
+            set_config_value("theme = light", "disabled", "PATH", "[]"),
+            "theme = light\n\n[disabled]\nPATH = []\n"
+        );
+    }
+
+    #[test]
+    fn test_persist_disabled() {
+        let dir = env::temp_dir().join("pathctl-test-disabled");
+        let _ = std::fs::remove_dir_all(&dir);
+        let file = dir.join("config.toml");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&file, "[disabled]\nPATH = ['/elsewhere/bin', '/usr/bin']\n").unwrap();
+
+        let mut app = App::new(vec![PathBuf::from("/usr/bin"), PathBuf::from("/bin")]);
+        app.config_file = Some(file.clone());
+        app.disabled.insert(PathBuf::from("/usr/bin"));
+        app.handle_key(KeyEvent::new(KeyCode::Char('x'), KeyModifiers::NONE));
+        app.handle_key(KeyEvent::new(KeyCode::Char('j'), KeyModifiers::NONE));
+        app.handle_key(KeyEvent::new(KeyCode::Char('x'), KeyModifiers::NONE));
+
+        let config = parse_config(&std::fs::read_to_string(&file).unwrap()).unwrap();
+        assert_eq!(
+            config.disabled["PATH"],
+            vec![PathBuf::from("/elsewhere/bin"), PathBuf::from("/bin")]
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_pinned_entries() {
+        let dir = env::temp_dir().join("pathctl-test-pinned");
+        let _ = std::fs::remove_dir_all(&dir);
+        let file = dir.join("config.toml");
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        let missing = dir.join("missing");
+        let mut app = App::new(vec![
+            PathBuf::from("/usr/bin"),
+            missing.clone(),
+            PathBuf::from("/bin"),
+        ]);
+        app.config_file = Some(file.clone());
+
+        app.handle_key(key('g'));
+        app.handle_key(key('p'));
+        assert!(app.pinned.contains(Path::new("/usr/bin")));
+        let config = parse_config(&std::fs::read_to_string(&file).unwrap()).unwrap();
+        assert_eq!(config.pinned["PATH"], vec![PathBuf::from("/usr/bin")]);
+
+        // dd asks even without --confirm, and y keeps the pinned entry
+        app.handle_key(key('d'));
+        app.handle_key(key('d'));
+        assert_eq!(app.popup, Some(Popup::ConfirmDelete));
+        app.handle_key(key('y'));
+        assert_eq!(app.paths.len(), 3);
+
+        // Marked entries lose all but the pinned one
+        app.handle_key(key(' '));
+        app.handle_key(key(' '));
+        app.handle_key(key('d'));
+        app.handle_key(key('d'));
+        app.handle_key(key('y'));
+        assert_eq!(
+            app.paths,
+            vec![PathBuf::from("/usr/bin"), PathBuf::from("/bin")]
+        );
+        assert_eq!(
+            app.status.as_deref(),
+            Some("deleted 1 entries, kept 1 pinned entry")
+        );
+
+        // F leaves it alone too, unless ! overrides the pin
+        app.handle_key(key('F'));
+        app.handle_key(key('b'));
+        app.handle_key(key('i'));
+        app.handle_key(key('n'));
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        app.handle_key(key('y'));
+        assert_eq!(app.paths, vec![PathBuf::from("/usr/bin")]);
+        app.handle_key(key('F'));
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        app.handle_key(key('!'));
+        assert!(app.paths.is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_theme_named() {
+        for name in Theme::NAMES {
+            assert!(Theme::named(name).is_some(), "{}", name);
+        }
+        assert_eq!(Theme::named("default"), Some(Theme::default()));
+        assert_eq!(Theme::named("neon"), None);
+        assert_eq!(Theme::MONOCHROME.zebra, None);
+    }
+
+    #[test]
+    fn test_row_background() {
+        let shaded: Vec<bool> = (0..4)
+            .map(|row| row_background(row, true, &Theme::default()).is_some())
+            .collect();
+        assert_eq!(shaded, vec![false, true, false, true]);
+        let theme = Theme::default();
+        assert!((0..4).all(|row| row_background(row, false, &theme).is_none()));
+        let theme = Theme::MONOCHROME;
+        assert!((0..4).all(|row| row_background(row, true, &theme).is_none()));
+    }
+
+    #[test]
+    fn test_list_title_counts_enabled() {
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        let mut app = App::new(vec![
+            PathBuf::from("/a"),
+            PathBuf::from("/b"),
+            PathBuf::from("/c"),
+        ]);
+        assert_eq!(list_title(&app), "PATH Entries");
+
+        app.handle_key(key('x'));
+        app.handle_key(key('j'));
+        app.handle_key(key('j'));
+        app.handle_key(key('x'));
+        assert_eq!(list_title(&app), "PATH: 3 entries (1 enabled)");
+        assert_eq!(app.enabled(&app.paths), vec![PathBuf::from("/b")]);
+
+        app.handle_key(key('x'));
+        app.handle_key(key('k'));
+        app.handle_key(key('k'));
+        app.handle_key(key('x'));
+        assert_eq!(list_title(&app), "PATH Entries");
+    }
+
+    #[test]
+    fn test_add_command() {
+        assert_eq!(
+            add_command(Path::new("/opt/tool/bin")),
+            "pathctl add /opt/tool/bin"
+        );
+        assert_eq!(
+            add_command(Path::new("/opt/my tool/bin")),
+            "pathctl add '/opt/my tool/bin'"
+        );
+
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        let mut app = App::new(vec![PathBuf::from("/usr/bin"), PathBuf::from("/it's here")]);
+        app.handle_key(key('j'));
+        app.handle_key(key('c'));
+        assert_eq!(
+            app.clipboard.as_deref(),
+            Some("pathctl add '/it'\\''s here'")
+        );
+    }
+
+    #[test]
+    fn test_move_entry() {
+        let options = parse_args(["--move", "/usr/bin/", "--to", "1"].map(String::from)).unwrap();
+        let (dir, position) = options.move_entry.unwrap();
+        let mut paths = vec![
+            PathBuf::from("/usr/local/bin"),
+            PathBuf::from("/bin"),
+            PathBuf::from("/usr/bin"),
+        ];
+        let opts = DedupeOpts::default();
+
+        move_entry(&mut paths, &dir, position, &opts).unwrap();
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("/usr/bin"),
+                PathBuf::from("/usr/local/bin"),
+                PathBuf::from("/bin"),
+            ]
+        );
+
+        move_entry(&mut paths, Path::new("/usr/bin"), 99, &opts).unwrap();
+        assert_eq!(paths[2], PathBuf::from("/usr/bin"));
+
+        let before = paths.clone();
+        assert!(move_entry(&mut paths, Path::new("/opt/bin"), 1, &opts).is_err());
+        assert_eq!(paths, before);
+
+        assert!(parse_args(["--move", "/bin"].map(String::from)).is_err());
+        assert!(parse_args(["--move", "/bin", "--to", "0"].map(String::from)).is_err());
+    }
+
+    #[test]
+    fn test_add_entries() {
+        let options = parse_args(["add", "/opt/b", "/opt/a"].map(String::from)).unwrap();
+        let mut paths = vec![PathBuf::from("/usr/bin"), PathBuf::from("/opt/a/")];
+        add_entries(
+            &mut paths,
+            options.add.as_deref().unwrap(),
+            &options.dedupe_opts,
+        );
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("/opt/b"),
+                PathBuf::from("/opt/a"),
+                PathBuf::from("/usr/bin"),
+            ]
+        );
+        assert!(parse_args(["add"].map(String::from)).is_err());
+        assert!(parse_args(["/opt/a"].map(String::from)).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_copy_to_clipboard() {
+        // A program that takes the text leaves the terminal alone
+        let mut terminal = Vec::new();
+        copy_to_clipboard("/usr/bin", &[("cat", &[])], &mut terminal).unwrap();
+        assert!(terminal.is_empty());
+
+        // Missing and failing programs fall back to OSC 52
+        let tools: &[(&str, &[&str])] = &[("pathctl-test-no-such-tool", &[]), ("false", &[])];
+        copy_to_clipboard("/usr/bin", tools, &mut terminal).unwrap();
+        assert_eq!(terminal, b"\x1b]52;c;L3Vzci9iaW4=\x07");
+    }
+
+    #[test]
+    fn test_base64_encode() {
+        assert_eq!(base64_encode(b""), "");
+        assert_eq!(base64_encode(b"f"), "Zg==");
+        assert_eq!(base64_encode(b"fo"), "Zm8=");
+        assert_eq!(base64_encode(b"foo"), "Zm9v");
+        assert_eq!(
+            base64_encode(b"pathctl add /opt"),
+            "cGF0aGN0bCBhZGQgL29wdA=="
+        );
+    }
+
+    #[test]
+    fn test_join_with() {
+        let paths = vec![PathBuf::from("/usr/local/bin"), PathBuf::from("/usr/bin")];
+        assert_eq!(join_with(&paths, ","), "/usr/local/bin,/usr/bin");
+        assert_eq!(join_with(&paths, " -> "), "/usr/local/bin -> /usr/bin");
+
+        let options =
+            parse_args(["--format", "raw", "--out-separator", ","].map(String::from)).unwrap();
+        assert_eq!(render_command(&paths, &options), "/usr/local/bin,/usr/bin");
+        assert!(parse_args(["--out-separator", ","].map(String::from)).is_err());
+    }
+
+    #[test]
+    fn test_write_output() {
+        let file = env::temp_dir().join("pathctl-test-output.sh");
+        let paths = vec![PathBuf::from("/usr/local/bin"), PathBuf::from("/usr/bin")];
+        let command = generate_shell_command(&paths, &Options::default());
+
+        write_output(&file, &command).unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&file).unwrap(),
+            format!("{}\n", command)
+        );
+        std::fs::remove_file(&file).unwrap();
+    }
+
+    #[test]
+    fn test_build_script() {
+        let script = build_script("fish", "set -x PATH /usr/bin");
+        assert!(script.starts_with("#!/usr/bin/env fish\n"));
+        assert!(script.ends_with("\nset -x PATH /usr/bin\n"));
+
+        let script = build_script("sh", "export PATH=\"/usr/bin\"");
+        assert!(script.starts_with("#!/bin/sh\n"));
+        assert!(script
+            .lines()
+            .any(|line| line == "export PATH=\"/usr/bin\""));
+    }
+
+    #[test]
+    fn test_undo_steps() {
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        let original = vec![
+            PathBuf::from("/a"),
+            PathBuf::from("/b"),
+            PathBuf::from("/c"),
+            PathBuf::from("/d"),
+        ];
+        let mut app = App::new(original.clone());
+        for _ in 0..4 {
+            app.handle_key(key('d'));
+            app.handle_key(key('d'));
+        }
+        assert!(app.paths.is_empty());
+        assert_eq!(app.history.len(), 4);
+        assert_eq!(app.history.snapshots[0].description, "remove /a");
+
+        assert_eq!(app.undo(3), 3);
+        assert_eq!(
+            app.paths,
+            vec![
+                PathBuf::from("/b"),
+                PathBuf::from("/c"),
+                PathBuf::from("/d")
+            ]
+        );
+
+        // Clamped to what is left
+        assert_eq!(app.undo(10), 1);
+        assert_eq!(app.paths, original);
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        let redo = KeyEvent::new(KeyCode::Char('r'), KeyModifiers::CONTROL);
+        let original = vec![
+            PathBuf::from("/a"),
+            PathBuf::from("/b"),
+            PathBuf::from("/c"),
+        ];
+        let mut app = App::new(original.clone());
+
+        // Reorder, then delete
+        app.handle_key(key('J'));
+        let reordered = app.paths.clone();
+        app.handle_key(key('d'));
+        app.handle_key(key('d'));
+        let deleted = app.paths.clone();
+
+        app.handle_key(key('u'));
+        assert_eq!(app.paths, reordered);
+        app.handle_key(key('u'));
+        assert_eq!(app.paths, original);
+        app.handle_key(key('u'));
+        assert_eq!(app.status.as_deref(), Some("nothing to undo"));
+
+        app.handle_key(redo);
+        assert_eq!(app.paths, reordered);
+        app.handle_key(redo);
+        assert_eq!(app.paths, deleted);
+        app.handle_key(redo);
+        assert_eq!(app.status.as_deref(), Some("nothing to redo"));
+
+        // A new edit after undoing drops what could be redone
+        app.handle_key(key('u'));
+        app.handle_key(key('T'));
+        app.handle_key(key('K'));
+        app.handle_key(redo);
+        assert_eq!(app.status.as_deref(), Some("nothing to redo"));
+    }
+
+    #[test]
+    fn test_history_browser() {
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        let mut app = App::new(vec![PathBuf::from("/a"), PathBuf::from("/b")]);
+        app.handle_key(key('d'));
+        app.handle_key(key('d'));
+        app.handle_key(key('D'));
+        app.handle_key(key('d'));
+        app.handle_key(key('d'));
+
+        app.handle_key(key('U'));
+        app.handle_key(key('j'));
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+
+        assert_eq!(app.paths, vec![PathBuf::from("/a"), PathBuf::from("/b")]);
+        assert_eq!(app.history.len(), 0);
+    }
+
+    #[test]
+    fn test_command_popup() {
+        let paths = vec![PathBuf::from("/usr/local/bin"), PathBuf::from("/usr/bin")];
+        let options = Options {
+            format: OutputFormat::Shell("fish".to_string()),
+            ..Options::default()
+        };
+        let mut app = App::new(paths.clone());
+        app.output = options.clone();
+
+        app.handle_key(KeyEvent::new(KeyCode::Char('g'), KeyModifiers::CONTROL));
+
+        assert_eq!(app.popup, Some(Popup::Command));
+        assert_eq!(app.command(), render_command(&paths, &options));
+        assert_eq!(app.command(), "set -x PATH /usr/local/bin:/usr/bin");
+    }
+
+    #[test]
+    fn test_is_pathctl_ignored() {
+        let dir = env::temp_dir().join("pathctl-test-ignore");
+        std::fs::create_dir_all(&dir).unwrap();
+        let _ = std::fs::remove_file(dir.join(IGNORE_MARKER));
+        assert!(!is_pathctl_ignored(&dir));
+
+        std::fs::write(dir.join(IGNORE_MARKER), "").unwrap();
+        assert!(is_pathctl_ignored(&dir));
+        let mut paths = vec![dir.clone(), dir.clone()];
+        assert_eq!(
+            dedupe_paths(&mut paths, KeepPolicy::First, &DedupeOpts::default()),
+            0
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(!is_pathctl_ignored(&dir));
+    }
+
+    #[test]
+    fn test_mandatory_first() {
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        let shims = env::temp_dir();
+        let mut app = App::new(vec![shims.clone(), PathBuf::from("/usr/bin")]);
+        app.handle_key(key('!'));
+        assert_eq!(app.mandatory_first, Some(shims.clone()));
+
+        // Insert a directory before the pinned entry
+        app.handle_key(key('b'));
+        for c in "/".chars() {
+            app.handle_key(key(c));
+        }
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+
+        assert_eq!(
+            app.paths,
+            vec![shims, PathBuf::from("/"), PathBuf::from("/usr/bin")]
+        );
+        assert_eq!(app.list_state.selected(), Some(1));
+    }
+
+    #[test]
+    fn test_under_repo() {
+        let root = Path::new("/home/me/src/app");
+        assert!(under_repo(Path::new("/home/me/src/app/bin"), root));
+        assert!(under_repo(
+            Path::new("/home/me/src/app/node_modules/.bin/"),
+            root
+        ));
+        assert!(under_repo(Path::new("/home/me/src/app"), root));
+        assert!(!under_repo(Path::new("/home/me/src/app-tools/bin"), root));
+        assert!(!under_repo(
+            Path::new("/home/me/src/app/../other/bin"),
+            root
+        ));
+        assert!(!under_repo(Path::new("/usr/bin"), root));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scans_skip_non_directories() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = env::temp_dir().join("pathctl-test-scan");
+        let (first, second) = (root.join("first"), root.join("second"));
+        std::fs::create_dir_all(&first).unwrap();
+        std::fs::create_dir_all(&second).unwrap();
+        for dir in [&first, &second] {
+            let tool = dir.join("tool");
+            std::fs::write(&tool, "#!/bin/sh\n").unwrap();
+            std::fs::set_permissions(&tool, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        std::fs::write(first.join("README"), "").unwrap();
+        let file = first.join("tool");
+        let missing = root.join("missing");
+
+        let counts = count_executables(&[missing.clone(), file.clone(), first.clone()]);
+        assert_eq!(
+            counts,
+            ExecutableCounts {
+                counts: vec![None, None, Some(1)],
+                skipped: 2,
+            }
+        );
+
+        let report = find_shadowed(&[missing, first, file, second]);
+        assert_eq!(report.skipped, 2);
+        assert_eq!(
+            report.shadowed,
+            vec![Shadowed {
+                name: OsString::from("tool"),
+                winner: 1,
+                shadowed: vec![3],
+            }]
+        );
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_which() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = env::temp_dir().join("pathctl-test-which");
+        let dirs: Vec<PathBuf> = ["a", "b", "c"].iter().map(|d| root.join(d)).collect();
+        for dir in &dirs {
+            std::fs::create_dir_all(dir).unwrap();
+        }
+        for dir in [&dirs[1], &dirs[2]] {
+            let tool = dir.join("tool");
+            std::fs::write(&tool, "#!/bin/sh\n").unwrap();
+            std::fs::set_permissions(&tool, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        std::fs::write(dirs[0].join("tool"), "").unwrap();
+        assert_eq!(providers(&dirs, "tool"), vec![1, 2]);
+
+        // The shadowing view shows the same
+        let mut app = App::new(dirs.clone());
+        app.handle_key(KeyEvent::new(KeyCode::Char('W'), KeyModifiers::SHIFT));
+        assert_eq!(app.popup, Some(Popup::Shadowing));
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert_eq!(app.list_state.selected(), Some(1));
+        assert_eq!(app.marks, BTreeSet::from([2]));
+
+        let mut app = App::new(dirs.clone());
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        for c in ":tool".chars() {
+            app.handle_key(key(c));
+        }
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert_eq!(app.list_state.selected(), Some(1));
+        assert_eq!(app.marks, BTreeSet::from([2]));
+
+        for c in ":nope".chars() {
+            app.handle_key(key(c));
+        }
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert_eq!(app.status.as_deref(), Some("nope is not in any entry"));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_remove_matching() {
+        let mut app = App::new(vec![
+            PathBuf::from("/home/me/.nvm/v18/bin"),
+            PathBuf::from("/usr/bin"),
+            PathBuf::from("/home/me/.nvm/v20/bin"),
+        ]);
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        let enter = KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE);
+
+        // An invalid pattern keeps the box open and says why
+        app.handle_key(key('F'));
+        app.handle_key(key('('));
+        assert_eq!(
+            app.removal_regex.as_ref().unwrap().as_ref().unwrap_err(),
+            "unclosed group"
+        );
+        app.handle_key(enter);
+        assert!(matches!(app.input_mode, InputMode::RemoveMatching));
+        app.handle_key(KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE));
+        assert!(app.removal_regex.is_none());
+
+        for c in r"\.nvm/".chars() {
+            app.handle_key(key(c));
+        }
+        app.handle_key(enter);
+        assert_eq!(app.popup, Some(Popup::RemoveMatching));
+        assert_eq!(app.pending_delete, BTreeSet::from([0, 2]));
+        app.handle_key(key('y'));
+        assert_eq!(app.paths, vec![PathBuf::from("/usr/bin")]);
+
+        app.handle_key(key('u'));
+        app.handle_key(key('F'));
+        app.handle_key(enter);
+        app.handle_key(key('n'));
+        assert_eq!(app.paths.len(), 3);
+    }
+
+    #[test]
+    fn test_section_routing() {
+        // Three system entries followed by two user ones
+        let sections: Vec<Hive> = (0..5).map(|i| section_of(i, 3)).collect();
+        assert_eq!(
+            sections,
+            vec![
+                Hive::System,
+                Hive::System,
+                Hive::System,
+                Hive::User,
+                Hive::User
+            ]
+        );
+        assert_eq!(section_of(0, 0), Hive::User);
+
+        // Inserting after the last system entry grows the system section
+        assert_eq!(shift_boundary(3, Some(2), 5, 6), 4);
+        // Inserting before the first user entry does not
+        assert_eq!(shift_boundary(3, Some(3), 5, 6), 3);
+        // Cutting a system entry shrinks it
+        assert_eq!(shift_boundary(3, Some(0), 5, 4), 2);
+        // Replacing the list with a shorter one keeps the boundary in range
+        assert_eq!(shift_boundary(3, None, 5, 2), 2);
+    }
+
+    #[test]
+    fn test_zsh_array() {
+        let paths = vec![
+            PathBuf::from("/usr/local/bin"),
+            PathBuf::from("/opt/my tool/bin"),
+        ];
+        let options = Options {
+            zsh_array: true,
+            ..Options::default()
+        };
+
+        assert_eq!(
+            shell_command(&paths, Some("zsh"), &options),
+            "typeset -U path\npath=( /usr/local/bin '/opt/my tool/bin' )"
+        );
+        assert!(shell_command(&paths, Some("bash"), &options).starts_with("export PATH="));
+    }
+
+    #[test]
+    fn test_sh_quote() {
+        assert_eq!(sh_quote("/usr/bin"), "/usr/bin");
+        assert_eq!(sh_quote("/opt/my tool"), "'/opt/my tool'");
+        assert_eq!(sh_quote("it's"), "'it'\\''s'");
+        assert_eq!(sh_quote(""), "''");
+    }
+
+    #[test]
+    fn test_universal_snippet() {
+        let paths = vec![PathBuf::from("/custom/bin"), PathBuf::from("/usr/bin")];
+        let joined = join_entries(&paths);
+
+        let snippet = universal_snippet(&paths, &Options::default());
+
+        assert!(snippet.starts_with("case \"$SHELL\" in\n"));
+        assert!(snippet.ends_with("esac"));
+        assert!(snippet.contains(&format!(
+            "  */fish)\n    printf '%s\\n' 'set -x PATH {}'\n    ;;",
+            joined
+        )));
+        assert!(snippet.contains(&format!(
+            "  */csh|*/tcsh)\n    printf '%s\\n' 'setenv PATH \"{}\"'\n    ;;",
+            joined
+        )));
+        assert!(snippet.contains(&format!("  *)\n    export PATH=\"{}\"\n    ;;", joined)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_universal_snippet_syntax() {
+        let paths = vec![PathBuf::from("/custom/bin"), PathBuf::from("/custom/sbin")];
+        let joined = join_entries(&paths);
+        let options = Options {
+            fish_add_path: true,
+            ..Options::default()
+        };
+        let snippet = universal_snippet(&paths, &options);
+        let run = |shell: &str| {
+            let output = std::process::Command::new("sh")
+                .args(["-c", &format!("{}\necho \"$PATH\"", snippet)])
+                .env("SHELL", shell)
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "{:?}", output);
+            String::from_utf8(output.stdout).unwrap()
+        };
+
+        // sh runs the snippet whatever the shell, and sets PATH itself for bash
+        assert_eq!(run("/bin/bash"), format!("{}\n", joined));
+
+        // For fish and csh it prints their code, a line at a time, and
+        // leaves PATH alone
+        let path = env::var("PATH").unwrap_or_default();
+        let fish = shell_command(&paths, Some("fish"), &options);
+        assert_eq!(fish.lines().count(), 2);
+        assert_eq!(run("/usr/bin/fish"), format!("{}\n{}\n", fish, path));
+        assert_eq!(
+            run("/bin/tcsh"),
+            format!("setenv PATH \"{}\"\n{}\n", joined, path)
+        );
+    }
+
+    #[test]
+    fn test_parse_args_format() {
+        let options = parse_args(vec!["--format".to_string(), "universal".to_string()]).unwrap();
+        assert_eq!(options.format, OutputFormat::Universal);
+
+        let options = parse_args(Vec::new()).unwrap();
+        assert_eq!(options.format, OutputFormat::Auto);
+
+        assert!(parse_args(vec!["--format".to_string()]).is_err());
+        assert!(parse_args(vec!["--format".to_string(), "cmd".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_parse_args_theme() {
+        let options = parse_args(vec!["--theme".to_string(), "light".to_string()]).unwrap();
+        assert_eq!(options.theme.as_deref(), Some("light"));
+        assert!(parse_args(vec!["--theme".to_string(), "neon".to_string()]).is_err());
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_join_entries() {
+        let paths = vec![
+            PathBuf::from("/usr/bin"),
+            PathBuf::from("/bin"),
+            PathBuf::from("/usr/local/bin"),
+        ];
+
+        assert_eq!(join_entries(&paths), "/usr/bin:/bin:/usr/local/bin");
+    }
+
+    #[test]
+    fn test_toggle_raw_view() {
+        let mut app = App::new(vec![PathBuf::from("/usr/bin")]);
+        let key = KeyEvent::new(KeyCode::Char('r'), KeyModifiers::NONE);
+
+        assert!(!app.handle_key(key));
+        assert!(app.raw_view);
+        assert!(!app.handle_key(key));
+        assert!(!app.raw_view);
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_clean_entries_keep_empty() {
+        let path_var = ":/usr/bin:/bin";
+
+        let mut kept: Vec<PathBuf> = env::split_paths(path_var).collect();
+        assert_eq!(clean_entries(&mut kept, true), 0);
+        assert_eq!(
+            kept,
+            vec![
+                PathBuf::new(),
+                PathBuf::from("/usr/bin"),
+                PathBuf::from("/bin")
+            ]
+        );
+        assert_eq!(display_entry(&kept[0]), "(current directory)");
+        assert_eq!(join_entries(&kept), path_var);
+
+        let mut cleaned: Vec<PathBuf> = env::split_paths(path_var).collect();
+        assert_eq!(clean_entries(&mut cleaned, false), 1);
+        assert_eq!(join_entries(&cleaned), "/usr/bin:/bin");
+    }
+
+    #[test]
+    fn test_dedupe_key() {
+        let exact = DedupeOpts {
+            ignore_case: false,
+            unify_separators: false,
+        };
+        let folding = DedupeOpts {
+            ignore_case: true,
+            unify_separators: true,
+        };
+        let key = |p: &str, opts: &DedupeOpts| dedupe_key(Path::new(p), opts);
+
+        // Trailing slash
+        assert_eq!(key("/usr/bin/", &exact), key("/usr/bin", &exact));
+        // `.` and `..`
+        assert_eq!(key("/usr/./lib/../bin", &exact), key("/usr/bin", &exact));
+        // Case
+        assert_ne!(key("/Usr/Bin", &exact), key("/usr/bin", &exact));
+        assert_eq!(
+            key(
+                "/Usr/Bin",
+                &DedupeOpts {
+                    ignore_case: true,
+                    ..exact
+                }
+            ),
+            key("/usr/bin", &exact)
+        );
+        // Separator style
+        assert_ne!(key("/usr\\bin", &exact), key("/usr/bin", &exact));
+        assert_eq!(
+            key(
+                "/usr\\bin",
+                &DedupeOpts {
+                    unify_separators: true,
+                    ..exact
+                }
+            ),
+            key("/usr/bin", &exact)
+        );
+        // All at once
+        assert_eq!(
+            key("/USR\\local\\..\\Bin\\", &folding),
+            key("/usr/bin", &exact)
+        );
+    }
+
+    #[test]
+    fn test_dedupe_paths() {
+        let paths = vec![
+            PathBuf::from("/a"),
+            PathBuf::from("/b"),
+            PathBuf::from("/a"),
+            PathBuf::from("/c"),
+            PathBuf::from("/b"),
+        ];
+        let opts = DedupeOpts::default();
+
+        let mut first = paths.clone();
+        assert_eq!(dedupe_paths(&mut first, KeepPolicy::First, &opts), 2);
+        assert_eq!(
+            first,
+            vec![
+                PathBuf::from("/a"),
+                PathBuf::from("/b"),
+                PathBuf::from("/c")
+            ]
+        );
+
+        let mut last = paths.clone();
+        assert_eq!(dedupe_paths(&mut last, KeepPolicy::Last, &opts), 2);
+        assert_eq!(
+            last,
+            vec![
+                PathBuf::from("/a"),
+                PathBuf::from("/c"),
+                PathBuf::from("/b")
+            ]
+        );
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_top_level_histogram() {
+        let paths = vec![
+            PathBuf::from("/usr/bin"),
+            PathBuf::from("/opt/tool/bin"),
+            PathBuf::from("/usr/local/bin"),
+            PathBuf::from("/home/me/.cargo/bin"),
+            PathBuf::from("/usr/sbin"),
+            PathBuf::from("/opt/other/bin"),
+        ];
+
+        assert_eq!(
+            top_level_histogram(&paths),
+            vec![
+                ("/usr".to_string(), 3),
+                ("/opt".to_string(), 2),
+                ("/home".to_string(), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_key_name() {
+        assert_eq!(
+            parse_key_name("a"),
+            Some(KeyEvent::new(KeyCode::Char('a'), KeyModifiers::NONE))
+        );
+        assert_eq!(
+            parse_key_name("D"),
+            Some(KeyEvent::new(KeyCode::Char('D'), KeyModifiers::SHIFT))
+        );
+        assert_eq!(
+            parse_key_name("Down"),
+            Some(KeyEvent::new(KeyCode::Down, KeyModifiers::NONE))
+        );
+        assert_eq!(
+            parse_key_name("Ctrl+D"),
+            Some(KeyEvent::new(KeyCode::Char('d'), KeyModifiers::CONTROL))
+        );
+        assert_eq!(
+            parse_key_name("esc"),
+            Some(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE))
+        );
+        assert_eq!(
+            parse_key_name("+"),
+            Some(KeyEvent::new(KeyCode::Char('+'), KeyModifiers::NONE))
+        );
+        assert_eq!(parse_key_name("Hyper+x"), None);
+        assert_eq!(parse_key_name("Nope"), None);
+    }
+
+    #[test]
+    fn test_clean_pipeline_report() {
+        let existing = env::temp_dir();
+        let missing = existing.join("pathctl-test-missing-dir");
+        let mut paths = vec![
+            existing.clone(),
+            PathBuf::new(),
+            missing.clone(),
+            existing.clone(),
+            PathBuf::new(),
+            missing,
+            existing.clone(),
+        ];
+        let options = Options {
+            clean: true,
+            ..Options::default()
+        };
+
+        let report = clean_pipeline(&mut paths, &options);
+
+        assert_eq!(paths, vec![existing]);
+        assert_eq!(
+            report,
+            CleanReport {
+                duplicates: 2,
+                missing: 2,
+                empty: 2,
+                before: 7,
+                after: 1,
+            }
+        );
+        assert_eq!(
+            report.to_string(),
+            "removed 2 duplicates, dropped 2 missing, trimmed 2 empty; \
+             lookup dirs: 7 → 1 (85% fewer)"
+        );
+    }
+
+    #[test]
+    fn test_lookup_estimate() {
+        assert_eq!(lookup_estimate(42, 36), "lookup dirs: 42 → 36 (14% fewer)");
+        assert_eq!(lookup_estimate(10, 10), "lookup dirs: 10 → 10");
+        assert_eq!(lookup_estimate(0, 0), "lookup dirs: 0 → 0");
+    }
+
+    #[test]
+    fn test_single_mode() {
+        let original = env::var_os("PATHCTL_TEST_SINGLE");
+        let dir = env::temp_dir().join("with:colon");
+        env::set_var("PATHCTL_TEST_SINGLE", &dir);
+
+        let paths = get_var_entries("PATHCTL_TEST_SINGLE", true);
+        assert_eq!(paths, vec![dir.clone()]);
+        assert_eq!(
+            shell_command(
+                &paths,
+                Some("bash"),
+                &Options {
+                    var: Some("PATHCTL_TEST_SINGLE".to_string()),
+                    single: true,
+                    ..Options::default()
+                }
+            ),
+            format!("export PATHCTL_TEST_SINGLE=\"{}\"", dir.display())
+        );
+
+        let mut app = App::new(paths);
+        app.single = true;
+        app.handle_key(KeyEvent::new(KeyCode::Char('a'), KeyModifiers::NONE));
+        app.input = env::temp_dir().display().to_string();
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert_eq!(app.paths, vec![env::temp_dir()]);
+
+        match original {
+            Some(value) => env::set_var("PATHCTL_TEST_SINGLE", value),
+            None => env::remove_var("PATHCTL_TEST_SINGLE"),
+        }
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_lexically_normalize() {
+        assert_eq!(
+            lexically_normalize(Path::new("/usr/bin/.")),
+            PathBuf::from("/usr/bin")
+        );
+        assert_eq!(
+            lexically_normalize(Path::new("/a/b/../c")),
+            PathBuf::from("/a/c")
+        );
+        assert_eq!(
+            lexically_normalize(Path::new("/usr/local/bin")),
+            PathBuf::from("/usr/local/bin")
+        );
+        assert_eq!(
+            lexically_normalize(Path::new("/../bin")),
+            PathBuf::from("/bin")
+        );
+        assert_eq!(
+            lexically_normalize(Path::new("../bin")),
+            PathBuf::from("../bin")
+        );
+        assert_eq!(lexically_normalize(Path::new("bin/..")), PathBuf::from("."));
+
+        let mut paths = vec![PathBuf::from("/usr/bin"), PathBuf::from("/usr/lib/../bin")];
+        let opts = DedupeOpts::default();
+        assert_eq!(dedupe_paths(&mut paths, KeepPolicy::First, &opts), 1);
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_save_targets() {
+        let targets = save_targets(Some("zsh"));
+        match targets.first() {
+            Some(SaveTarget::File { path, shell }) => {
+                assert!(path.ends_with(".zshrc"));
+                assert_eq!(shell, "zsh");
+            }
+            other => panic!("expected the zshrc first, got {:?}", other),
+        }
+        assert!(!targets.contains(&SaveTarget::Registry));
+    }
+
+    #[cfg(target_os = "windows")]
+    #[test]
+    fn test_save_targets() {
+        assert_eq!(save_targets(None), vec![SaveTarget::Registry]);
+    }
+
+    #[test]
+    fn test_upsert_managed_block() {
+        let fresh = upsert_managed_block("alias ll='ls -l'\n", "export PATH=\"/a\"");
+        assert_eq!(
+            fresh,
+            "alias ll='ls -l'\n\n# >>> pathctl >>>\nexport PATH=\"/a\"\n# <<< pathctl <<<\n"
+        );
+
+        let updated = upsert_managed_block(&format!("{}echo done\n", fresh), "export PATH=\"/b\"");
+        assert_eq!(
+            updated,
+            "alias ll='ls -l'\n\n# >>> pathctl >>>\nexport PATH=\"/b\"\n# <<< pathctl <<<\necho done\n"
+        );
+    }
+
+    #[test]
+    fn test_line_diff() {
+        assert_eq!(
+            line_diff(
+                "typeset -U path\npath=( /a )",
+                "typeset -U path\npath=( /b )"
+            ),
+            vec![
+                DiffLine::Same("typeset -U path".to_string()),
+                DiffLine::Removed("path=( /a )".to_string()),
+                DiffLine::Added("path=( /b )".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_declined_save_does_not_write() {
+        let rc = env::temp_dir().join("pathctl-test-confirm-save.rc");
+        let contents = upsert_managed_block("alias ll='ls -l'\n", "export PATH=\"/old\"");
+        std::fs::write(&rc, &contents).unwrap();
+        assert_eq!(managed_block(&contents), Some("export PATH=\"/old\""));
+
+        let mut app = App::new(vec![PathBuf::from("/new")]);
+        app.save_targets = vec![SaveTarget::File {
+            path: rc.clone(),
+            shell: "bash".to_string(),
+        }];
+        app.popup = Some(Popup::SaveTargets);
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+
+        assert_eq!(app.popup, Some(Popup::ConfirmSave));
+        assert_eq!(
+            app.save_diff,
+            vec![
+                DiffLine::Removed("export PATH=\"/old\"".to_string()),
+                DiffLine::Added("export PATH=\"/new\"".to_string()),
+            ]
+        );
+
+        app.handle_key(KeyEvent::new(KeyCode::Char('n'), KeyModifiers::NONE));
+        assert_eq!(app.popup, None);
+        assert_eq!(std::fs::read_to_string(&rc).unwrap(), contents);
+
+        std::fs::remove_file(&rc).unwrap();
+    }
+
+    #[test]
+    fn test_parse_launchctl_output() {
+        assert_eq!(
+            parse_launchctl_output("/usr/bin:/bin:/usr/sbin:/sbin\n"),
+            vec![
+                PathBuf::from("/usr/bin"),
+                PathBuf::from("/bin"),
+                PathBuf::from("/usr/sbin"),
+                PathBuf::from("/sbin"),
+            ]
+        );
+        assert!(parse_launchctl_output("").is_empty());
+        assert!(parse_launchctl_output("\n").is_empty());
+    }
+
+    #[test]
+    fn test_extract_dockerfile_path() {
+        let contents = "FROM debian:bookworm\n\
+            ENV LANG=C.UTF-8 PATH=\"/opt/tool/bin:$PATH\"\n\
+            RUN make install\n";
+        assert_eq!(
+            extract_dockerfile_path(contents),
+            Some("/opt/tool/bin:$PATH".to_string())
+        );
+
+        let contents = "FROM debian:bookworm\nenv PATH /usr/local/go/bin:${PATH}\n";
+        assert_eq!(
+            extract_dockerfile_path(contents),
+            Some("/usr/local/go/bin:${PATH}".to_string())
+        );
+
+        let contents = "FROM debian:bookworm\nENV GOPATH=/go\nENV PATHS /x\n";
+        assert_eq!(extract_dockerfile_path(contents), None);
+    }
+
+    #[test]
+    fn test_move_to_scratch_and_back() {
+        let mut app = App::new(vec![
+            PathBuf::from("/usr/bin"),
+            PathBuf::from("/bin"),
+            PathBuf::from("/usr/local/bin"),
+        ]);
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+
+        app.handle_key(key('>'));
+        assert_eq!(
+            app.paths,
+            vec![PathBuf::from("/bin"), PathBuf::from("/usr/local/bin")]
+        );
+        assert_eq!(app.scratch, vec![PathBuf::from("/usr/bin")]);
+        assert_eq!(app.list_state.selected(), Some(0));
+
+        app.handle_key(key('j'));
+        app.handle_key(key('<'));
+        assert_eq!(
+            app.paths,
+            vec![
+                PathBuf::from("/bin"),
+                PathBuf::from("/usr/local/bin"),
+                PathBuf::from("/usr/bin"),
+            ]
+        );
+        assert!(app.scratch.is_empty());
+        assert_eq!(app.list_state.selected(), Some(2));
+    }
+
+    #[test]
+    fn test_compare_recency() {
+        let epoch = SystemTime::UNIX_EPOCH;
+        let old = Some(epoch + Duration::from_secs(10));
+        let new = Some(epoch + Duration::from_secs(20));
+        let mut times = vec![None, old, new, None, old];
+
+        times.sort_by(|a, b| compare_recency(*a, *b));
+
+        assert_eq!(times, vec![new, old, old, None, None]);
+    }
+
+    #[test]
+    fn test_preview_move_to_front() {
+        let paths = vec![
+            PathBuf::from("/usr/bin"),
+            PathBuf::from("/bin"),
+            PathBuf::from("/usr/local/bin"),
+        ];
+
+        let preview = preview_move_to_front(&paths, 2);
+
+        assert_eq!(
+            preview,
+            vec![
+                PathBuf::from("/usr/local/bin"),
+                PathBuf::from("/usr/bin"),
+                PathBuf::from("/bin"),
+            ]
+        );
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("/usr/bin"),
+                PathBuf::from("/bin"),
+                PathBuf::from("/usr/local/bin"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_expand_entries() {
+        env::set_var("PATHCTL_TEST_TOOL", "/opt/tool");
+        env::remove_var("PATHCTL_TEST_UNDEFINED");
+        let mut paths = vec![
+            PathBuf::from("$PATHCTL_TEST_TOOL/bin"),
+            PathBuf::from("${PATHCTL_TEST_TOOL}/sbin"),
+            PathBuf::from("%PATHCTL_TEST_TOOL%/lib"),
+            PathBuf::from("$PATHCTL_TEST_UNDEFINED/bin"),
+            PathBuf::from("%PATHCTL_TEST_UNDEFINED%/bin"),
+            PathBuf::from("/usr/bin"),
+            PathBuf::from("/price/$5"),
+        ];
+
+        expand_entries(&mut paths);
+
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("/opt/tool/bin"),
+                PathBuf::from("/opt/tool/sbin"),
+                PathBuf::from("/opt/tool/lib"),
+                PathBuf::from("$PATHCTL_TEST_UNDEFINED/bin"),
+                PathBuf::from("%PATHCTL_TEST_UNDEFINED%/bin"),
+                PathBuf::from("/usr/bin"),
+                PathBuf::from("/price/$5"),
+            ]
+        );
+        env::remove_var("PATHCTL_TEST_TOOL");
+    }
+
+    #[test]
+    fn test_split_joined_entry() {
+        assert_eq!(
+            split_joined_entry(Path::new("/a:/b:/c"), ':'),
+            vec![
+                PathBuf::from("/a"),
+                PathBuf::from("/b"),
+                PathBuf::from("/c")
+            ]
+        );
+        assert_eq!(
+            split_joined_entry(Path::new("/usr/bin"), ':'),
+            vec![PathBuf::from("/usr/bin")]
+        );
+    }
+
+    #[test]
+    fn test_missing_reason() {
+        let root = env::temp_dir().join("pathctl-test-missing-reason");
+        std::fs::create_dir_all(&root).unwrap();
+
+        assert_eq!(
+            missing_reason(&root.join("bin")),
+            MissingReason::Missing(Some(root.clone()))
+        );
+        assert_eq!(
+            missing_reason(&root.join("tool").join("bin")),
+            MissingReason::Missing(Some(root.clone()))
+        );
+
+        let file = root.join("file");
+        std::fs::write(&file, "").unwrap();
+        assert_eq!(missing_reason(&file), MissingReason::NotADirectory);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_trim_entry() {
+        assert_eq!(
+            trim_entry(Path::new("  /usr/bin  ")),
+            PathBuf::from("/usr/bin")
+        );
+        assert_eq!(trim_entry(Path::new("/usr/bin")), PathBuf::from("/usr/bin"));
+    }
+
+    #[test]
+    fn test_file_uri() {
+        assert_eq!(
+            file_uri(Path::new("/usr/local/bin")),
+            "file:///usr/local/bin"
+        );
+        assert_eq!(file_uri(Path::new("/opt/my tool")), "file:///opt/my%20tool");
+    }
+
+    #[test]
+    fn test_hyperlinks() {
+        let dir = env::temp_dir().join("pathctl-test-links");
+        std::fs::create_dir_all(dir.join("a")).unwrap();
+        std::fs::create_dir_all(dir.join("b")).unwrap();
+        let linked = PathBuf::from("/");
+        let mut app = App::new(vec![
+            linked.clone(),
+            dir.join("a"),
+            dir.join("b"),
+            PathBuf::from("/pathctl-test-links-missing"),
+        ]);
+        app.hyperlinks = true;
+        app.show_numbers = true;
+        app.grouped = true;
+
+        let backend = tui::backend::TestBackend::new(80, 24);
+        let mut terminal = Terminal::new(backend).unwrap();
+        let frame = terminal.draw(|f| draw(f, &mut app)).unwrap();
+
+        // Only the plain existing entry is linked, past the highlight symbol
+        // and the number column; the folded and the missing row are not
+        let area = app.list_area;
+        assert_eq!(
+            app.links,
+            vec![(Rect::new(area.x + 3 + 2, area.y, 1, 1), linked.clone())]
+        );
+
+        // The buffer keeps one column per cell, the escapes only go out after
+        let row: String = (area.left()..area.right())
+            .map(|x| frame.buffer.get(x, area.y).symbol.as_str())
+            .collect();
+        assert!(row.starts_with(">> 1 / "), "{}", row);
+        let cells = hyperlink_cells(frame.buffer, &app.links);
+        assert_eq!(cells.len(), 1);
+        assert_eq!(cells[0].2.symbol, "\x1b]8;;file:///\x1b\\/\x1b]8;;\x1b\\");
+
+        // Nothing is linked under a popup drawn over the row
+        let mut covered = frame.buffer.clone();
+        covered.get_mut(area.x + 5, area.y).symbol = "x".to_string();
+        assert!(hyperlink_cells(&covered, &app.links).is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_sanitize_paste() {
+        assert_eq!(sanitize_paste("/usr/bin\n"), ("/usr/bin".to_string(), 0));
+        assert_eq!(
+            sanitize_paste("  /opt/a\tb \r\n"),
+            ("/opt/ab".to_string(), 0)
+        );
+        assert_eq!(
+            sanitize_paste("\n/usr/bin\n\n/bin\n/sbin"),
+            ("/usr/bin".to_string(), 2)
+        );
+        assert_eq!(sanitize_paste(""), (String::new(), 0));
+    }
+
+    #[test]
+    fn test_handle_paste() {
+        let dir = env::temp_dir().join("pathctl-test-paste");
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut app = App::new(vec![PathBuf::from("/usr/bin")]);
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+
+        // Pasting in normal mode does nothing, not even run commands
+        app.handle_paste("dd");
+        assert_eq!(app.paths, vec![PathBuf::from("/usr/bin")]);
+
+        // A trailing newline does not submit the input box
+        app.handle_key(key('a'));
+        app.handle_paste(&format!("{}\n", dir.display()));
+        assert!(matches!(app.input_mode, InputMode::InsertAfter));
+        assert_eq!(app.input, dir.display().to_string());
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert_eq!(app.paths, vec![PathBuf::from("/usr/bin"), dir.clone()]);
+
+        // The block editor keeps every line
+        app.handle_key(key('R'));
+        app.handle_paste("/usr/bin\r\n/bin\n");
+        assert_eq!(app.input, "/usr/bin\n/bin\n");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_insert_duplicate() {
+        let dir = env::temp_dir().join("pathctl-test-insert-duplicate");
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut app = App::new(vec![dir.clone(), PathBuf::from("/usr/bin")]);
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        app.handle_key(key('j'));
+
+        // The same directory with a trailing slash is refused
+        app.handle_key(key('a'));
+        for c in format!("{}/", dir.display()).chars() {
+            app.handle_key(key(c));
+        }
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert_eq!(app.paths.len(), 2);
+        assert_eq!(app.list_state.selected(), Some(0));
+        assert_eq!(
+            app.status,
+            Some(format!("{} is already in the list", display_entry(&dir)))
+        );
+        assert!(matches!(app.input_mode, InputMode::Normal));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_fit_layout() {
+        let fit = fit_layout(Rect::new(0, 0, 80, 24), false).unwrap();
+        assert!(fit.footer && fit.status && fit.side_panes);
+        assert_eq!(fit.margin, 1);
+
+        // Shorter terminals lose the margin, then the status bar, then the footer
+        let fit = fit_layout(Rect::new(0, 0, 80, 8), false).unwrap();
+        assert!(fit.footer && fit.status);
+        assert_eq!(fit.margin, 0);
+        let fit = fit_layout(Rect::new(0, 0, 80, 6), false).unwrap();
+        assert!(fit.footer && !fit.status);
+        let fit = fit_layout(Rect::new(0, 0, 40, 5), false).unwrap();
+        assert!(!fit.footer && !fit.side_panes);
+
+        assert!(fit_layout(Rect::new(0, 0, 80, 5), true).is_none());
+        assert!(fit_layout(Rect::new(0, 0, 10, 24), false).is_none());
+    }
+
+    #[test]
+    fn test_raw_view() {
+        let mut app = App::new(vec![
+            PathBuf::from("/usr/local/bin"),
+            PathBuf::from("/opt/off/bin"),
+            PathBuf::from("/usr/bin"),
+        ]);
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        app.handle_key(key('j'));
+        app.handle_key(key('x'));
+        app.handle_key(key('r'));
+
+        // The raw string leaves out the disabled entry, as the command does
+        let backend = tui::backend::TestBackend::new(80, 24);
+        let mut terminal = Terminal::new(backend).unwrap();
+        let frame = terminal.draw(|f| draw(f, &mut app)).unwrap();
+        let text: String = frame
+            .buffer
+            .content()
+            .iter()
+            .map(|cell| cell.symbol.as_str())
+            .collect();
+        let shown = join_entries(&[PathBuf::from("/usr/local/bin"), PathBuf::from("/usr/bin")]);
+        assert!(text.contains(&shown), "{}", text);
+        assert!(!text.contains("/opt/off/bin"));
+        assert!(app.command().contains(&shown));
+    }
+
+    #[test]
+    fn test_draw_small_terminals() {
+        let mut app = App::new(
+            (0..30)
+                .map(|i| PathBuf::from(format!("/opt/{}", i)))
+                .collect(),
+        );
+        app.show_details = true;
+        for (width, height) in [(80, 24), (30, 6), (20, 3), (5, 2), (0, 0)] {
+            let backend = tui::backend::TestBackend::new(width, height);
+            let mut terminal = Terminal::new(backend).unwrap();
+            terminal.draw(|f| draw(f, &mut app)).unwrap();
+            app.handle_key(KeyEvent::new(KeyCode::Char('a'), KeyModifiers::NONE));
+            terminal.draw(|f| draw(f, &mut app)).unwrap();
+            app.handle_key(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+        }
+    }
+
+    #[test]
+    fn test_scrollbar_thumb() {
+        // Everything fits: the thumb is the whole track
+        assert_eq!(scrollbar_thumb(0, 10, 5), (0, 10));
+        assert_eq!(scrollbar_thumb(0, 10, 100), (0, 1));
+        assert_eq!(scrollbar_thumb(90, 10, 100), (9, 1));
+        assert_eq!(scrollbar_thumb(0, 10, 20), (0, 5));
+        assert_eq!(scrollbar_thumb(5, 10, 20), (3, 5));
+        assert_eq!(scrollbar_thumb(10, 10, 20), (5, 5));
+    }
+
+    #[test]
+    fn test_list_offset() {
+        // Selection within the viewport keeps the offset
+        assert_eq!(list_offset(0, Some(3), 10, 50), 0);
+        // Scrolling down past the bottom row
+        assert_eq!(list_offset(0, Some(12), 10, 50), 3);
+        // Scrolling up past the top row
+        assert_eq!(list_offset(20, Some(5), 10, 50), 5);
+    }
+
+    #[test]
+    fn test_parse_block() {
+        let block = "/usr/local/bin\n\n  /usr/bin  \n\t\n/bin\n";
+
+        assert_eq!(
+            parse_block(block),
+            vec![
+                PathBuf::from("/usr/local/bin"),
+                PathBuf::from("/usr/bin"),
+                PathBuf::from("/bin"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cut_and_put() {
+        let mut app = App::new(vec![
+            PathBuf::from("/usr/bin"),
+            PathBuf::from("/bin"),
+            PathBuf::from("/usr/local/bin"),
+        ]);
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+
+        app.handle_key(key('d'));
+        app.handle_key(key('d'));
+        assert_eq!(
+            app.paths,
+            vec![PathBuf::from("/bin"), PathBuf::from("/usr/local/bin")]
+        );
+
+        app.handle_key(key('j'));
+        app.handle_key(key('p'));
+        assert_eq!(
+            app.paths,
+            vec![
+                PathBuf::from("/bin"),
+                PathBuf::from("/usr/local/bin"),
+                PathBuf::from("/usr/bin"),
+            ]
+        );
+        assert_eq!(app.list_state.selected(), Some(2));
+    }
+
+    #[test]
+    fn test_yank_and_put() {
+        let mut app = App::new(vec![
+            PathBuf::from("/usr/bin"),
+            PathBuf::from("/bin"),
+            PathBuf::from("/usr/local/bin"),
+        ]);
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+
+        app.handle_key(key('y'));
+        app.handle_key(key('y'));
+        app.handle_key(key('j'));
+        app.handle_key(key('j'));
+        assert_eq!(app.clipboard.take().as_deref(), Some("/usr/bin"));
+        app.handle_key(key('p'));
+        assert_eq!(app.paths.len(), 4);
+        assert_eq!(app.paths[3], PathBuf::from("/usr/bin"));
+
+        app.handle_key(key('Y'));
+        assert_eq!(app.clipboard.take(), Some(join_entries(&app.paths)));
+
+        // Marked entries are yanked together and put back in order
+        app.list_state.select(Some(0));
+        app.handle_key(key(' '));
+        app.handle_key(key(' '));
+        app.handle_key(key('y'));
+        app.handle_key(key('y'));
+        assert!(app.marks.is_empty());
+        app.handle_key(key('P'));
+        assert_eq!(
+            app.paths,
+            vec![
+                PathBuf::from("/usr/bin"),
+                PathBuf::from("/bin"),
+                PathBuf::from("/usr/bin"),
+                PathBuf::from("/bin"),
+                PathBuf::from("/usr/local/bin"),
+                PathBuf::from("/usr/bin"),
+            ]
+        );
+        assert_eq!(app.list_state.selected(), Some(2));
+    }
+
+    #[test]
+    fn test_jump_and_page() {
+        let paths = (0..30).map(|i| PathBuf::from(format!("/p{}", i))).collect();
+        let mut app = App::new(paths);
+        app.list_area = Rect::new(1, 1, 40, 10);
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        let ctrl = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL);
+        let code = |code| KeyEvent::new(code, KeyModifiers::NONE);
+
+        app.handle_key(key('G'));
+        assert_eq!(app.list_state.selected(), Some(29));
+        app.handle_key(key('g'));
+        app.handle_key(key('g'));
+        assert_eq!(app.list_state.selected(), Some(0));
+
+        app.handle_key(code(KeyCode::PageDown));
+        assert_eq!(app.list_state.selected(), Some(10));
+        app.handle_key(ctrl('d'));
+        assert_eq!(app.list_state.selected(), Some(15));
+        app.handle_key(ctrl('u'));
+        app.handle_key(code(KeyCode::PageUp));
+        assert_eq!(app.list_state.selected(), Some(0));
+        app.handle_key(code(KeyCode::End));
+        app.handle_key(code(KeyCode::PageDown));
+        assert_eq!(app.list_state.selected(), Some(29));
+        app.handle_key(code(KeyCode::Home));
+        assert_eq!(app.list_state.selected(), Some(0));
+
+        // Pages move through the entries shown, and none of this edits
+        app.handle_key(key('/'));
+        app.handle_key(key('2'));
+        app.handle_key(code(KeyCode::Enter));
+        app.handle_key(key('G'));
+        assert_eq!(app.list_state.selected(), Some(29));
+        app.handle_key(code(KeyCode::PageUp));
+        assert_eq!(app.list_state.selected(), Some(12));
+        assert_eq!(app.history.len(), 0);
+    }
+
+    #[test]
+    fn test_mouse() {
+        let mut app = App::new((0..20).map(|i| PathBuf::from(format!("/p{}", i))).collect());
+        app.list_area = Rect::new(1, 1, 40, 5);
+        app.list_offset = 10;
+        let mouse = |kind, row| MouseEvent {
+            kind,
+            column: 5,
+            row,
+            modifiers: KeyModifiers::NONE,
+        };
+
+        app.handle_mouse(mouse(MouseEventKind::Down(MouseButton::Left), 3));
+        assert_eq!(app.list_state.selected(), Some(12));
+        app.handle_mouse(mouse(MouseEventKind::Drag(MouseButton::Left), 2));
+        app.handle_mouse(mouse(MouseEventKind::Drag(MouseButton::Left), 1));
+        app.handle_mouse(mouse(MouseEventKind::Up(MouseButton::Left), 1));
+        assert_eq!(app.paths[10], PathBuf::from("/p12"));
+        assert_eq!(app.paths[11], PathBuf::from("/p10"));
+        assert_eq!(app.list_state.selected(), Some(10));
+        assert_eq!(app.history.len(), 1);
+
+        // Clicks on the border select nothing
+        app.handle_mouse(mouse(MouseEventKind::Down(MouseButton::Left), 6));
+        assert_eq!(app.list_state.selected(), Some(10));
+
+        app.handle_mouse(mouse(MouseEventKind::ScrollDown, 0));
+        assert_eq!(app.list_state.selected(), Some(13));
+        app.handle_mouse(mouse(MouseEventKind::ScrollUp, 0));
+        assert_eq!(app.list_state.selected(), Some(10));
+
+        // The mandatory first entry cannot be dragged down, nor pushed down
+        // by dragging another entry above it
+        app.mandatory_first = Some(PathBuf::from("/p0"));
+        app.list_offset = 0;
+        app.handle_mouse(mouse(MouseEventKind::Down(MouseButton::Left), 1));
+        app.handle_mouse(mouse(MouseEventKind::Drag(MouseButton::Left), 3));
+        app.handle_mouse(mouse(MouseEventKind::Up(MouseButton::Left), 3));
+        assert_eq!(app.paths[0], PathBuf::from("/p0"));
+        assert_eq!(app.list_state.selected(), Some(0));
+        assert_eq!(app.history.len(), 1);
+        app.handle_mouse(mouse(MouseEventKind::Down(MouseButton::Left), 3));
+        app.handle_mouse(mouse(MouseEventKind::Drag(MouseButton::Left), 1));
+        app.handle_mouse(mouse(MouseEventKind::Up(MouseButton::Left), 1));
+        assert_eq!(app.paths[0], PathBuf::from("/p0"));
+        assert_eq!(app.paths[1], PathBuf::from("/p2"));
+        assert_eq!(app.list_state.selected(), Some(1));
+        assert_eq!(app.history.len(), 2);
+    }
+
+    #[test]
+    fn test_count_prefix() {
+        let paths = (0..10).map(|i| PathBuf::from(format!("/p{}", i))).collect();
+        let mut app = App::new(paths);
+        let keys = |app: &mut App, keys: &str| {
+            for c in keys.chars() {
+                app.handle_key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
+            }
+        };
+
+        keys(&mut app, "5j");
+        assert_eq!(app.list_state.selected(), Some(5));
+        keys(&mut app, "12j");
+        assert_eq!(app.list_state.selected(), Some(9));
+        keys(&mut app, "2k");
+        assert_eq!(app.list_state.selected(), Some(7));
+
+        keys(&mut app, "3K");
+        assert_eq!(app.paths[4], PathBuf::from("/p7"));
+        assert_eq!(app.list_state.selected(), Some(4));
+
+        keys(&mut app, "3dd");
+        assert_eq!(
+            app.register,
+            vec![
+                PathBuf::from("/p7"),
+                PathBuf::from("/p4"),
+                PathBuf::from("/p5"),
+            ]
+        );
+        assert_eq!(app.paths.len(), 7);
+        assert_eq!(app.paths[4], PathBuf::from("/p6"));
+
+        // The count only applies to the next command
+        keys(&mut app, "2xj");
+        assert_eq!(app.list_state.selected(), Some(5));
+    }
+
+    #[test]
+    fn test_executable_count_cache() {
+        let dir = env::temp_dir().join("pathctl-test-count-cache");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let missing = dir.join("missing");
+        let mut cache = ExecutableCountCache::new();
+        let paths = vec![dir.clone(), missing.clone()];
+
+        cache.request(&paths);
+        let started = Instant::now();
+        while cache.get(&missing).is_none() && started.elapsed() < Duration::from_secs(5) {
+            thread::sleep(Duration::from_millis(10));
+            cache.request(&paths);
+        }
+        assert_eq!(cache.get(&dir), Some(Some(0)));
+        assert_eq!(cache.get(&missing), Some(None));
+        assert!(cache.scanning.is_empty());
+
+        cache.clear();
+        assert_eq!(cache.get(&dir), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_relative_entries() {
+        for entry in ["", ".", "bin", "../tools", "node_modules/.bin"] {
+            assert!(is_relative_entry(Path::new(entry)), "{:?}", entry);
+        }
+        assert!(!is_relative_entry(Path::new("/usr/bin")));
+
+        let cwd = Path::new("/home/user/project");
+        assert_eq!(absolutize(Path::new(""), cwd), cwd);
+        assert_eq!(absolutize(Path::new("."), cwd), cwd);
+        assert_eq!(
+            absolutize(Path::new("../tools"), cwd),
+            Path::new("/home/user/tools")
+        );
+        assert_eq!(
+            absolutize(Path::new("/usr/bin"), cwd),
+            Path::new("/usr/bin")
+        );
+
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        let paths = || {
+            vec![
+                PathBuf::from("/usr/bin"),
+                PathBuf::from("."),
+                PathBuf::new(),
+            ]
+        };
+        let mut app = App::new(paths());
+        app.handle_key(key('z'));
+        assert_eq!(app.popup, Some(Popup::Relative));
+        app.handle_key(key('r'));
+        assert_eq!(app.paths, vec![PathBuf::from("/usr/bin")]);
+
+        let mut app = App::new(paths());
+        app.handle_key(key('z'));
+        app.handle_key(key('a'));
+        let cwd = env::current_dir().unwrap();
+        assert_eq!(app.paths, vec![PathBuf::from("/usr/bin"), cwd.clone(), cwd]);
+
+        app.handle_key(key('z'));
+        assert_eq!(app.popup, None);
+        assert_eq!(app.status.as_deref(), Some("no relative entries"));
+    }
+
+    #[test]
+    fn test_open_command() {
+        let dir = Path::new("/usr/bin");
+        assert_eq!(
+            open_command(Opener::Editor, dir, Some("code -w".as_ref())),
+            Some(vec!["code".into(), "-w".into(), "/usr/bin".into()])
+        );
+        assert_eq!(open_command(Opener::Editor, dir, None), None);
+        assert_eq!(open_command(Opener::Editor, dir, Some(" ".as_ref())), None);
+        let opener = open_command(Opener::FileManager, dir, None).unwrap();
+        assert_eq!(opener.last(), Some(&OsString::from("/usr/bin")));
+
+        let missing = env::temp_dir().join("pathctl-test-open-missing");
+        let mut app = App::new(vec![missing]);
+        app.handle_key(KeyEvent::new(KeyCode::Char('g'), KeyModifiers::NONE));
+        app.handle_key(KeyEvent::new(KeyCode::Char('x'), KeyModifiers::NONE));
+        assert_eq!(app.to_open, None);
+        assert!(app.status.is_some());
+
+        let mut app = App::new(vec![env::temp_dir()]);
+        app.handle_key(KeyEvent::new(KeyCode::Char('g'), KeyModifiers::NONE));
+        app.handle_key(KeyEvent::new(KeyCode::Char('x'), KeyModifiers::NONE));
+        assert_eq!(
+            app.to_open.and_then(|command| command.last().cloned()),
+            Some(env::temp_dir().into_os_string())
+        );
+    }
 
-    // Restore terminal
-    disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
-    terminal.show_cursor()?;
+    #[test]
+    fn test_fold_runs() {
+        let home = Some(Path::new("/home/me"));
+        assert_eq!(
+            fold_prefix(Path::new("/opt/a/bin"), home),
+            Some(PathBuf::from("/opt"))
+        );
+        assert_eq!(
+            fold_prefix(Path::new("/home/me/.asdf/shims"), home),
+            Some(PathBuf::from("/home/me/.asdf"))
+        );
+        assert_eq!(fold_prefix(Path::new("/"), home), None);
+        assert_eq!(fold_prefix(Path::new(""), home), None);
 
-    match res {
-        Ok(paths) => {
-            let command = generate_shell_command(&paths);
-            println!("{}", command);
-        }
-        Err(err) => {
-            eprintln!("Error: {:?}", err);
-        }
+        let paths: Vec<PathBuf> = [
+            "/home/me/.asdf/shims",
+            "/home/me/.asdf/bin",
+            "/opt/a/bin",
+            "/opt/b/bin",
+            "/opt/c/bin",
+            "/usr/bin",
+            "/opt/d/bin",
+        ]
+        .iter()
+        .map(PathBuf::from)
+        .collect();
+        assert_eq!(
+            fold_runs(&paths, home),
+            vec![
+                (PathBuf::from("/home/me/.asdf"), 0..2),
+                (PathBuf::from("/opt"), 2..5)
+            ]
+        );
+
+        let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+        let mut app = App::new(paths[2..].to_vec());
+        app.list_state.select(Some(1));
+        app.handle_key(key(KeyCode::Char('^')));
+        assert_eq!(app.visible(), vec![0, 3, 4]);
+        assert_eq!(app.list_state.selected(), Some(0));
+        app.handle_key(key(KeyCode::Right));
+        assert_eq!(app.visible(), vec![0, 1, 2, 3, 4]);
+        app.handle_key(key(KeyCode::Char('j')));
+        app.handle_key(key(KeyCode::Left));
+        assert_eq!(app.visible(), vec![0, 3, 4]);
+        assert_eq!(app.list_state.selected(), Some(0));
+        app.handle_key(key(KeyCode::Char('^')));
+        assert_eq!(app.visible().len(), 5);
     }
 
-    Ok(())
-}
+    #[test]
+    fn test_suggested_dirs() {
+        let root = env::temp_dir().join("pathctl-test-suggest");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("listed")).unwrap();
+        std::fs::create_dir_all(root.join("tools")).unwrap();
+        let opts = DedupeOpts::default();
+        let paths = vec![root.join("listed")];
+        let extra = vec![
+            root.join("listed"),
+            root.join("tools"),
+            root.join("missing"),
+            root.join("tools"),
+        ];
+        let suggested = suggested_dirs(&paths, &extra, &opts);
+        assert_eq!(suggested.last(), Some(&root.join("tools")));
+        assert!(!suggested.contains(&root.join("listed")));
+        assert_eq!(
+            suggested
+                .iter()
+                .filter(|dir| **dir == root.join("tools"))
+                .count(),
+            1
+        );
 
-fn run_app<B: Backend>(terminal: &mut Terminal<B>) -> io::Result<Vec<PathBuf>> {
-    let mut paths = get_path_entries();
-    let mut list_state = ListState::default();
-    if !paths.is_empty() {
-        list_state.select(Some(0));
+        let mut app = App::new(paths);
+        app.extra_suggestions = extra;
+        app.handle_key(KeyEvent::new(KeyCode::Char('t'), KeyModifiers::NONE));
+        assert_eq!(app.popup, Some(Popup::Suggest));
+        let last = app.suggestions.len();
+        app.suggest_choice = last - 1;
+        app.handle_key(KeyEvent::new(KeyCode::Char('A'), KeyModifiers::SHIFT));
+        assert_eq!(app.paths, vec![root.join("listed"), root.join("tools")]);
+        assert_eq!(app.suggestions.len(), last - 1);
+        std::fs::remove_dir_all(&root).unwrap();
     }
-    let mut input_mode = InputMode::Normal;
-    let mut input = String::new();
 
-    loop {
-        terminal.draw(|f| draw(f, &paths, &mut list_state, &input_mode, &input))?;
-
-        if event::poll(std::time::Duration::from_millis(100))? {
-            if let Event::Key(key) = event::read()? {
-                match input_mode {
-                    InputMode::Normal => {
-                        // Handle quitting the application
-                        if key.code == KeyCode::Char('q')
-                            || key.code == KeyCode::Esc
-                            || (key.code == KeyCode::Char('c')
-                                && key.modifiers == KeyModifiers::CONTROL)
-                        {
-                            return Ok(paths);
-                        }
+    #[test]
+    fn test_browse() {
+        let root = env::temp_dir().join("pathctl-test-browse");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("tools/bin")).unwrap();
+        std::fs::create_dir_all(root.join(".cargo/bin")).unwrap();
+        std::fs::write(root.join("notes.txt"), "").unwrap();
+        assert_eq!(
+            subdirectories(&root).unwrap(),
+            vec![root.join(".cargo"), root.join("tools")]
+        );
 
-                        match key.code {
-                            KeyCode::Char('a') => {
-                                // Enter input mode to insert after
-                                input_mode = InputMode::InsertAfter;
-                                input.clear();
-                            }
-                            KeyCode::Char('b') => {
-                                // Enter input mode to insert before
-                                input_mode = InputMode::InsertBefore;
-                                input.clear();
-                            }
-                            KeyCode::Char('d') => {
-                                if let Some(selected) = list_state.selected() {
-                                    paths.remove(selected);
-                                    let new_index = if selected >= paths.len() {
-                                        paths.len().saturating_sub(1)
-                                    } else {
-                                        selected
-                                    };
-                                    if paths.is_empty() {
-                                        list_state.select(None);
-                                    } else {
-                                        list_state.select(Some(new_index));
-                                    }
-                                }
-                            }
-                            KeyCode::Up | KeyCode::Char('k') => {
-                                let i = match list_state.selected() {
-                                    Some(i) => {
-                                        if i > 0 {
-                                            Some(i - 1)
-                                        } else {
-                                            Some(0)
-                                        }
-                                    }
-                                    None => Some(0),
-                                };
-                                list_state.select(i);
-                            }
-                            KeyCode::Down | KeyCode::Char('j') => {
-                                let i = match list_state.selected() {
-                                    Some(i) => {
-                                        if i < paths.len() - 1 {
-                                            Some(i + 1)
-                                        } else {
-                                            Some(paths.len() - 1)
-                                        }
-                                    }
-                                    None => Some(0),
-                                };
-                                list_state.select(i);
-                            }
-                            _ => {}
-                        }
-                    }
-                    InputMode::InsertAfter => {
-                        handle_input_mode(
-                            key,
-                            &mut input,
-                            &mut input_mode,
-                            &mut paths,
-                            &mut list_state,
-                            InsertionPoint::After,
-                        );
-                    }
-                    InputMode::InsertBefore => {
-                        handle_input_mode(
-                            key,
-                            &mut input,
-                            &mut input_mode,
-                            &mut paths,
-                            &mut list_state,
-                            InsertionPoint::Before,
-                        );
-                    }
-                }
-            }
-        }
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        let mut app = App::new(vec![PathBuf::from("/usr/bin")]);
+        app.browse(root.clone());
+        app.popup = Some(Popup::Browse);
+        app.handle_key(key('j'));
+        app.handle_key(key('l'));
+        assert_eq!(app.browse_dir, root.join("tools"));
+        app.handle_key(key('h'));
+        assert_eq!(app.browse_dir, root);
+        assert_eq!(app.browse_choice, 1);
+        app.handle_key(key('l'));
+        app.handle_key(key('b'));
+        assert_eq!(app.popup, None);
+        assert_eq!(
+            app.paths,
+            vec![root.join("tools/bin"), PathBuf::from("/usr/bin")]
+        );
+        std::fs::remove_dir_all(&root).unwrap();
     }
-}
 
-fn handle_input_mode(
-    key: event::KeyEvent,
-    input: &mut String,
-    input_mode: &mut InputMode,
-    paths: &mut Vec<PathBuf>,
-    list_state: &mut ListState,
-    insertion_point: InsertionPoint,
-) {
-    match key.code {
-        KeyCode::Enter => {
-            let new_path = PathBuf::from(input.trim());
-            if new_path.exists() {
-                insert_path_at_selection(paths, list_state, new_path, insertion_point);
+    #[test]
+    fn test_macros() {
+        let paths = ["/usr/bin", "/bin", "/opt/a/bin", "/opt/b/bin"];
+        let mut app = App::new(paths.iter().map(PathBuf::from).collect());
+        let keys = |app: &mut App, keys: &str| {
+            for c in keys.chars() {
+                app.handle_key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
             }
-            input.clear();
-            *input_mode = InputMode::Normal;
-        }
-        KeyCode::Esc => {
-            input.clear();
-            *input_mode = InputMode::Normal;
-        }
-        KeyCode::Char(c) => {
-            input.push(c);
-        }
-        KeyCode::Backspace => {
-            input.pop();
-        }
-        _ => {}
-    }
-}
+        };
 
-#[derive(Clone, Copy)]
-enum InputMode {
-    Normal,
-    InsertAfter,
-    InsertBefore,
-}
+        let order =
+            |app: &App| -> Vec<String> { app.paths.iter().map(|p| display_entry(p)).collect() };
 
-#[derive(Clone, Copy)]
-enum InsertionPoint {
-    Before,
-    After,
-}
+        keys(&mut app, "GQaK");
+        assert!(app.recording.is_some());
+        keys(&mut app, "Q");
+        assert_eq!(app.recording, None);
+        assert_eq!(app.macros[&'a'].len(), 1);
+        assert_eq!(
+            order(&app),
+            ["/usr/bin", "/bin", "/opt/b/bin", "/opt/a/bin"]
+        );
 
-fn insert_path_at_selection(
-    paths: &mut Vec<PathBuf>,
-    list_state: &mut ListState,
-    new_path: PathBuf,
-    insertion_point: InsertionPoint,
-) {
-    let selected_index = list_state.selected().unwrap_or(0);
-    let insert_index = match insertion_point {
-        InsertionPoint::Before => selected_index,
-        InsertionPoint::After => selected_index + 1,
-    };
-    let insert_index = insert_index.min(paths.len()); // Ensure we don't go out of bounds
-    paths.insert(insert_index, new_path);
-    list_state.select(Some(insert_index));
-}
+        keys(&mut app, "@a");
+        keys(&mut app, "@@");
+        assert_eq!(
+            order(&app),
+            ["/opt/b/bin", "/usr/bin", "/bin", "/opt/a/bin"]
+        );
 
-fn draw<B: Backend>(
-    f: &mut Frame<B>,
-    paths: &[PathBuf],
-    list_state: &mut ListState,
-    input_mode: &InputMode,
-    input: &str,
-) {
-    let size = f.size();
+        // A replay is undone in one step
+        keys(&mut app, "G2@a");
+        assert_eq!(
+            order(&app),
+            ["/opt/b/bin", "/opt/a/bin", "/usr/bin", "/bin"]
+        );
+        keys(&mut app, "u");
+        assert_eq!(
+            order(&app),
+            ["/opt/b/bin", "/usr/bin", "/bin", "/opt/a/bin"]
+        );
 
-    // Adjust layout to include commands footer
-    let constraints = match input_mode {
-        InputMode::Normal => vec![
-            Constraint::Min(1),    // List of paths
-            Constraint::Length(3), // Commands footer
-        ],
-        InputMode::InsertAfter | InputMode::InsertBefore => vec![
-            Constraint::Min(1),
-            Constraint::Length(3), // Input box
-            Constraint::Length(3), // Commands footer
-        ],
-    };
+        keys(&mut app, "@z");
+        assert_eq!(app.status.as_deref(), Some("no macro z"));
+    }
 
-    let chunks = Layout::default()
-        .direction(Direction::Vertical)
-        .margin(1)
-        .constraints(constraints)
-        .split(size);
+    #[test]
+    fn test_jump_to_number() {
+        let paths = (1..=12)
+            .map(|i| PathBuf::from(format!("/p{}", i)))
+            .collect();
+        let mut app = App::new(paths);
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
 
-    // Create the list items
-    let items: Vec<ListItem> = paths
-        .iter()
-        .map(|p| ListItem::new(p.display().to_string()))
-        .collect();
+        app.handle_key(key('1'));
+        app.handle_key(key('1'));
+        app.handle_key(key('G'));
+        assert_eq!(app.list_state.selected(), Some(10));
+        app.handle_key(key('3'));
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert_eq!(app.list_state.selected(), Some(2));
 
-    // Create the list widget
-    let list = List::new(items)
-        .block(Block::default().borders(Borders::ALL).title("PATH Entries"))
-        .highlight_style(
-            Style::default()
-                .fg(Color::Yellow)
-                .add_modifier(Modifier::BOLD),
-        )
-        .highlight_symbol(">> ");
+        // Out of range numbers leave the selection alone
+        app.handle_key(key('4'));
+        app.handle_key(key('0'));
+        app.handle_key(key('G'));
+        assert_eq!(app.list_state.selected(), Some(2));
+        assert_eq!(app.status.as_deref(), Some("no entry 40"));
 
-    // Render the list widget with the ListState
-    f.render_stateful_widget(list, chunks[0], list_state);
+        // A search hiding the entry is dropped
+        app.search = Some("p1".to_string());
+        app.handle_key(key('5'));
+        app.handle_key(key('G'));
+        assert_eq!(app.list_state.selected(), Some(4));
+        assert_eq!(app.search, None);
 
-    // Determine which additional widget to render based on input mode
-    let mut commands_chunk_index = 1;
-    match input_mode {
-        InputMode::Normal => {}
-        InputMode::InsertAfter => {
-            let input_block = Paragraph::new(input)
-                .style(Style::default().fg(Color::Cyan))
-                .block(Block::default().borders(Borders::ALL).title("Insert After"));
-            f.render_widget(input_block, chunks[1]);
-            f.set_cursor(chunks[1].x + input.len() as u16 + 1, chunks[1].y + 1);
-            commands_chunk_index = 2;
-        }
-        InputMode::InsertBefore => {
-            let input_block = Paragraph::new(input)
-                .style(Style::default().fg(Color::Cyan))
-                .block(
-                    Block::default()
-                        .borders(Borders::ALL)
-                        .title("Insert Before"),
-                );
-            f.render_widget(input_block, chunks[1]);
-            f.set_cursor(chunks[1].x + input.len() as u16 + 1, chunks[1].y + 1);
-            commands_chunk_index = 2;
-        }
+        // Without a count G still goes to the last entry
+        app.handle_key(key('G'));
+        assert_eq!(app.list_state.selected(), Some(11));
     }
 
-    // Render the commands footer
-    let commands = vec![Spans::from(vec![
-        Span::styled("a", Style::default().add_modifier(Modifier::BOLD)),
-        Span::raw(": Insert after   "),
-        Span::styled("b", Style::default().add_modifier(Modifier::BOLD)),
-        Span::raw(": Insert before   "),
-        Span::styled("d", Style::default().add_modifier(Modifier::BOLD)),
-        Span::raw(": Delete   "),
-        Span::styled("↑/k", Style::default().add_modifier(Modifier::BOLD)),
-        Span::raw(": Up   "),
-        Span::styled("↓/j", Style::default().add_modifier(Modifier::BOLD)),
-        Span::raw(": Down   "),
-        Span::styled(
-            "q/ESC/Ctrl+C",
-            Style::default().add_modifier(Modifier::BOLD),
-        ),
-        Span::raw(": Quit"),
-    ])];
-
-    let commands_paragraph = Paragraph::new(commands).block(Block::default().borders(Borders::ALL));
+    #[test]
+    fn test_confirm_delete() {
+        let mut app = App::new(vec![PathBuf::from("/usr/bin"), PathBuf::from("/bin")]);
+        app.confirm_delete = true;
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
 
-    f.render_widget(commands_paragraph, chunks[commands_chunk_index]);
-}
+        app.handle_key(key('d'));
+        app.handle_key(key('d'));
+        assert_eq!(app.popup, Some(Popup::ConfirmDelete));
+        app.handle_key(key('n'));
+        assert_eq!(app.popup, None);
+        assert_eq!(app.paths.len(), 2);
 
-fn get_path_entries() -> Vec<PathBuf> {
-    #[cfg(target_os = "windows")]
-    {
-        get_windows_path_entries()
+        app.handle_key(key('2'));
+        app.handle_key(key('d'));
+        app.handle_key(key('d'));
+        app.handle_key(key('y'));
+        assert!(app.paths.is_empty());
+        assert_eq!(app.register.len(), 2);
+        assert_eq!(app.history.len(), 1);
     }
-    #[cfg(not(target_os = "windows"))]
-    {
-        if let Ok(path_var) = env::var("PATH") {
-            env::split_paths(&path_var).collect()
-        } else {
-            vec![]
+
+    #[test]
+    fn test_keymap() {
+        for (_, bindings) in KEYMAP {
+            for binding in bindings.iter() {
+                for key in binding.keys {
+                    // Key names, or sequences of single keys such as `gg`
+                    let named = parse_key_name(&key.replace('↑', "Up").replace('↓', "Down"));
+                    let typed = || {
+                        key.chars()
+                            .all(|c| parse_key_name(&c.to_string()).is_some())
+                    };
+                    assert!(named.is_some() || typed() || *key == "1-9", "{}", key);
+                }
+            }
+        }
+        for key in FOOTER {
+            assert!(binding_for(key).is_some(), "{}", key);
         }
+        let defaults: HashSet<KeyId> = ACTIONS
+            .iter()
+            .map(|(action, key)| key_id(&parse_key_name(key).expect(action)))
+            .collect();
+        assert_eq!(defaults.len(), ACTIONS.len());
     }
-}
 
-fn generate_shell_command(paths: &[PathBuf]) -> String {
-    let new_path_var = env::join_paths(paths).expect("Failed to join paths");
-    let new_path_str = new_path_var.to_string_lossy();
+    #[test]
+    fn test_tab_completion() {
+        let root = env::temp_dir().join("pathctl-test-complete");
+        let _ = std::fs::remove_dir_all(&root);
+        for dir in ["alpha", "alpine", "beta", ".alps"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        std::fs::write(root.join("alps.txt"), "").unwrap();
+        let prefix = format!("{}{}", root.display(), std::path::MAIN_SEPARATOR);
+        let sep = std::path::MAIN_SEPARATOR;
 
-    // Detect the shell to output appropriate commands
-    let shell = detect_shell();
-    match shell.as_deref() {
-        Some("fish") => format!("set -x PATH {}", new_path_str),
-        _ => format!("export PATH=\"{}\"", new_path_str),
+        assert_eq!(
+            complete_dir(&format!("{}al", prefix)),
+            vec![
+                format!("{}alpha{}", prefix, sep),
+                format!("{}alpine{}", prefix, sep),
+            ]
+        );
+        assert_eq!(complete_dir(&format!("{}.al", prefix)).len(), 1);
+        assert!(complete_dir(&format!("{}x", prefix)).is_empty());
+
+        let tab = KeyEvent::new(KeyCode::Tab, KeyModifiers::NONE);
+        let mut app = App::new(vec![PathBuf::from("/usr/bin")]);
+        app.handle_key(KeyEvent::new(KeyCode::Char('a'), KeyModifiers::NONE));
+        app.input = format!("{}al", prefix);
+        app.handle_key(tab);
+        assert_eq!(app.input, format!("{}alpha{}", prefix, sep));
+        app.handle_key(tab);
+        assert_eq!(app.input, format!("{}alpine{}", prefix, sep));
+        app.handle_key(KeyEvent::new(KeyCode::BackTab, KeyModifiers::SHIFT));
+        assert_eq!(app.input, format!("{}alpha{}", prefix, sep));
+
+        // Typing starts over, and a single match is just filled in
+        app.handle_key(KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE));
+        assert!(app.completions.is_empty());
+        app.input = format!("{}b", prefix);
+        app.handle_key(tab);
+        assert_eq!(app.input, format!("{}beta{}", prefix, sep));
+        assert!(app.completions.is_empty());
+
+        std::fs::remove_dir_all(&root).unwrap();
     }
-}
 
-#[cfg(target_os = "windows")]
-fn get_windows_path_entries() -> Vec<PathBuf> {
-    use winreg::enums::*;
-    use winreg::RegKey;
+    #[test]
+    fn test_sort_popup() {
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        let missing = env::temp_dir().join("pathctl-test-sort-missing");
+        let mut app = App::new(vec![
+            missing.clone(),
+            PathBuf::from("/usr/bin"),
+            env::temp_dir(),
+        ]);
+        app.list_state.select(Some(1));
 
-    let hkcu = RegKey::predef(HKEY_CURRENT_USER);
-    let env = hkcu.open_subkey("Environment").unwrap();
-    let path_var: String = env.get_value("Path").unwrap_or_default();
-    env::split_paths(&path_var).collect()
-}
+        // Cancelling leaves the list alone
+        app.handle_key(key('s'));
+        assert_eq!(app.popup, Some(Popup::Sort));
+        app.handle_key(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+        assert_eq!(app.paths[0], missing);
 
-#[cfg(not(target_os = "windows"))]
-fn detect_shell() -> Option<String> {
-    env::var("SHELL").ok().and_then(|path| {
-        Path::new(&path)
-            .file_name()
-            .and_then(|os_str| os_str.to_str())
-            .map(|s| s.to_string())
-    })
-}
+        app.handle_key(key('s'));
+        app.handle_key(key('j'));
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert_eq!(
+            app.paths,
+            vec![PathBuf::from("/usr/bin"), env::temp_dir(), missing]
+        );
+        assert_eq!(app.list_state.selected(), Some(0));
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::env;
-    use std::path::PathBuf;
+        app.sort_entries(SortOrder::Alphabetical);
+        let mut sorted = app.paths.clone();
+        sorted.sort();
+        assert_eq!(app.paths, sorted);
+    }
 
     #[test]
-    fn test_get_path_entries() {
-        // Backup the original PATH
-        let original_path = env::var("PATH").unwrap_or_default();
+    fn test_dedupe_key_reports_removed() {
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        let mut app = App::new(
+            ["/usr/bin", "/bin", "/usr/bin/", "/bin", "/sbin"]
+                .iter()
+                .map(PathBuf::from)
+                .collect(),
+        );
+        app.handle_key(key('D'));
+        assert_eq!(
+            app.paths,
+            vec![
+                PathBuf::from("/usr/bin"),
+                PathBuf::from("/bin"),
+                PathBuf::from("/sbin"),
+            ]
+        );
+        assert_eq!(app.status.as_deref(), Some("removed 2 duplicates"));
+        app.handle_key(key('D'));
+        assert_eq!(app.status.as_deref(), Some("no duplicates"));
+    }
 
-        // Set a known PATH value
-        let test_paths = ["/usr/bin", "/bin", "/usr/local/bin"];
-        let test_path_var = env::join_paths(test_paths.iter().map(PathBuf::from)).unwrap();
-        env::set_var("PATH", &test_path_var);
+    #[test]
+    fn test_prune_missing() {
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        let missing = env::temp_dir().join("pathctl-test-prune-missing");
+        let mut app = App::new(vec![
+            missing.clone(),
+            env::temp_dir(),
+            PathBuf::new(),
+            missing.join("bin"),
+        ]);
 
-        // Call the function
-        let paths = get_path_entries();
+        app.handle_key(key('M'));
+        assert_eq!(app.popup, Some(Popup::Prune));
+        assert_eq!(app.pending_delete, BTreeSet::from([0, 3]));
+        app.handle_key(key('y'));
+        assert_eq!(app.paths, vec![env::temp_dir(), PathBuf::new()]);
+        assert_eq!(app.status.as_deref(), Some("pruned 2 missing entries"));
 
-        // Verify the result
-        let expected_paths: Vec<PathBuf> = test_paths.iter().map(PathBuf::from).collect();
-        assert_eq!(paths, expected_paths);
+        app.handle_key(key('M'));
+        assert_eq!(app.popup, None);
+        assert_eq!(app.status.as_deref(), Some("no missing entries"));
+    }
 
-        // Restore the original PATH
-        env::set_var("PATH", &original_path);
+    #[test]
+    fn test_lone_d_does_not_delete() {
+        let mut app = App::new(vec![PathBuf::from("/usr/bin"), PathBuf::from("/bin")]);
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+
+        // Another key in between cancels the pending `d`
+        app.handle_key(key('d'));
+        app.handle_key(key('j'));
+        app.handle_key(key('d'));
+        assert_eq!(app.paths.len(), 2);
+        assert_eq!(app.list_state.selected(), Some(1));
+
+        // So does waiting too long
+        app.pending = Some(('d', Instant::now() - PENDING_TIMEOUT));
+        app.handle_key(key('d'));
+        assert_eq!(app.paths.len(), 2);
     }
 
     #[test]
-    fn test_generate_shell_command() {
-        // Prepare new paths
-        let new_paths = vec![PathBuf::from("/custom/bin"), PathBuf::from("/another/bin")];
+    fn test_drift_report() {
+        let opts = DedupeOpts::default();
+        let expected = parse_path_file("/usr/local/bin:/usr/bin:/bin\n");
 
-        // Generate the shell command
-        let command = generate_shell_command(&new_paths);
+        let same = expected.clone();
+        assert_eq!(drift_report(&expected, &same, false, &opts), None);
 
-        // Detect shell
-        let shell = detect_shell();
-        let expected_command = {
-            let new_path_var = env::join_paths(&new_paths).expect("Failed to join paths");
-            let new_path_str = new_path_var.to_string_lossy();
+        let reordered = vec![
+            PathBuf::from("/usr/bin"),
+            PathBuf::from("/usr/local/bin"),
+            PathBuf::from("/bin"),
+        ];
+        assert_eq!(drift_report(&expected, &reordered, true, &opts), None);
+        assert!(drift_report(&expected, &reordered, false, &opts).is_some());
 
-            match shell.as_deref() {
-                Some("fish") => format!("set -x PATH {}", new_path_str),
-                _ => format!("export PATH=\"{}\"", new_path_str),
-            }
-        };
+        let different = vec![
+            PathBuf::from("/opt/bin"),
+            PathBuf::from("/usr/local/bin"),
+            PathBuf::from("/usr/bin"),
+        ];
+        assert_eq!(
+            drift_report(&expected, &different, true, &opts).as_deref(),
+            Some("+ /opt/bin\n- /bin")
+        );
+    }
 
-        // Verify the command
-        assert_eq!(command, expected_command);
+    #[test]
+    fn test_diff_paths() {
+        let base = vec![
+            PathBuf::from("/usr/bin"),
+            PathBuf::from("/bin"),
+            PathBuf::from("/usr/sbin"),
+            PathBuf::from("/sbin"),
+        ];
+        let current = vec![
+            PathBuf::from("/home/me/.cargo/bin"),
+            PathBuf::from("/bin"),
+            PathBuf::from("/usr/bin"),
+            PathBuf::from("/usr/sbin"),
+        ];
+
+        let opts = DedupeOpts::default();
+        let diff = diff_paths(&base, &current, &opts);
+
+        assert_eq!(
+            diff.tags,
+            vec![
+                DiffTag::Added,
+                DiffTag::Unchanged,
+                DiffTag::Moved,
+                DiffTag::Unchanged,
+            ]
+        );
+        assert_eq!(diff.removed, vec![PathBuf::from("/sbin")]);
+        assert_eq!(
+            classify_origins(&base, &current, &opts),
+            vec![
+                Origin::Added,
+                Origin::Inherited,
+                Origin::Inherited,
+                Origin::Inherited,
+            ]
+        );
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_parse_path_file() {
+        let expected = vec![PathBuf::from("/usr/bin"), PathBuf::from("/bin")];
+        assert_eq!(parse_path_file("/usr/bin:/bin\n"), expected);
+        assert_eq!(parse_path_file("/usr/bin\n/bin\n"), expected);
     }
 
     #[test]
@@ -458,6 +10466,26 @@ mod tests {
         ];
         assert_eq!(paths, expected_paths_after);
         assert_eq!(list_state.selected(), Some(2));
+
+        // Top and bottom ignore the selection
+        let top = PathBuf::from("/top/bin");
+        insert_path_at_selection(
+            &mut paths,
+            &mut list_state,
+            top.clone(),
+            InsertionPoint::First,
+        );
+        assert_eq!(paths[0], top);
+        assert_eq!(list_state.selected(), Some(0));
+        let bottom = PathBuf::from("/bottom/bin");
+        insert_path_at_selection(
+            &mut paths,
+            &mut list_state,
+            bottom.clone(),
+            InsertionPoint::Last,
+        );
+        assert_eq!(paths.last(), Some(&bottom));
+        assert_eq!(list_state.selected(), Some(6));
     }
 
     #[cfg(not(target_os = "windows"))]